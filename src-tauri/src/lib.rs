@@ -1,13 +1,24 @@
+use tauri::Wry;
+
+pub use tauri_plugin_krome as krome;
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
+// App builder with Krome's plugins registered. Apps can keep chaining
+// `.manage()`, `.plugin()` and their own `.invoke_handler()` on the result.
+pub fn builder() -> tauri::Builder<Wry> {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(krome::init(krome::Config::default()))
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    builder()
         .invoke_handler(tauri::generate_handler![greet])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,74 @@
+const COMMANDS: &[&str] = &[
+    "start_helios",
+    "stop_helios",
+    "restart_helios",
+    "helios_status",
+    "get_endpoint_health",
+    "get_networks",
+    "get_config",
+    "update_config",
+    "get_checkpoint",
+    "pin_checkpoint",
+    "unpin_checkpoint",
+    "export_checkpoint",
+    "import_checkpoint",
+    "get_latest_block",
+    "get_balance",
+    "get_transaction_count",
+    "get_code",
+    "get_storage_at",
+    "get_block_by_number",
+    "get_block_by_hash",
+    "get_transaction_by_hash",
+    "get_transaction_receipt",
+    "get_logs",
+    "gas_price",
+    "max_priority_fee",
+    "fee_history",
+    "chain_id",
+    "block_number",
+    "call",
+    "estimate_gas",
+    "eth_request",
+    "send_raw_transaction",
+    "get_tracked_transactions",
+    "get_tracked_transaction",
+    "forget_transaction",
+    "sign_transaction",
+    "send_transaction",
+    "personal_sign",
+    "sign_typed_data",
+    "recover_message_signer",
+    "recover_typed_data_signer",
+    "summarize_typed_data",
+    "simulate_transaction",
+    "trace_call",
+    "start_fork",
+    "stop_fork",
+    "get_fork_status",
+    "get_queue",
+    "speed_up_transaction",
+    "cancel_transaction",
+    "resync_nonces",
+    "get_pending_approvals",
+    "resolve_approval",
+    "subscribe_new_heads",
+    "subscribe_logs",
+    "unsubscribe",
+    "get_secret_store_status",
+    "list_accounts",
+    "create_account",
+    "import_private_key",
+    "import_keystore",
+    "export_keystore",
+    "unlock_account",
+    "lock_account",
+    "generate_mnemonic",
+    "validate_mnemonic",
+    "derive_addresses",
+    "import_mnemonic",
+];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
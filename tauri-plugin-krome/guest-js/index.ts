@@ -0,0 +1,1001 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
+
+export * from "./provider";
+
+// Shape of every error rejected by a Krome command
+export interface KromeErrorPayload {
+  code: string;
+  message: string;
+  details: Record<string, unknown> | null;
+}
+
+export class KromeError extends Error {
+  readonly code: string;
+  readonly details: Record<string, unknown> | null;
+
+  constructor(payload: KromeErrorPayload) {
+    super(payload.message);
+    this.name = new.target.name;
+    this.code = payload.code;
+    this.details = payload.details;
+  }
+}
+
+export class ClientNotStartedError extends KromeError {}
+export class ClientNotSyncedError extends KromeError {}
+export class ClientAlreadyStartedError extends KromeError {}
+export class UnsupportedNetworkError extends KromeError {}
+export class InvalidNetworkConfigError extends KromeError {}
+export class InvalidConfigError extends KromeError {}
+export class RpcUnreachableError extends KromeError {}
+export class InvalidCheckpointError extends KromeError {}
+export class CheckpointTooOldError extends KromeError {}
+export class InvalidParamsError extends KromeError {}
+export class AccountNotFoundError extends KromeError {}
+export class AccountExistsError extends KromeError {}
+export class AccountLockedError extends KromeError {}
+export class InvalidPasswordError extends KromeError {}
+export class InvalidMnemonicError extends KromeError {}
+export class KeystoreError extends KromeError {}
+export class SecretStoreError extends KromeError {}
+export class SigningError extends KromeError {}
+export class TransactionNotFoundError extends KromeError {}
+export class UserRejectedError extends KromeError {}
+export class UnauthorizedError extends KromeError {}
+export class ApprovalError extends KromeError {}
+export class SimulationError extends KromeError {}
+export class ForkError extends KromeError {}
+export class HeliosError extends KromeError {}
+
+// A call the client ran locally reverted. `reason` is decoded from Error(string)
+// or Panic(uint256) data when the contract gave one.
+export class ExecutionRevertedError extends KromeError {
+  get reason(): string | null {
+    return (this.details?.reason as string | null | undefined) ?? null;
+  }
+
+  get data(): string {
+    return (this.details?.data as string | undefined) ?? "0x";
+  }
+}
+export class SerializationError extends KromeError {}
+export class PathError extends KromeError {}
+export class IoError extends KromeError {}
+
+const ERROR_CLASSES: Record<string, new (payload: KromeErrorPayload) => KromeError> = {
+  client_not_started: ClientNotStartedError,
+  client_not_synced: ClientNotSyncedError,
+  client_already_started: ClientAlreadyStartedError,
+  unsupported_network: UnsupportedNetworkError,
+  invalid_network_config: InvalidNetworkConfigError,
+  invalid_config: InvalidConfigError,
+  rpc_unreachable: RpcUnreachableError,
+  invalid_checkpoint: InvalidCheckpointError,
+  checkpoint_too_old: CheckpointTooOldError,
+  invalid_params: InvalidParamsError,
+  execution_reverted: ExecutionRevertedError,
+  account_not_found: AccountNotFoundError,
+  account_exists: AccountExistsError,
+  account_locked: AccountLockedError,
+  invalid_password: InvalidPasswordError,
+  invalid_mnemonic: InvalidMnemonicError,
+  keystore_error: KeystoreError,
+  secret_store_error: SecretStoreError,
+  signing_error: SigningError,
+  transaction_not_found: TransactionNotFoundError,
+  user_rejected: UserRejectedError,
+  unauthorized: UnauthorizedError,
+  approval_error: ApprovalError,
+  simulation_error: SimulationError,
+  fork_error: ForkError,
+  helios_error: HeliosError,
+  serialization_error: SerializationError,
+  path_error: PathError,
+  io_error: IoError,
+};
+
+// Turns a command rejection into the matching KromeError subclass
+export function toKromeError(error: unknown): KromeError {
+  if (error instanceof KromeError) {
+    return error;
+  }
+  if (error && typeof error === "object" && "code" in error && "message" in error) {
+    const payload = error as KromeErrorPayload;
+    const ErrorClass = ERROR_CLASSES[payload.code] ?? KromeError;
+    return new ErrorClass({ ...payload, details: payload.details ?? null });
+  }
+  return new KromeError({ code: "unknown", message: String(error), details: null });
+}
+
+// Accepts a single URL or a list of them
+function urlList(urls?: string | string[]): string[] | undefined {
+  return typeof urls === "string" ? [urls] : urls;
+}
+
+// Invokes a command on the krome plugin, rejecting with a typed KromeError
+async function call<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  try {
+    return await invoke<T>(`plugin:krome|${command}`, args);
+  } catch (error) {
+    throw toKromeError(error);
+  }
+}
+
+export interface Block {
+  number: string;
+  hash: string;
+  parentHash: string;
+  timestamp: string;
+  // Add other block fields as needed
+}
+
+// "latest", "finalized", a hex quantity or a block number. Commands reject
+// "pending", "safe" and "earliest", which the light client can't answer.
+export type BlockParam = "latest" | "pending" | "safe" | "finalized" | "earliest" | string | number;
+
+export interface LogFilter {
+  fromBlock?: BlockParam;
+  toBlock?: BlockParam;
+  blockHash?: string;
+  address?: string | string[];
+  topics?: (string | string[] | null)[];
+}
+
+export interface Log {
+  address: string;
+  topics: string[];
+  data: string;
+  blockNumber: string | null;
+  blockHash: string | null;
+  transactionHash: string | null;
+  transactionIndex: string | null;
+  logIndex: string | null;
+  removed: boolean;
+}
+
+export interface TransactionRequest {
+  from?: string;
+  to?: string | null;
+  data?: string;
+  value?: string;
+  gas?: string;
+  gasPrice?: string;
+  maxFeePerGas?: string;
+  maxPriorityFeePerGas?: string;
+  nonce?: string;
+  chainId?: string;
+  // 0 legacy, 1 EIP-2930, 2 EIP-1559
+  type?: string;
+  accessList?: { address: string; storageKeys: string[] }[];
+}
+
+// EIP-712 typed data, as passed to eth_signTypedData_v4
+export interface TypedData {
+  types: Record<string, { name: string; type: string }[]>;
+  primaryType: string;
+  domain: {
+    name?: string;
+    version?: string;
+    chainId?: number | string;
+    verifyingContract?: string;
+    salt?: string;
+  };
+  message: Record<string, unknown>;
+}
+
+export interface TypedDataSummary {
+  primaryType: string;
+  domain: {
+    name: string | null;
+    version: string | null;
+    chainId: string | null;
+    verifyingContract: string | null;
+    salt: string | null;
+  };
+  // Nested structs and arrays flattened to paths like `details.token`
+  fields: { path: string; type: string; value: string }[];
+  // Unlimited amounts, far-off deadlines, a chain other than the active one
+  warnings: string[];
+}
+
+export type Asset =
+  | { standard: 'native' }
+  | { standard: 'erc20'; token: string }
+  | { standard: 'erc721'; token: string; tokenId: string }
+  | { standard: 'erc1155'; token: string; tokenId: string };
+
+// Signed, in the asset's smallest unit. Negative means the sender loses it.
+export type BalanceChange = Asset & {
+  amount: string;
+  symbol: string | null;
+  decimals: number | null;
+};
+
+export interface StorageDiff {
+  slot: string;
+  before: string;
+  after: string;
+}
+
+export interface AccountDiff {
+  address: string;
+  balanceBefore: string;
+  balanceAfter: string;
+  nonceBefore: number;
+  nonceAfter: number;
+  codeChanged: boolean;
+  // Only slots whose value changed
+  storage: StorageDiff[];
+}
+
+export interface SimulatedLog {
+  address: string;
+  topics: string[];
+  data: string;
+}
+
+// A transaction run locally on top of the latest verified block
+export interface Simulation {
+  blockNumber: number;
+  success: boolean;
+  gasUsed: number;
+  // gasUsed at the transaction's fee, in wei
+  gasCost: string;
+  output: string;
+  // Revert reason or halt cause, if it failed
+  error: string | null;
+  logs: SimulatedLog[];
+  // What the sender sends and receives, not counting gas
+  balanceChanges: BalanceChange[];
+  stateDiff: AccountDiff[];
+}
+
+// geth's debug_traceCall struct logger options
+export interface StructLogOptions {
+  disableStorage?: boolean;
+  disableStack?: boolean;
+  enableMemory?: boolean;
+  enableReturnData?: boolean;
+  // Stop recording after this many steps
+  limit?: number;
+}
+
+export interface TraceOptions {
+  // Include the logs each frame emitted
+  withLogs?: boolean;
+  // Record every opcode
+  structLogs?: StructLogOptions;
+}
+
+// One frame of the call tree, as geth's callTracer returns it
+export interface CallFrame {
+  type: 'CALL' | 'STATICCALL' | 'DELEGATECALL' | 'CALLCODE' | 'CREATE' | 'CREATE2' | 'SELFDESTRUCT';
+  from: string;
+  to?: string;
+  value?: string;
+  gas: string;
+  gasUsed: string;
+  input: string;
+  output?: string;
+  error?: string;
+  revertReason?: string;
+  calls?: CallFrame[];
+  logs?: { address: string; topics: string[]; data: string; position?: string }[];
+}
+
+export interface StructLog {
+  pc: number;
+  op: string;
+  gas: number;
+  gasCost: number;
+  depth: number;
+  stack?: string[];
+  memory?: string[];
+  storage?: Record<string, string>;
+  returnData?: string;
+  refund?: number;
+  error?: string;
+}
+
+// As geth's default struct logger returns it
+export interface StructLogTrace {
+  failed: boolean;
+  gas: number;
+  returnValue: string;
+  structLogs: StructLog[];
+}
+
+export interface CallTrace {
+  blockNumber: number;
+  call: CallFrame;
+  structLogs: StructLogTrace | null;
+}
+
+export interface ForkStatus {
+  chainId: number;
+  // Block the fork was taken at. State the fork hasn't read yet is loaded
+  // here, which only works while the light client can still prove it.
+  forkBlock: number;
+  // Latest local block
+  blockNumber: number;
+  automine: boolean;
+  // Transactions waiting for the next mined block when automine is off
+  pending: number;
+  impersonated: string[];
+}
+
+// What the approval window is asked to show. Transactions come with every
+// field filled, as they'll be signed, and a simulation of them unless it
+// couldn't run.
+export type ApprovalKind =
+  | { kind: 'signTransaction'; tx: TransactionRequest; simulation: Simulation | null }
+  | { kind: 'sendTransaction'; tx: TransactionRequest; simulation: Simulation | null }
+  | {
+      kind: 'replaceTransaction';
+      tx: TransactionRequest;
+      simulation: Simulation | null;
+      replaces: string;
+      cancel: boolean;
+    }
+  | { kind: 'personalSign'; address: string; message: string; text: string | null }
+  | { kind: 'signTypedData'; address: string; summary: TypedDataSummary; typedData: TypedData }
+  // eth_requestAccounts: let the origin see these unlocked accounts
+  | { kind: 'requestAccounts'; accounts: string[] };
+
+export type ApprovalRequest = ApprovalKind & {
+  id: number;
+  // Webview window label, and the origin of the page in it
+  origin: { window: string; url: string | null };
+  createdAt: number;
+};
+
+export interface ApprovalResolved {
+  id: number;
+  approved: boolean;
+}
+
+export interface SignedTransaction {
+  hash: string;
+  // EIP-2718 encoded, ready for sendRawTransaction()
+  raw: string;
+  from: string;
+  nonce: number;
+  type: number;
+}
+
+export interface FeeHistory {
+  oldestBlock: string;
+  baseFeePerGas: string[];
+  gasUsedRatio: number[];
+  reward?: string[][];
+}
+
+export type TxStatus =
+  | { state: "pending" }
+  // Mined, but with fewer confirmations than asked for
+  | { state: "included"; blockNumber: number; confirmations: number; success: boolean }
+  | { state: "confirmed"; blockNumber: number; confirmations: number; success: boolean }
+  // Another transaction with the same nonce was mined instead
+  | { state: "replaced"; by: string | null }
+  | { state: "dropped" };
+
+export interface TrackedTx {
+  hash: string;
+  chainId: number;
+  from: string;
+  nonce: number;
+  status: TxStatus;
+  confirmationsRequired: number;
+  // Unix timestamps in seconds
+  submittedAt: number;
+  updatedAt: number;
+}
+
+// `done` only shows up in the helios://queue event sent as it leaves the queue
+export type QueueStatus = 'queued' | 'pending' | 'done';
+
+export interface QueuedTx {
+  // Latest version; speed-up and cancel replace it
+  hash: string;
+  replaced: string[];
+  chainId: number;
+  from: string;
+  nonce: number;
+  tx: TransactionRequest;
+  raw: string;
+  status: QueueStatus;
+  cancelled: boolean;
+  confirmations: number | null;
+  // Why a node rejected it
+  error: string | null;
+  createdAt: number;
+  updatedAt: number;
+}
+
+// Payload of helios://subscription, the `params` of an eth_subscription
+// notification. `result` is a block header or a log.
+export interface SubscriptionMessage {
+  subscription: string;
+  result: unknown;
+}
+
+export interface Subscription {
+  id: string;
+  unsubscribe(): Promise<void>;
+}
+
+export interface SecretStoreStatus {
+  backend: "secret-service" | "file" | "memory";
+  // Why the `auto` backend fell back to the encrypted file. Secrets saved to
+  // the keychain on an earlier launch are missing until it's reachable again.
+  fallbackReason: string | null;
+}
+
+export interface AccountInfo {
+  address: string;
+  unlocked: boolean;
+}
+
+export type MnemonicLanguage =
+  | "english"
+  | "chineseSimplified"
+  | "chineseTraditional"
+  | "czech"
+  | "french"
+  | "italian"
+  | "japanese"
+  | "korean"
+  | "portuguese"
+  | "spanish";
+
+// Derivation path templates; `{index}` is the account number
+export const DerivationPaths = {
+  // MetaMask, Rabby, Trezor and most other wallets
+  bip44: "m/44'/60'/0'/0/{index}",
+  ledgerLive: "m/44'/60'/{index}'/0/0",
+  ledgerLegacy: "m/44'/60'/0'/{index}",
+} as const;
+
+export interface MnemonicOptions {
+  language?: MnemonicLanguage;
+  // BIP-39 passphrase, sometimes called the 25th word
+  passphrase?: string;
+  // Defaults to DerivationPaths.bip44
+  path?: string;
+}
+
+export interface DerivedAccount {
+  index: number;
+  path: string;
+  address: string;
+}
+
+export interface WalletLocked {
+  address: string;
+}
+
+export type HeliosStatus =
+  | { state: "stopped" }
+  | { state: "syncing" }
+  | { state: "synced" }
+  | { state: "failed"; error: KromeErrorPayload };
+
+export interface NetworkInfo {
+  chainId: number;
+  name: string;
+  custom: boolean;
+}
+
+export interface NetworkSettings {
+  // Tried fastest-first, failing over to the next on errors
+  executionRpcs?: string[];
+  consensusRpcs?: string[];
+  checkpoint?: string | null;
+  checkpointFallback?: string | null;
+  loadExternalFallback?: boolean;
+  strictCheckpointAge?: boolean;
+}
+
+// Contents of krome.json in the app config dir
+export interface KromeConfig {
+  version: number;
+  activeChainId: number;
+  autoStart: boolean;
+  // Keyed by chain ID
+  networks: Record<string, NetworkSettings>;
+}
+
+// Endpoints come in config order
+export interface EndpointHealth {
+  kind: "execution" | "consensus";
+  // Scheme and host only, e.g. https://eth-mainnet.example
+  url: string;
+  healthy: boolean;
+  latencyMs: number | null;
+  consecutiveFailures: number;
+  backoffRemainingMs: number;
+  lastError: string | null;
+  lastCheckedMsAgo: number | null;
+}
+
+export interface CheckpointInfo {
+  chainId: number;
+  // Checkpoint Helios saved from its last sync
+  stored: string | null;
+  // Checkpoint pinned in krome.json, used instead of the stored one
+  pinned: string | null;
+  // The network's built-in checkpoint, used when neither is set
+  default: string;
+  // The one the next start bootstraps from
+  active: string;
+  slot: number | null;
+  ageSecs: number | null;
+  maxAgeSecs: number;
+  stale: boolean;
+}
+
+export interface CheckpointFile {
+  chainId: number;
+  checkpoint: string;
+}
+
+export interface StaleCheckpoint {
+  chainId: number;
+  checkpoint: string;
+  ageSecs: number;
+  maxAgeSecs: number;
+}
+
+export interface SyncProgress {
+  chainId: number;
+  elapsedMs: number;
+}
+
+export interface Synced {
+  chainId: number;
+  elapsedMs: number;
+  blockNumber: number | null;
+}
+
+export interface SyncError {
+  chainId: number;
+  error: KromeErrorPayload;
+}
+
+export class HeliosClient {
+  private static instance: HeliosClient;
+
+  private constructor() {}
+
+  public static getInstance(): HeliosClient {
+    if (!HeliosClient.instance) {
+      HeliosClient.instance = new HeliosClient();
+    }
+    return HeliosClient.instance;
+  }
+
+  // Resolves as soon as startup has been kicked off; use the on* listeners
+  // below (or status()) to follow the sync. Anything left out is taken from
+  // the saved config (see getConfig()).
+  async start(rpcUrls?: string | string[], chainId?: number, consensusRpcs?: string | string[]): Promise<void> {
+    try {
+      await call('start_helios', {
+        rpcUrls: urlList(rpcUrls),
+        consensusRpcs: urlList(consensusRpcs),
+        chainId,
+      });
+    } catch (error) {
+      console.error('Failed to start Helios:', error);
+      throw error;
+    }
+  }
+
+  async stop(): Promise<void> {
+    try {
+      await call('stop_helios');
+    } catch (error) {
+      console.error('Failed to stop Helios:', error);
+      throw error;
+    }
+  }
+
+  async restart(rpcUrls?: string | string[], chainId?: number, consensusRpcs?: string | string[]): Promise<void> {
+    try {
+      await call('restart_helios', {
+        rpcUrls: urlList(rpcUrls),
+        consensusRpcs: urlList(consensusRpcs),
+        chainId,
+      });
+    } catch (error) {
+      console.error('Failed to restart Helios:', error);
+      throw error;
+    }
+  }
+
+  async status(): Promise<HeliosStatus> {
+    return call<HeliosStatus>('helios_status');
+  }
+
+  // Health of every RPC the running client is using
+  async getEndpointHealth(): Promise<EndpointHealth[]> {
+    return call<EndpointHealth[]>('get_endpoint_health');
+  }
+
+  // Networks that can be passed to start(). Besides the ones Helios knows
+  // about, any `networks/<chainId>.json` file in the app config dir adds a
+  // custom network.
+  async getNetworks(): Promise<NetworkInfo[]> {
+    return call<NetworkInfo[]>('get_networks');
+  }
+
+  // RPC URLs with API keys in them come back as `secret:` references
+  async getConfig(): Promise<KromeConfig> {
+    return call<KromeConfig>('get_config');
+  }
+
+  // Validates and saves the config; takes effect on the next start/restart.
+  // `secret:` references from getConfig() keep the URLs they stand for.
+  async updateConfig(config: KromeConfig): Promise<KromeConfig> {
+    return call<KromeConfig>('update_config', { newConfig: config });
+  }
+
+  async getCheckpoint(chainId?: number): Promise<CheckpointInfo> {
+    return call<CheckpointInfo>('get_checkpoint', { chainId });
+  }
+
+  // Bootstraps from this checkpoint from the next start on
+  async pinCheckpoint(checkpoint: string, chainId?: number): Promise<void> {
+    await call('pin_checkpoint', { checkpoint, chainId });
+  }
+
+  async unpinCheckpoint(chainId?: number): Promise<void> {
+    await call('unpin_checkpoint', { chainId });
+  }
+
+  // Returns `krome-checkpoint:<chainId>:<hash>`, small enough for a QR code
+  async exportCheckpoint(chainId?: number): Promise<string> {
+    return call<string>('export_checkpoint', { chainId });
+  }
+
+  // Pins a checkpoint from exportCheckpoint()'s string, or the contents of a
+  // CheckpointFile JSON file
+  async importCheckpoint(source: { encoded: string } | { json: string }): Promise<CheckpointFile> {
+    return call<CheckpointFile>('import_checkpoint', source);
+  }
+
+  // Broadcasts a signed transaction and tracks it until it has
+  // `confirmations` blocks on top (12 by default), is replaced or is dropped.
+  // Every status change is sent to onTxStatus() listeners.
+  async sendRawTransaction(raw: string, confirmations?: number): Promise<string> {
+    return call<string>('send_raw_transaction', { raw, confirmations });
+  }
+
+  // Signs `tx` with the unlocked account in `tx.from`. Missing nonce, chain ID,
+  // fees and gas limit are filled from the light client; the key stays in Rust.
+  async signTransaction(tx: TransactionRequest): Promise<SignedTransaction> {
+    return call<SignedTransaction>('sign_transaction', { tx });
+  }
+
+  // Signs at the next nonce from the transaction queue, then broadcasts and
+  // tracks it like sendRawTransaction()
+  async sendTransaction(tx: TransactionRequest, confirmations?: number): Promise<string> {
+    return call<string>('send_transaction', { tx, confirmations });
+  }
+
+  // EIP-191 signature. `message` is 0x-prefixed hex or plain text.
+  async personalSign(address: string, message: string): Promise<string> {
+    return call<string>('personal_sign', { address, message });
+  }
+
+  // EIP-712 signature. Typed data for another chain than the running one is refused.
+  async signTypedData(address: string, typedData: TypedData | string): Promise<string> {
+    return call<string>('sign_typed_data', { address, typedData });
+  }
+
+  async recoverMessageSigner(message: string, signature: string): Promise<string> {
+    return call<string>('recover_message_signer', { message, signature });
+  }
+
+  async recoverTypedDataSigner(typedData: TypedData | string, signature: string): Promise<string> {
+    return call<string>('recover_typed_data_signer', { typedData, signature });
+  }
+
+  // Human-readable view of typed data, for showing before signTypedData()
+  async summarizeTypedData(typedData: TypedData | string): Promise<TypedDataSummary> {
+    return call<TypedDataSummary>('summarize_typed_data', { typedData });
+  }
+
+  // Runs the transaction against the latest verified block without sending it
+  async simulateTransaction(tx: TransactionRequest): Promise<Simulation> {
+    return call<Simulation>('simulate_transaction', { tx });
+  }
+
+  // Traces the transaction the way simulateTransaction() runs it
+  async traceCall(tx: TransactionRequest, options?: TraceOptions): Promise<CallTrace> {
+    return call<CallTrace>('trace_call', { tx, options });
+  }
+
+  // Forks the chain at `blockNumber`, or the latest block, replacing any
+  // running fork. Provider requests go to the fork until stopFork().
+  async startFork(blockNumber?: number): Promise<ForkStatus> {
+    return call<ForkStatus>('start_fork', { blockNumber });
+  }
+
+  async stopFork(): Promise<void> {
+    return call<void>('stop_fork');
+  }
+
+  // null while no fork is running
+  async getForkStatus(): Promise<ForkStatus | null> {
+    return call<ForkStatus | null>('get_fork_status');
+  }
+
+  async getTrackedTransactions(chainId?: number): Promise<TrackedTx[]> {
+    return call<TrackedTx[]>('get_tracked_transactions', { chainId });
+  }
+
+  async getTrackedTransaction(hash: string): Promise<TrackedTx | null> {
+    return call<TrackedTx | null>('get_tracked_transaction', { hash });
+  }
+
+  async forgetTransaction(hash: string): Promise<void> {
+    await call('forget_transaction', { hash });
+  }
+
+  async getQueue(options: { chainId?: number; from?: string } = {}): Promise<QueuedTx[]> {
+    return call<QueuedTx[]>('get_queue', options);
+  }
+
+  // Replaces a queued or pending transaction with the same one at fees at
+  // least 10% higher. `hash` can be that of any earlier version.
+  async speedUpTransaction(hash: string): Promise<QueuedTx> {
+    return call<QueuedTx>('speed_up_transaction', { hash });
+  }
+
+  // Replaces it with a zero-value send to self at the same nonce
+  async cancelTransaction(hash: string): Promise<QueuedTx> {
+    return call<QueuedTx>('cancel_transaction', { hash });
+  }
+
+  // Makes the next send read its nonce from the chain again
+  async resyncNonces(): Promise<void> {
+    await call('resync_nonces');
+  }
+
+  onQueue(handler: (tx: QueuedTx) => void): Promise<UnlistenFn> {
+    return listen<QueuedTx>('helios://queue', (event) => handler(event.payload));
+  }
+
+  onTxStatus(handler: (tx: TrackedTx) => void): Promise<UnlistenFn> {
+    return listen<TrackedTx>('helios://tx-status', (event) => handler(event.payload));
+  }
+
+  // Calls `handler` with the header of every new block
+  async subscribeNewHeads(handler: (header: Block) => void): Promise<Subscription> {
+    return this.subscribe(() => call<string>('subscribe_new_heads'), handler);
+  }
+
+  // Calls `handler` with every log from a new block that matches `filter`.
+  // Its block range is ignored.
+  async subscribeLogs(filter: LogFilter, handler: (log: Log) => void): Promise<Subscription> {
+    return this.subscribe(() => call<string>('subscribe_logs', { filter }), handler);
+  }
+
+  async unsubscribe(id: string): Promise<boolean> {
+    return call<boolean>('unsubscribe', { id });
+  }
+
+  // Notifications only go to the window that subscribed, so listen there
+  // first and filter on the ID once it's known
+  private async subscribe<T>(start: () => Promise<string>, handler: (result: T) => void): Promise<Subscription> {
+    let id: string | null = null;
+    const unlisten = await getCurrentWebviewWindow().listen<SubscriptionMessage>('helios://subscription', (event) => {
+      if (event.payload.subscription === id) {
+        handler(event.payload.result as T);
+      }
+    });
+    try {
+      id = await start();
+    } catch (error) {
+      unlisten();
+      throw error;
+    }
+    const subscribed = id;
+    return {
+      id: subscribed,
+      unsubscribe: async () => {
+        unlisten();
+        await this.unsubscribe(subscribed);
+      },
+    };
+  }
+
+  // Where keystores and RPC credentials are kept. Check `fallbackReason` at
+  // startup to warn the user when the keychain couldn't be used.
+  async getSecretStoreStatus(): Promise<SecretStoreStatus> {
+    return call<SecretStoreStatus>('get_secret_store_status');
+  }
+
+  // Wallet accounts, stored as Web3 Secret Storage v3 keystores under
+  // `keystore/` in the app data dir
+  async listAccounts(): Promise<AccountInfo[]> {
+    return call<AccountInfo[]>('list_accounts');
+  }
+
+  async createAccount(password: string): Promise<AccountInfo> {
+    return call<AccountInfo>('create_account', { password });
+  }
+
+  async importPrivateKey(privateKey: string, password: string): Promise<AccountInfo> {
+    return call<AccountInfo>('import_private_key', { privateKey, password });
+  }
+
+  // Imports keystore JSON from geth, foundry or another wallet. `password`
+  // must be the one it was encrypted with.
+  async importKeystore(json: string, password: string): Promise<AccountInfo> {
+    return call<AccountInfo>('import_keystore', { json, password });
+  }
+
+  // Returns the keystore JSON once `password` checks out against it
+  async exportKeystore(address: string, password: string): Promise<string> {
+    return call<string>('export_keystore', { address, password });
+  }
+
+  async generateMnemonic(wordCount: 12 | 15 | 18 | 21 | 24 = 12, language?: MnemonicLanguage): Promise<string> {
+    return call<string>('generate_mnemonic', { wordCount, language });
+  }
+
+  async validateMnemonic(phrase: string, language?: MnemonicLanguage): Promise<boolean> {
+    return call<boolean>('validate_mnemonic', { phrase, language });
+  }
+
+  // Addresses at `count` indices from `start`, to pick which to restore
+  async deriveAddresses(phrase: string, options: MnemonicOptions = {}, start = 0, count = 10): Promise<DerivedAccount[]> {
+    return call<DerivedAccount[]>('derive_addresses', { phrase, ...options, start, count });
+  }
+
+  // Stores the accounts at `indices` as keystores encrypted with `password`
+  async importMnemonic(
+    phrase: string,
+    indices: number[],
+    password: string,
+    options: MnemonicOptions = {},
+  ): Promise<AccountInfo[]> {
+    return call<AccountInfo[]>('import_mnemonic', { phrase, indices, password, ...options });
+  }
+
+  // Keeps the account unlocked until it goes unused for `timeoutSecs`
+  // (the plugin's walletAutoLockSecs by default, 0 for never)
+  async unlockAccount(address: string, password: string, timeoutSecs?: number): Promise<void> {
+    await call('unlock_account', { address, password, timeoutSecs });
+  }
+
+  // Locks one account, or all of them without an address
+  async lockAccount(address?: string): Promise<void> {
+    await call('lock_account', { address });
+  }
+
+  // The approval methods below only work from the approval window, the
+  // `krome-approval` webview the plugin opens on `approvalUrl`.
+  async getPendingApprovals(): Promise<ApprovalRequest[]> {
+    return call<ApprovalRequest[]>('get_pending_approvals');
+  }
+
+  async resolveApproval(id: number, approved: boolean): Promise<void> {
+    await call('resolve_approval', { id, approved });
+  }
+
+  onApprovalRequest(handler: (request: ApprovalRequest) => void): Promise<UnlistenFn> {
+    return getCurrentWebviewWindow().listen<ApprovalRequest>('helios://approval-request', (event) =>
+      handler(event.payload),
+    );
+  }
+
+  // Answered requests, and ones withdrawn because the caller gave up
+  onApprovalResolved(handler: (resolved: ApprovalResolved) => void): Promise<UnlistenFn> {
+    return getCurrentWebviewWindow().listen<ApprovalResolved>('helios://approval-resolved', (event) =>
+      handler(event.payload),
+    );
+  }
+
+  onWalletLocked(handler: (locked: WalletLocked) => void): Promise<UnlistenFn> {
+    return listen<WalletLocked>('helios://wallet-locked', (event) => handler(event.payload));
+  }
+
+  onCheckpointStale(handler: (stale: StaleCheckpoint) => void): Promise<UnlistenFn> {
+    return listen<StaleCheckpoint>('helios://checkpoint-stale', (event) => handler(event.payload));
+  }
+
+  onSyncProgress(handler: (progress: SyncProgress) => void): Promise<UnlistenFn> {
+    return listen<SyncProgress>('helios://sync-progress', (event) => handler(event.payload));
+  }
+
+  onSynced(handler: (synced: Synced) => void): Promise<UnlistenFn> {
+    return listen<Synced>('helios://synced', (event) => handler(event.payload));
+  }
+
+  onError(handler: (error: SyncError) => void): Promise<UnlistenFn> {
+    return listen<SyncError>('helios://error', (event) => handler(event.payload));
+  }
+
+  async getLatestBlock(): Promise<Block> {
+    try {
+      const block = await call<Block>('get_latest_block');
+      return block;
+    } catch (error) {
+      console.error('Failed to get latest block:', error);
+      throw error;
+    }
+  }
+
+  // Verified reads. Quantities come back as hex strings, as in JSON-RPC, and
+  // `block` defaults to "latest".
+  async getBalance(address: string, block?: BlockParam): Promise<string> {
+    return call<string>('get_balance', { address, block });
+  }
+
+  async getTransactionCount(address: string, block?: BlockParam): Promise<string> {
+    return call<string>('get_transaction_count', { address, block });
+  }
+
+  async getCode(address: string, block?: BlockParam): Promise<string> {
+    return call<string>('get_code', { address, block });
+  }
+
+  async getStorageAt(address: string, slot: string, block?: BlockParam): Promise<string> {
+    return call<string>('get_storage_at', { address, slot, block });
+  }
+
+  async getBlockByNumber(block?: BlockParam, fullTransactions = false): Promise<Block | null> {
+    return call<Block | null>('get_block_by_number', { block, fullTransactions });
+  }
+
+  async getBlockByHash(hash: string, fullTransactions = false): Promise<Block | null> {
+    return call<Block | null>('get_block_by_hash', { hash, fullTransactions });
+  }
+
+  async getTransactionByHash(hash: string): Promise<Record<string, unknown> | null> {
+    return call('get_transaction_by_hash', { hash });
+  }
+
+  async getTransactionReceipt(hash: string): Promise<Record<string, unknown> | null> {
+    return call('get_transaction_receipt', { hash });
+  }
+
+  async getLogs(filter: LogFilter): Promise<Log[]> {
+    return call<Log[]>('get_logs', { filter });
+  }
+
+  // Runs the call locally against proven state and returns its output.
+  // Rejects with ExecutionRevertedError if it reverts.
+  async call(tx: TransactionRequest, block?: BlockParam): Promise<string> {
+    return call<string>('call', { tx, block });
+  }
+
+  // Estimated against the latest block unless `block` says otherwise
+  async estimateGas(tx: TransactionRequest, block?: BlockParam): Promise<string> {
+    return call<string>('estimate_gas', { tx, block });
+  }
+
+  async gasPrice(): Promise<string> {
+    return call<string>('gas_price');
+  }
+
+  async maxPriorityFee(): Promise<string> {
+    return call<string>('max_priority_fee');
+  }
+
+  async feeHistory(blockCount: number, newestBlock?: BlockParam, rewardPercentiles?: number[]): Promise<FeeHistory> {
+    return call<FeeHistory>('fee_history', { blockCount, newestBlock, rewardPercentiles });
+  }
+
+  async chainId(): Promise<string> {
+    return call<string>('chain_id');
+  }
+
+  async blockNumber(): Promise<string> {
+    return call<string>('block_number');
+  }
+} 
\ No newline at end of file
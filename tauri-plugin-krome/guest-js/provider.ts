@@ -0,0 +1,110 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
+import type { SubscriptionMessage, SyncError, Synced } from "./index";
+
+// EIP-1193 provider backed by the light client, e.g. for viem:
+//
+//   createPublicClient({ chain: mainnet, transport: custom(new HeliosProvider()) })
+
+export interface RequestArguments {
+  readonly method: string;
+  readonly params?: readonly unknown[] | object;
+}
+
+export interface ProviderConnectInfo {
+  readonly chainId: string;
+}
+
+// Sent as the `message` event for eth_subscribe notifications
+export interface ProviderMessage {
+  readonly type: "eth_subscription";
+  readonly data: SubscriptionMessage;
+}
+
+export class ProviderRpcError extends Error {
+  readonly code: number;
+  readonly data?: unknown;
+
+  constructor(code: number, message: string, data?: unknown) {
+    super(message);
+    this.name = "ProviderRpcError";
+    this.code = code;
+    this.data = data;
+  }
+}
+
+// Turns an eth_request rejection into a ProviderRpcError
+function toProviderRpcError(error: unknown): ProviderRpcError {
+  if (error instanceof ProviderRpcError) {
+    return error;
+  }
+  if (error && typeof error === "object" && "code" in error && "message" in error) {
+    const { code, message, data } = error as { code: number; message: string; data?: unknown };
+    return new ProviderRpcError(code, message, data);
+  }
+  return new ProviderRpcError(-32603, String(error));
+}
+
+type Listener = (...args: any[]) => void;
+
+export class HeliosProvider {
+  private listeners = new Map<string, Set<Listener>>();
+  private chainId: string | null = null;
+  private unlisten: Promise<UnlistenFn[]>;
+
+  constructor() {
+    this.unlisten = Promise.all([
+      listen<Synced>("helios://synced", (event) => this.connected(event.payload.chainId)),
+      listen<SyncError>("helios://error", (event) => {
+        this.chainId = null;
+        this.emit("disconnect", new ProviderRpcError(4900, event.payload.error.message, event.payload.error));
+      }),
+      getCurrentWebviewWindow().listen<SubscriptionMessage>("helios://subscription", (event) => {
+        this.emit("message", { type: "eth_subscription", data: event.payload } satisfies ProviderMessage);
+      }),
+    ]);
+  }
+
+  async request<T = unknown>({ method, params }: RequestArguments): Promise<T> {
+    try {
+      return await invoke<T>("plugin:krome|eth_request", { request: { method, params: params ?? [] } });
+    } catch (error) {
+      throw toProviderRpcError(error);
+    }
+  }
+
+  on(event: string, listener: Listener): this {
+    if (!this.listeners.has(event)) {
+      this.listeners.set(event, new Set());
+    }
+    this.listeners.get(event)!.add(listener);
+    return this;
+  }
+
+  removeListener(event: string, listener: Listener): this {
+    this.listeners.get(event)?.delete(listener);
+    return this;
+  }
+
+  // Stops listening to the client's events
+  async close(): Promise<void> {
+    (await this.unlisten).forEach((unlisten) => unlisten());
+    this.listeners.clear();
+  }
+
+  protected emit(event: string, ...args: unknown[]): void {
+    this.listeners.get(event)?.forEach((listener) => listener(...args));
+  }
+
+  private connected(chainId: number): void {
+    const hex = `0x${chainId.toString(16)}`;
+    const previous = this.chainId;
+    this.chainId = hex;
+    if (previous === null) {
+      this.emit("connect", { chainId: hex } satisfies ProviderConnectInfo);
+    } else if (previous !== hex) {
+      this.emit("chainChanged", hex);
+    }
+  }
+}
@@ -0,0 +1,457 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tauri::{
+    AppHandle, Emitter, EventTarget, Manager, Runtime, State, Url, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri::webview::PageLoadEvent;
+
+use alloy::primitives::{Address, Bytes, B256};
+use alloy::rpc::types::TransactionRequest;
+
+use crate::error::{KromeError, Result};
+use crate::signing::message::TypedDataSummary;
+use crate::simulation::Simulation;
+use crate::wallet::WalletState;
+use crate::Config;
+
+// Nothing is signed or sent without the user's say-so. Each request waits
+// here until the user answers it in the approval window, a separate native
+// window that loads the app's own approval page. Only that window can list
+// or answer requests, so the webview asking for a signature can't approve
+// it itself.
+
+pub const APPROVAL_WINDOW_LABEL: &str = "krome-approval";
+// Sent to the approval window with each new ApprovalRequest
+pub const APPROVAL_REQUEST_EVENT: &str = "helios://approval-request";
+// Sent to the approval window once a request is answered or withdrawn
+pub const APPROVAL_RESOLVED_EVENT: &str = "helios://approval-resolved";
+
+// Where a request came from
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Origin {
+    // Label of the webview window that made the request
+    pub window: String,
+    // Origin of the page loaded in it, e.g. https://app.uniswap.org
+    pub url: Option<String>,
+}
+
+impl Origin {
+    pub fn of<R: Runtime>(window: &WebviewWindow<R>) -> Self {
+        Origin {
+            window: window.label().to_string(),
+            url: window.url().ok().map(|url| url.origin().ascii_serialization()),
+        }
+    }
+
+    // What permissions are granted to: the page origin, or the window when
+    // there's no page URL
+    fn key(&self) -> &str {
+        self.url.as_deref().unwrap_or(&self.window)
+    }
+}
+
+// Accounts each origin was shown through eth_requestAccounts. Grants last
+// until the app quits.
+#[derive(Default)]
+pub struct Connections(Mutex<HashMap<String, Vec<Address>>>);
+
+impl Connections {
+    // What eth_accounts returns: the origin's accounts that are unlocked now
+    pub fn accounts(&self, origin: &Origin, wallet: &WalletState) -> Vec<Address> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(origin.key())
+            .map(|accounts| accounts.iter().copied().filter(|a| wallet.is_unlocked(*a)).collect())
+            .unwrap_or_default()
+    }
+
+    // Whether the user has let the origin see accounts, even if they've all
+    // been locked since
+    pub fn is_connected(&self, origin: &Origin) -> bool {
+        self.0.lock().unwrap().contains_key(origin.key())
+    }
+
+    // Asks the user to show the unlocked accounts to the origin, unless it
+    // can see some already
+    pub async fn request<R: Runtime>(&self, app_handle: &AppHandle<R>, origin: &Origin) -> Result<Vec<Address>> {
+        let wallet = app_handle.state::<WalletState>();
+        let connected = self.accounts(origin, &wallet);
+        if !connected.is_empty() {
+            return Ok(connected);
+        }
+
+        let accounts = wallet.unlocked_accounts();
+        if accounts.is_empty() {
+            return Err(KromeError::Unauthorized("unlock an account to connect".to_string()));
+        }
+        let kind = ApprovalKind::RequestAccounts {
+            accounts: accounts.clone(),
+        };
+        app_handle
+            .state::<ApprovalBroker>()
+            .request(app_handle, origin.clone(), kind)
+            .await?;
+        self.0
+            .lock()
+            .unwrap()
+            .insert(origin.key().to_string(), accounts.clone());
+        Ok(accounts)
+    }
+}
+
+// What the user is asked to approve, with everything decoded that can be
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ApprovalKind {
+    // Transactions are shown with every field filled, as they'll be signed,
+    // and what running them now would do. The simulation is left out if it
+    // couldn't run.
+    SignTransaction {
+        tx: TransactionRequest,
+        simulation: Option<Simulation>,
+    },
+    SendTransaction {
+        tx: TransactionRequest,
+        simulation: Option<Simulation>,
+    },
+    // Speed-up or cancel of a queued transaction
+    ReplaceTransaction {
+        tx: TransactionRequest,
+        simulation: Option<Simulation>,
+        replaces: B256,
+        cancel: bool,
+    },
+    PersonalSign {
+        address: Address,
+        message: Bytes,
+        // The message as text, if it's UTF-8
+        text: Option<String>,
+    },
+    SignTypedData {
+        address: Address,
+        summary: TypedDataSummary,
+        typed_data: Value,
+    },
+    // eth_requestAccounts: let the origin see these unlocked accounts
+    RequestAccounts {
+        accounts: Vec<Address>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRequest {
+    pub id: u64,
+    pub origin: Origin,
+    #[serde(flatten)]
+    pub kind: ApprovalKind,
+    // Unix timestamp in seconds
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalResolved {
+    pub id: u64,
+    pub approved: bool,
+}
+
+struct Pending {
+    request: ApprovalRequest,
+    respond: oneshot::Sender<bool>,
+}
+
+#[derive(Default)]
+pub struct ApprovalBroker {
+    next_id: AtomicU64,
+    pending: Mutex<BTreeMap<u64, Pending>>,
+    // Page the approval window was opened on, once it starts loading
+    page: Mutex<Option<Url>>,
+}
+
+// Withdraws the request if the caller stops waiting for it
+struct Withdraw<'a, R: Runtime> {
+    broker: &'a ApprovalBroker,
+    app_handle: &'a AppHandle<R>,
+    id: u64,
+}
+
+impl<R: Runtime> Drop for Withdraw<'_, R> {
+    fn drop(&mut self) {
+        if self.broker.pending.lock().unwrap().remove(&self.id).is_some() {
+            emit_resolved(self.app_handle, self.id, false);
+        }
+    }
+}
+
+impl ApprovalBroker {
+    // Shows the request in the approval window and waits for the user. Fails
+    // with KromeError::UserRejected unless they approve.
+    pub async fn request<R: Runtime>(&self, app_handle: &AppHandle<R>, origin: Origin, kind: ApprovalKind) -> Result<()> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = ApprovalRequest {
+            id,
+            origin,
+            kind,
+            created_at: now(),
+        };
+        let (respond, response) = oneshot::channel();
+        self.pending.lock().unwrap().insert(
+            id,
+            Pending {
+                request: request.clone(),
+                respond,
+            },
+        );
+        let _withdraw = Withdraw {
+            broker: self,
+            app_handle,
+            id,
+        };
+
+        open_window(app_handle)?;
+        let _ = app_handle.emit_to(
+            EventTarget::webview_window(APPROVAL_WINDOW_LABEL),
+            APPROVAL_REQUEST_EVENT,
+            &request,
+        );
+
+        // A dropped sender means the window went away without an answer
+        match response.await {
+            Ok(true) => Ok(()),
+            _ => Err(KromeError::UserRejected),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ApprovalRequest> {
+        self.pending
+            .lock()
+            .unwrap()
+            .values()
+            .map(|pending| pending.request.clone())
+            .collect()
+    }
+
+    // Returns false if there's no such request, e.g. it was withdrawn
+    pub fn resolve(&self, id: u64, approved: bool) -> bool {
+        match self.pending.lock().unwrap().remove(&id) {
+            Some(pending) => {
+                let _ = pending.respond.send(approved);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Rejects everything still waiting, e.g. when the approval window closes
+    pub fn reject_all(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for (_, pending) in pending {
+            let _ = pending.respond.send(false);
+        }
+    }
+
+    // Answers everything in a closed approval window with no, and forgets the
+    // page it was on
+    pub fn window_closed(&self) {
+        self.reject_all();
+        self.page.lock().unwrap().take();
+    }
+}
+
+fn emit_resolved<R: Runtime>(app_handle: &AppHandle<R>, id: u64, approved: bool) {
+    let _ = app_handle.emit_to(
+        EventTarget::webview_window(APPROVAL_WINDOW_LABEL),
+        APPROVAL_RESOLVED_EVENT,
+        ApprovalResolved { id, approved },
+    );
+}
+
+// Focuses the approval window, opening it on the configured page if needed
+fn open_window<R: Runtime>(app_handle: &AppHandle<R>) -> Result<()> {
+    if let Some(window) = app_handle.get_webview_window(APPROVAL_WINDOW_LABEL) {
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+        return Ok(());
+    }
+
+    let url = app_handle.state::<Config>().approval_url.clone();
+    WebviewWindowBuilder::new(app_handle, APPROVAL_WINDOW_LABEL, WebviewUrl::App(url.into()))
+        .title("Approve request")
+        .inner_size(420.0, 640.0)
+        .resizable(false)
+        .always_on_top(true)
+        .center()
+        .focused(true)
+        // The first page it loads is the configured one; anything it
+        // navigates to later can't answer requests
+        .on_page_load(|window, payload| {
+            if matches!(payload.event(), PageLoadEvent::Started) {
+                let broker = window.state::<ApprovalBroker>();
+                broker.page.lock().unwrap().get_or_insert_with(|| payload.url().clone());
+            }
+        })
+        .build()
+        .map_err(|e| KromeError::Approval(e.to_string()))?;
+    Ok(())
+}
+
+// The label is set by Tauri from the webview that made the call, so other
+// webviews can't pass for the approval window. The label stays the same if
+// the window navigates, so it also has to still show the page it was opened on.
+fn require_approval_window<R: Runtime>(window: &WebviewWindow<R>, broker: &ApprovalBroker) -> Result<()> {
+    let opened = broker.page.lock().unwrap().clone();
+    let on_page = match (opened, window.url()) {
+        (Some(opened), Ok(current)) => same_page(&opened, &current),
+        _ => false,
+    };
+    if window.label() != APPROVAL_WINDOW_LABEL || !on_page {
+        return Err(KromeError::Unauthorized(
+            "approvals can only be answered from the approval window".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Same document, whatever the query or fragment
+fn same_page(a: &Url, b: &Url) -> bool {
+    a.origin() == b.origin() && a.path() == b.path()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// For the approval page to load what's waiting when it opens
+#[tauri::command]
+pub(crate) async fn get_pending_approvals<R: Runtime>(
+    window: WebviewWindow<R>,
+    broker: State<'_, ApprovalBroker>,
+) -> Result<Vec<ApprovalRequest>> {
+    require_approval_window(&window, &broker)?;
+    Ok(broker.list())
+}
+
+#[tauri::command]
+pub(crate) async fn resolve_approval<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    broker: State<'_, ApprovalBroker>,
+    id: u64,
+    approved: bool,
+) -> Result<()> {
+    require_approval_window(&window, &broker)?;
+    if !broker.resolve(id, approved) {
+        return Err(KromeError::InvalidParams(format!("no pending approval {}", id)));
+    }
+    emit_resolved(&app_handle, id, approved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::{RpcError, USER_REJECTED};
+    use tauri::test::{mock_app, MockRuntime};
+
+    fn app() -> tauri::App<MockRuntime> {
+        let app = mock_app();
+        app.manage(Config::default());
+        app.manage(ApprovalBroker::default());
+        app
+    }
+
+    fn personal_sign() -> ApprovalKind {
+        ApprovalKind::PersonalSign {
+            address: Address::ZERO,
+            message: Bytes::from_static(b"hello"),
+            text: Some("hello".to_string()),
+        }
+    }
+
+    fn origin() -> Origin {
+        Origin {
+            window: "main".to_string(),
+            url: Some("https://dapp.example".to_string()),
+        }
+    }
+
+    // Starts a request and waits until the broker lists it
+    async fn pending_request(app: &tauri::App<MockRuntime>) -> (u64, tokio::task::JoinHandle<Result<()>>) {
+        let app_handle = app.handle().clone();
+        let request = tokio::spawn(async move {
+            let broker = app_handle.state::<ApprovalBroker>();
+            broker.request(&app_handle, origin(), personal_sign()).await
+        });
+        loop {
+            if let Some(pending) = app.state::<ApprovalBroker>().list().first() {
+                return (pending.id, request);
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_fail_with_4001() {
+        let app = app();
+        let (id, request) = pending_request(&app).await;
+
+        assert!(app.state::<ApprovalBroker>().resolve(id, false));
+        let error = request.await.unwrap().unwrap_err();
+        assert!(matches!(error, KromeError::UserRejected));
+        assert_eq!(RpcError::from(error).code, USER_REJECTED);
+        assert!(app.state::<ApprovalBroker>().list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn closing_the_window_rejects_everything() {
+        let app = app();
+        let (_, request) = pending_request(&app).await;
+
+        app.state::<ApprovalBroker>().reject_all();
+        assert!(matches!(request.await.unwrap(), Err(KromeError::UserRejected)));
+    }
+
+    #[tokio::test]
+    async fn approved_requests_go_ahead() {
+        let app = app();
+        let (id, request) = pending_request(&app).await;
+
+        assert!(app.state::<ApprovalBroker>().resolve(id, true));
+        assert!(request.await.unwrap().is_ok());
+        assert!(!app.state::<ApprovalBroker>().resolve(id, true));
+    }
+
+    #[test]
+    fn connections_are_per_origin() {
+        let connections = Connections::default();
+        assert!(!connections.is_connected(&origin()));
+
+        connections.0.lock().unwrap().insert(origin().key().to_string(), vec![Address::ZERO]);
+        assert!(connections.is_connected(&origin()));
+        let other = Origin {
+            url: Some("https://other.example".to_string()),
+            ..origin()
+        };
+        assert!(!connections.is_connected(&other));
+    }
+
+    #[test]
+    fn only_the_opened_page_counts() {
+        let opened: Url = "tauri://localhost/approval".parse().unwrap();
+        assert!(same_page(&opened, &"tauri://localhost/approval#/request/3".parse().unwrap()));
+        assert!(same_page(&opened, &"tauri://localhost/approval?id=3".parse().unwrap()));
+        assert!(!same_page(&opened, &"tauri://localhost/settings".parse().unwrap()));
+        assert!(!same_page(&opened, &"https://evil.example/approval".parse().unwrap()));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use alloy::primitives::B256;
+
+use crate::error::{KromeError, Result};
+use crate::network::NetworkSelection;
+
+// Sent when the checkpoint a client would bootstrap from is older than the
+// weak-subjectivity period, so syncing from it can't be trusted
+pub const CHECKPOINT_STALE_EVENT: &str = "helios://checkpoint-stale";
+
+// Prefix of the QR-friendly form, `krome-checkpoint:<chain_id>:<hash>`
+const ENCODED_PREFIX: &str = "krome-checkpoint";
+
+// File Helios' FileDB keeps the latest checkpoint in
+const CHECKPOINT_FILE: &str = "checkpoint";
+
+const SECONDS_PER_SLOT: u64 = 12;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointInfo {
+    pub chain_id: u64,
+    // Checkpoint Helios saved from its last sync
+    pub stored: Option<B256>,
+    // Checkpoint pinned in krome.json, used instead of the stored one
+    pub pinned: Option<B256>,
+    // The network's built-in checkpoint, used when neither is set
+    pub default: B256,
+    // The one the next start bootstraps from
+    pub active: B256,
+    pub slot: Option<u64>,
+    pub age_secs: Option<u64>,
+    pub max_age_secs: u64,
+    pub stale: bool,
+}
+
+// Payload of CHECKPOINT_STALE_EVENT
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleCheckpoint {
+    pub chain_id: u64,
+    pub checkpoint: B256,
+    pub age_secs: u64,
+    pub max_age_secs: u64,
+}
+
+// File form used by export/import
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointFile {
+    pub chain_id: u64,
+    pub checkpoint: B256,
+}
+
+pub fn read_stored(data_dir: &Path) -> Result<Option<B256>> {
+    let path = data_dir.join(CHECKPOINT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = fs::read(&path)?;
+    if bytes.len() != 32 {
+        return Err(KromeError::InvalidCheckpoint(format!(
+            "{} holds {} bytes, expected 32",
+            path.display(),
+            bytes.len()
+        )));
+    }
+    Ok(Some(B256::from_slice(&bytes)))
+}
+
+pub fn encode(chain_id: u64, checkpoint: B256) -> String {
+    format!("{}:{}:{}", ENCODED_PREFIX, chain_id, checkpoint)
+}
+
+pub fn decode(encoded: &str) -> Result<CheckpointFile> {
+    let invalid = || KromeError::InvalidCheckpoint(format!("expected {}:<chain_id>:<hash>", ENCODED_PREFIX));
+
+    let mut parts = encoded.trim().splitn(3, ':');
+    if parts.next() != Some(ENCODED_PREFIX) {
+        return Err(invalid());
+    }
+    let chain_id = parts.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?;
+    let checkpoint = parts.next().and_then(|hash| hash.parse().ok()).ok_or_else(invalid)?;
+    Ok(CheckpointFile { chain_id, checkpoint })
+}
+
+// Reads the JSON file form, as the front end loaded it
+pub fn parse_file(json: &str) -> Result<CheckpointFile> {
+    serde_json::from_str(json).map_err(|e| KromeError::InvalidCheckpoint(e.to_string()))
+}
+
+// Looks up the slot of a checkpoint through a consensus RPC's light client
+// bootstrap endpoint
+pub async fn checkpoint_slot(consensus_rpc: &str, checkpoint: B256) -> Result<u64> {
+    let url = format!(
+        "{}/eth/v1/beacon/light_client/bootstrap/{}",
+        consensus_rpc.trim_end_matches('/'),
+        checkpoint
+    );
+    let response: Value = reqwest::Client::new()
+        .get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| KromeError::RpcUnreachable(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| KromeError::RpcUnreachable(e.to_string()))?;
+
+    // Capella and later nest the slot under `beacon`, Altair didn't
+    let header = &response["data"]["header"];
+    header["beacon"]["slot"]
+        .as_str()
+        .or_else(|| header["slot"].as_str())
+        .and_then(|slot| slot.parse().ok())
+        .ok_or_else(|| KromeError::InvalidCheckpoint(format!("no bootstrap found for {}", checkpoint)))
+}
+
+pub fn age_secs(network: &NetworkSelection, slot: u64) -> u64 {
+    let slot_time = network.genesis_time() + slot * SECONDS_PER_SLOT;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    now.saturating_sub(slot_time)
+}
+
+// Describes the checkpoints for a chain. The age is only looked up when a
+// consensus RPC is given, and lookup failures leave it unknown.
+pub async fn info(
+    chain_id: u64,
+    network: &NetworkSelection,
+    data_dir: &Path,
+    pinned: Option<B256>,
+    consensus_rpc: Option<&str>,
+) -> Result<CheckpointInfo> {
+    let stored = read_stored(data_dir)?;
+    let default = network.default_checkpoint();
+    let active = pinned.or(stored).unwrap_or(default);
+    let max_age_secs = network.max_checkpoint_age();
+
+    let slot = match consensus_rpc {
+        Some(rpc) => checkpoint_slot(rpc, active).await.ok(),
+        None => None,
+    };
+    let age_secs = slot.map(|slot| age_secs(network, slot));
+
+    Ok(CheckpointInfo {
+        chain_id,
+        stored,
+        pinned,
+        default,
+        active,
+        slot,
+        age_secs,
+        max_age_secs,
+        stale: age_secs.is_some_and(|age| age > max_age_secs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios::ethereum::config::networks::Network;
+
+    const CHECKPOINT: B256 = B256::repeat_byte(0xab);
+
+    #[test]
+    fn encoded_checkpoints_round_trip() {
+        let encoded = encode(1, CHECKPOINT);
+        assert_eq!(encoded, format!("krome-checkpoint:1:{}", CHECKPOINT));
+        let decoded = decode(&format!(" {}\n", encoded)).unwrap();
+        assert_eq!((decoded.chain_id, decoded.checkpoint), (1, CHECKPOINT));
+
+        let file = parse_file(&serde_json::to_string(&decoded).unwrap()).unwrap();
+        assert_eq!((file.chain_id, file.checkpoint), (1, CHECKPOINT));
+    }
+
+    #[test]
+    fn rejects_malformed_checkpoints() {
+        let hash = CHECKPOINT.to_string();
+        for encoded in [
+            format!("helios-checkpoint:1:{}", hash),
+            format!("krome-checkpoint:mainnet:{}", hash),
+            format!("krome-checkpoint:-1:{}", hash),
+            "krome-checkpoint:1:0xabcd".to_string(),
+            format!("krome-checkpoint:1:{}zz", &hash[..64]),
+            "krome-checkpoint:1".to_string(),
+        ] {
+            assert!(matches!(decode(&encoded), Err(KromeError::InvalidCheckpoint(_))), "{}", encoded);
+        }
+        assert!(matches!(parse_file("{\"chainId\":1}"), Err(KromeError::InvalidCheckpoint(_))));
+        assert!(matches!(
+            parse_file("{\"chainId\":1,\"checkpoint\":\"0x12\"}"),
+            Err(KromeError::InvalidCheckpoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_network_default() {
+        let network = NetworkSelection::Known(Network::Mainnet);
+        // Nothing is stored before the first sync
+        let name = format!("krome-checkpoint-{}", alloy::hex::encode(rand::random::<[u8; 8]>()));
+        let data_dir = std::env::temp_dir().join(name);
+
+        let fresh = info(1, &network, &data_dir, None, None).await.unwrap();
+        assert_eq!(fresh.stored, None);
+        assert_eq!(fresh.default, network.default_checkpoint());
+        assert_eq!(fresh.active, fresh.default);
+
+        let pinned = info(1, &network, &data_dir, Some(CHECKPOINT), None).await.unwrap();
+        assert_eq!(pinned.active, CHECKPOINT);
+    }
+}
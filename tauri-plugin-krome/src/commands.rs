@@ -0,0 +1,182 @@
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use alloy::primitives::B256;
+
+use crate::checkpoint::{self, CheckpointFile, CheckpointInfo};
+use crate::config::{self, ConfigState, KromeConfig, NetworkSettings};
+use crate::endpoints::EndpointHealth;
+use crate::error::{KromeError, Result};
+use crate::fork::ForkState;
+use crate::helios::{self, HeliosState, HeliosStatus};
+use crate::network::{self, NetworkInfo};
+use crate::Config;
+
+// Fills in whatever the front end didn't pass from the saved config
+async fn resolve_settings(
+    config: &ConfigState,
+    rpc_urls: Option<Vec<String>>,
+    consensus_rpcs: Option<Vec<String>>,
+    chain_id: Option<u64>,
+) -> (u64, NetworkSettings) {
+    let config = config.get().await;
+    let chain_id = chain_id.unwrap_or(config.active_chain_id);
+
+    let mut settings = config.network(chain_id);
+    if let Some(rpc_urls) = rpc_urls {
+        settings.execution_rpcs = rpc_urls;
+    }
+    if let Some(consensus_rpcs) = consensus_rpcs {
+        settings.consensus_rpcs = consensus_rpcs;
+    }
+    (chain_id, settings)
+}
+
+#[tauri::command]
+pub(crate) async fn start_helios<R: Runtime>(
+    state: State<'_, HeliosState>,
+    config: State<'_, ConfigState>,
+    app_handle: AppHandle<R>,
+    rpc_urls: Option<Vec<String>>,
+    consensus_rpcs: Option<Vec<String>>,
+    chain_id: Option<u64>,
+) -> Result<()> {
+    let (chain_id, settings) = resolve_settings(&config, rpc_urls, consensus_rpcs, chain_id).await;
+    state.start(app_handle, chain_id, settings).await
+}
+
+#[tauri::command]
+pub(crate) async fn stop_helios(state: State<'_, HeliosState>, forks: State<'_, ForkState>) -> Result<()> {
+    forks.stop().await;
+    state.stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn restart_helios<R: Runtime>(
+    state: State<'_, HeliosState>,
+    config: State<'_, ConfigState>,
+    forks: State<'_, ForkState>,
+    app_handle: AppHandle<R>,
+    rpc_urls: Option<Vec<String>>,
+    consensus_rpcs: Option<Vec<String>>,
+    chain_id: Option<u64>,
+) -> Result<()> {
+    let (chain_id, settings) = resolve_settings(&config, rpc_urls, consensus_rpcs, chain_id).await;
+    // A fork keeps reading through the client it was started on
+    forks.stop().await;
+    state.restart(app_handle, chain_id, settings).await
+}
+
+#[tauri::command]
+pub(crate) async fn helios_status(state: State<'_, HeliosState>) -> Result<HeliosStatus> {
+    Ok(state.status().await)
+}
+
+#[tauri::command]
+pub(crate) async fn get_endpoint_health(state: State<'_, HeliosState>) -> Result<Vec<EndpointHealth>> {
+    Ok(state.endpoint_health().await)
+}
+
+#[tauri::command]
+pub(crate) async fn get_networks<R: Runtime>(app_handle: AppHandle<R>) -> Result<Vec<NetworkInfo>> {
+    Ok(network::list_networks(&helios::config_dir(&app_handle)?))
+}
+
+// URLs with credentials come back as `secret:` references, see config::redact
+#[tauri::command]
+pub(crate) async fn get_config(config: State<'_, ConfigState>) -> Result<KromeConfig> {
+    Ok(config::redact(&config.get().await))
+}
+
+#[tauri::command]
+pub(crate) async fn update_config<R: Runtime>(
+    config: State<'_, ConfigState>,
+    app_handle: AppHandle<R>,
+    new_config: KromeConfig,
+) -> Result<KromeConfig> {
+    config.update(new_config, &helios::config_dir(&app_handle)?).await
+}
+
+async fn checkpoint_info<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    config: &ConfigState,
+    chain_id: Option<u64>,
+    check_age: bool,
+) -> Result<CheckpointInfo> {
+    let saved = config.get().await;
+    let chain_id = chain_id.unwrap_or(saved.active_chain_id);
+    let settings = saved.network(chain_id);
+    let network = network::get_network(chain_id, &helios::config_dir(app_handle)?)?;
+
+    let consensus_rpc = settings
+        .consensus_rpcs
+        .first()
+        .cloned()
+        .unwrap_or_else(|| app_handle.state::<Config>().default_consensus_rpc.clone());
+    let consensus_rpc = check_age.then_some(consensus_rpc.as_str());
+
+    checkpoint::info(
+        chain_id,
+        &network,
+        &helios::data_dir(app_handle, chain_id)?,
+        settings.checkpoint,
+        consensus_rpc,
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn get_checkpoint<R: Runtime>(
+    app_handle: AppHandle<R>,
+    config: State<'_, ConfigState>,
+    chain_id: Option<u64>,
+) -> Result<CheckpointInfo> {
+    checkpoint_info(&app_handle, &config, chain_id, true).await
+}
+
+#[tauri::command]
+pub(crate) async fn pin_checkpoint(
+    config: State<'_, ConfigState>,
+    checkpoint: B256,
+    chain_id: Option<u64>,
+) -> Result<()> {
+    let chain_id = chain_id.unwrap_or(config.get().await.active_chain_id);
+    config.pin_checkpoint(chain_id, Some(checkpoint)).await
+}
+
+#[tauri::command]
+pub(crate) async fn unpin_checkpoint(config: State<'_, ConfigState>, chain_id: Option<u64>) -> Result<()> {
+    let chain_id = chain_id.unwrap_or(config.get().await.active_chain_id);
+    config.pin_checkpoint(chain_id, None).await
+}
+
+// Returns the active checkpoint in its QR-friendly form. Files are left to
+// the front end, which can only reach the ones the user picks.
+#[tauri::command]
+pub(crate) async fn export_checkpoint<R: Runtime>(
+    app_handle: AppHandle<R>,
+    config: State<'_, ConfigState>,
+    chain_id: Option<u64>,
+) -> Result<String> {
+    let info = checkpoint_info(&app_handle, &config, chain_id, false).await?;
+    Ok(checkpoint::encode(info.chain_id, info.active))
+}
+
+// Pins a checkpoint from its QR-friendly form or the contents of a JSON
+// checkpoint file
+#[tauri::command]
+pub(crate) async fn import_checkpoint(
+    config: State<'_, ConfigState>,
+    encoded: Option<String>,
+    json: Option<String>,
+) -> Result<CheckpointFile> {
+    let file = match (encoded, json) {
+        (Some(encoded), _) => checkpoint::decode(&encoded)?,
+        (None, Some(json)) => checkpoint::parse_file(&json)?,
+        (None, None) => {
+            return Err(KromeError::InvalidCheckpoint("nothing to import".to_string()));
+        }
+    };
+    config.pin_checkpoint(file.chain_id, Some(file.checkpoint)).await?;
+    Ok(file)
+}
@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use alloy::primitives::B256;
+use reqwest::Url;
+
+use crate::error::{KromeError, Result};
+use crate::network;
+use crate::secrets::{self, SecretStore, SharedSecretStore};
+
+// Name of the config file in the app config dir
+pub const CONFIG_FILE: &str = "krome.json";
+
+// Bumped whenever the file format changes; see `migrate`
+pub const CONFIG_VERSION: u32 = 2;
+
+// RPC URLs with credentials in them are kept in the SecretStore, and
+// krome.json holds `secret:<key>` in their place
+const SECRET_REF_PREFIX: &str = "secret:";
+const RPC_SECRET_PREFIX: &str = "config/rpc/";
+
+// Endpoints and options for one network
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+    // Tried fastest-first, failing over to the next on errors
+    pub execution_rpcs: Vec<String>,
+    pub consensus_rpcs: Vec<String>,
+    // Weak-subjectivity checkpoint to bootstrap from instead of the stored one
+    pub checkpoint: Option<B256>,
+    // Service used to fetch a fresh checkpoint when the stored one is too old
+    pub checkpoint_fallback: Option<String>,
+    pub load_external_fallback: bool,
+    pub strict_checkpoint_age: bool,
+}
+
+// Contents of krome.json
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KromeConfig {
+    pub version: u32,
+    // Network start_helios uses when it isn't given a chain ID
+    pub active_chain_id: u64,
+    // Start the client for the active network when the app launches
+    #[serde(default)]
+    pub auto_start: bool,
+    #[serde(default)]
+    pub networks: BTreeMap<u64, NetworkSettings>,
+}
+
+impl Default for KromeConfig {
+    fn default() -> Self {
+        KromeConfig {
+            version: CONFIG_VERSION,
+            active_chain_id: 1,
+            auto_start: false,
+            networks: BTreeMap::new(),
+        }
+    }
+}
+
+impl NetworkSettings {
+    // Every URL in the settings, named by field and position
+    fn urls_mut(&mut self) -> impl Iterator<Item = (String, &mut String)> {
+        let execution = self.execution_rpcs.iter_mut().enumerate().map(|(i, url)| (format!("execution/{}", i), url));
+        let consensus = self.consensus_rpcs.iter_mut().enumerate().map(|(i, url)| (format!("consensus/{}", i), url));
+        let fallback = self.checkpoint_fallback.iter_mut().map(|url| ("checkpointFallback".to_string(), url));
+        execution.chain(consensus).chain(fallback)
+    }
+}
+
+impl KromeConfig {
+    // Settings for a chain, or empty ones if it hasn't been configured
+    pub fn network(&self, chain_id: u64) -> NetworkSettings {
+        self.networks.get(&chain_id).cloned().unwrap_or_default()
+    }
+
+    pub fn validate(&self, config_dir: &Path) -> Result<()> {
+        if self.version != CONFIG_VERSION {
+            return Err(KromeError::InvalidConfig(format!(
+                "expected version {}, got {}",
+                CONFIG_VERSION, self.version
+            )));
+        }
+
+        network::get_network(self.active_chain_id, config_dir)?;
+
+        for (chain_id, settings) in &self.networks {
+            let urls = settings
+                .execution_rpcs
+                .iter()
+                .map(|url| ("executionRpcs", url))
+                .chain(settings.consensus_rpcs.iter().map(|url| ("consensusRpcs", url)))
+                .chain(settings.checkpoint_fallback.iter().map(|url| ("checkpointFallback", url)));
+            for (field, url) in urls {
+                validate_url(url).map_err(|e| {
+                    KromeError::InvalidConfig(format!("networks.{}.{}: {}", chain_id, field, e))
+                })?;
+            }
+        }
+
+        if self.auto_start && self.network(self.active_chain_id).execution_rpcs.is_empty() {
+            return Err(KromeError::InvalidConfig(format!(
+                "autoStart is set but chain {} has no executionRpcs",
+                self.active_chain_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn validate_url(url: &str) -> std::result::Result<(), String> {
+    let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(format!("unsupported scheme {}", scheme)),
+    }
+}
+
+// Userinfo, query strings and paths are where providers put API keys
+fn has_credentials(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| {
+        !url.username().is_empty() || url.password().is_some() || url.query().is_some() || url.path() != "/"
+    })
+}
+
+fn secret_key(chain_id: u64, name: &str) -> String {
+    format!("{}{}/{}", RPC_SECRET_PREFIX, chain_id, name)
+}
+
+// The config with every URL that has credentials replaced by its `secret:`
+// reference, as the webview gets it. update_config resolves the references
+// again, so settings can be changed without the webview seeing API keys.
+pub fn redact(config: &KromeConfig) -> KromeConfig {
+    let mut redacted = config.clone();
+    for (chain_id, settings) in redacted.networks.iter_mut() {
+        for (name, url) in settings.urls_mut() {
+            if has_credentials(url) {
+                *url = format!("{}{}", SECRET_REF_PREFIX, secret_key(*chain_id, &name));
+            }
+        }
+    }
+    redacted
+}
+
+// The config as written to disk, with credential-bearing URLs moved to the
+// store. Secrets no config URL refers to anymore are deleted.
+fn seal(config: &KromeConfig, store: &dyn SecretStore) -> Result<KromeConfig> {
+    let mut sealed = config.clone();
+    let mut kept = BTreeSet::new();
+    for (chain_id, settings) in sealed.networks.iter_mut() {
+        for (name, url) in settings.urls_mut() {
+            if has_credentials(url) {
+                let key = secret_key(*chain_id, &name);
+                store.set(&key, url.as_bytes())?;
+                *url = format!("{}{}", SECRET_REF_PREFIX, key);
+                kept.insert(key);
+            }
+        }
+    }
+    for key in store.keys(RPC_SECRET_PREFIX)? {
+        if !kept.contains(&key) {
+            store.delete(&key)?;
+        }
+    }
+    Ok(sealed)
+}
+
+// Resolves `secret:` references. Returns whether any URL with credentials
+// was stored in plain text, so the caller can seal it. Only RPC URL secrets
+// can be referenced, since the config comes from the webview and resolved
+// URLs can make their way back to it.
+fn unseal(config: &mut KromeConfig, store: &dyn SecretStore) -> Result<bool> {
+    let mut plaintext = false;
+    for settings in config.networks.values_mut() {
+        for (_, url) in settings.urls_mut() {
+            if let Some(key) = url.strip_prefix(SECRET_REF_PREFIX) {
+                if !key.starts_with(RPC_SECRET_PREFIX) {
+                    return Err(KromeError::InvalidConfig(format!("secret {} is not an RPC URL", key)));
+                }
+                *url = secrets::get_string(store, key)?
+                    .ok_or_else(|| KromeError::InvalidConfig(format!("secret {} is missing from the secret store", key)))?;
+            } else if has_credentials(url) {
+                plaintext = true;
+            }
+        }
+    }
+    Ok(plaintext)
+}
+
+// Upgrades an older config file, one version at a time
+fn migrate(mut value: Value) -> Result<Value> {
+    let mut version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| KromeError::InvalidConfig("missing version".to_string()))? as u32;
+    if version == 0 || version > CONFIG_VERSION {
+        return Err(KromeError::InvalidConfig(format!(
+            "unknown version {}",
+            version
+        )));
+    }
+
+    while version < CONFIG_VERSION {
+        value = match version {
+            1 => migrate_v1(value),
+            _ => unreachable!("no migration from config version {}", version),
+        };
+        version += 1;
+    }
+    Ok(value)
+}
+
+// Version 2 allows several execution and consensus RPCs per network
+fn migrate_v1(mut value: Value) -> Value {
+    if let Some(networks) = value.get_mut("networks").and_then(Value::as_object_mut) {
+        for network in networks.values_mut().filter_map(Value::as_object_mut) {
+            for (old, new) in [("executionRpc", "executionRpcs"), ("consensusRpc", "consensusRpcs")] {
+                let urls: Vec<Value> = network.remove(old).filter(|url| !url.is_null()).into_iter().collect();
+                network.insert(new.to_string(), Value::Array(urls));
+            }
+        }
+    }
+    value["version"] = Value::from(2);
+    value
+}
+
+// The loaded config and where it lives on disk
+pub struct ConfigState {
+    path: PathBuf,
+    store: SharedSecretStore,
+    config: RwLock<KromeConfig>,
+}
+
+impl ConfigState {
+    // Reads the config file, migrating it if needed. A missing file gives the
+    // defaults. URLs with credentials are moved to `store` if they aren't there
+    // yet. A hand-edited file is held to the same rules as update_config.
+    pub fn load(config_dir: &Path, store: SharedSecretStore) -> Result<Self> {
+        let path = config_dir.join(CONFIG_FILE);
+
+        let config = if path.exists() {
+            let raw: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            let migrated = raw.get("version") != Some(&Value::from(CONFIG_VERSION));
+            let mut config: KromeConfig = serde_json::from_value(migrate(raw)?)
+                .map_err(|e| KromeError::InvalidConfig(e.to_string()))?;
+            let plaintext = unseal(&mut config, store.as_ref())?;
+            config.validate(config_dir)?;
+            if migrated || plaintext {
+                save(&path, &seal(&config, store.as_ref())?)?;
+            }
+            config
+        } else {
+            KromeConfig::default()
+        };
+
+        Ok(ConfigState {
+            path,
+            store,
+            config: RwLock::new(config),
+        })
+    }
+
+    // The config with all secrets resolved
+    pub async fn get(&self) -> KromeConfig {
+        self.config.read().await.clone()
+    }
+
+    // Pins the checkpoint a network bootstraps from, or unpins it with None
+    pub async fn pin_checkpoint(&self, chain_id: u64, checkpoint: Option<B256>) -> Result<()> {
+        let mut guard = self.config.write().await;
+        let mut config = guard.clone();
+        config.networks.entry(chain_id).or_default().checkpoint = checkpoint;
+        self.save(&config).await?;
+        *guard = config;
+        Ok(())
+    }
+
+    // Validates and persists a new config, replacing the current one. It can
+    // keep the `secret:` references from `redact` for URLs it doesn't change.
+    // Returns the saved config, redacted.
+    pub async fn update(&self, config: KromeConfig, config_dir: &Path) -> Result<KromeConfig> {
+        let mut guard = self.config.write().await;
+        let store = self.store.clone();
+        let config = secrets::blocking(move || {
+            let mut config = config;
+            unseal(&mut config, store.as_ref())?;
+            Ok(config)
+        })
+        .await?;
+        config.validate(config_dir)?;
+
+        self.save(&config).await?;
+        *guard = config.clone();
+        Ok(redact(&config))
+    }
+
+    // Sealing goes through the secret store, so it runs off the async runtime
+    async fn save(&self, config: &KromeConfig) -> Result<()> {
+        let (path, store, config) = (self.path.clone(), self.store.clone(), config.clone());
+        secrets::blocking(move || save(&path, &seal(&config, store.as_ref())?)).await
+    }
+}
+
+fn save(path: &Path, config: &KromeConfig) -> Result<()> {
+    write_atomic(path, &serde_json::to_string_pretty(config)?)
+}
+
+// Writes to a temporary file first so a crash can't leave a half-written file
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::secrets::MemoryStore;
+
+    const KEYED_URL: &str = "https://eth-mainnet.example/v2/SECRETKEY";
+
+    fn config_with_keyed_url() -> KromeConfig {
+        let mut config = KromeConfig::default();
+        config.networks.insert(
+            1,
+            NetworkSettings {
+                execution_rpcs: vec!["https://public.example".to_string(), KEYED_URL.to_string()],
+                ..Default::default()
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn rejects_files_without_a_known_version() {
+        assert!(migrate(json!({ "activeChainId": 1 })).is_err());
+        assert!(migrate(json!({ "version": 0, "activeChainId": 1 })).is_err());
+        assert!(migrate(json!({ "version": CONFIG_VERSION + 1, "activeChainId": 1 })).is_err());
+    }
+
+    #[test]
+    fn migrates_single_rpcs_to_lists() {
+        let migrated = migrate(json!({
+            "version": 1,
+            "activeChainId": 1,
+            "networks": { "1": { "executionRpc": "https://rpc.example", "consensusRpc": null } },
+        }))
+        .unwrap();
+        let config: KromeConfig = serde_json::from_value(migrated).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.network(1).execution_rpcs, vec!["https://rpc.example".to_string()]);
+        assert!(config.network(1).consensus_rpcs.is_empty());
+    }
+
+    #[test]
+    fn redacted_urls_resolve_back() {
+        let store = MemoryStore::default();
+        let config = config_with_keyed_url();
+        let sealed = seal(&config, &store).unwrap();
+
+        let mut redacted = redact(&config);
+        let urls = &redacted.network(1).execution_rpcs;
+        assert_eq!(urls[0], "https://public.example");
+        assert!(urls[1].starts_with(SECRET_REF_PREFIX));
+        assert_eq!(urls, &sealed.network(1).execution_rpcs);
+        assert!(!serde_json::to_string(&redacted).unwrap().contains("SECRETKEY"));
+
+        unseal(&mut redacted, &store).unwrap();
+        assert_eq!(redacted.network(1).execution_rpcs, config.network(1).execution_rpcs);
+    }
+
+    #[test]
+    fn only_rpc_secrets_can_be_referenced() {
+        let store = MemoryStore::default();
+        store.set("wallet/keystore/0xabc", b"{\"crypto\":{}}").unwrap();
+
+        let mut config = KromeConfig::default();
+        config.networks.insert(
+            1,
+            NetworkSettings {
+                execution_rpcs: vec!["secret:wallet/keystore/0xabc".to_string()],
+                ..Default::default()
+            },
+        );
+        assert!(matches!(unseal(&mut config, &store), Err(KromeError::InvalidConfig(_))));
+        assert_eq!(config.network(1).execution_rpcs, vec!["secret:wallet/keystore/0xabc".to_string()]);
+    }
+
+    #[test]
+    fn sealing_drops_secrets_no_longer_used() {
+        let store = MemoryStore::default();
+        seal(&config_with_keyed_url(), &store).unwrap();
+        assert_eq!(store.keys(RPC_SECRET_PREFIX).unwrap().len(), 1);
+
+        seal(&KromeConfig::default(), &store).unwrap();
+        assert!(store.keys(RPC_SECRET_PREFIX).unwrap().is_empty());
+    }
+}
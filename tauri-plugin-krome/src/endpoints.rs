@@ -0,0 +1,594 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use alloy::hex;
+use serde::Serialize;
+use serde_json::json;
+use tauri::async_runtime::JoinHandle;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+
+use crate::error::{KromeError, Result};
+
+// Helios only takes a single execution and consensus URL, so when several are
+// configured it is pointed at a local proxy instead. The proxy forwards each
+// request to the fastest healthy upstream and fails over to the next one.
+// Upstream URLs often carry API keys, so the proxy only answers requests
+// under a random path token that only Helios is given.
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(120);
+// Weight of the newest sample in the latency moving average
+const LATENCY_ALPHA: f64 = 0.3;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointKind {
+    Execution,
+    Consensus,
+}
+
+impl EndpointKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EndpointKind::Execution => "execution",
+            EndpointKind::Consensus => "consensus",
+        }
+    }
+}
+
+struct Endpoint {
+    url: String,
+    latency_ms: Option<f64>,
+    consecutive_failures: u32,
+    backoff_until: Option<Instant>,
+    last_error: Option<String>,
+    last_checked: Option<Instant>,
+}
+
+impl Endpoint {
+    fn in_backoff(&self, now: Instant) -> bool {
+        self.backoff_until.is_some_and(|until| until > now)
+    }
+}
+
+// Health of one upstream, as reported to the front end. Upstreams are listed
+// in config order, each by scheme and host only since the rest of the URL
+// often holds an API key.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointHealth {
+    pub kind: EndpointKind,
+    pub url: String,
+    pub healthy: bool,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub backoff_remaining_ms: u64,
+    pub last_error: Option<String>,
+    pub last_checked_ms_ago: Option<u64>,
+}
+
+pub struct EndpointPool {
+    kind: EndpointKind,
+    endpoints: Mutex<Vec<Endpoint>>,
+    http: reqwest::Client,
+}
+
+impl EndpointPool {
+    pub fn new(kind: EndpointKind, urls: Vec<String>) -> Self {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                url: url.trim_end_matches('/').to_string(),
+                latency_ms: None,
+                consecutive_failures: 0,
+                backoff_until: None,
+                last_error: None,
+                last_checked: None,
+            })
+            .collect();
+
+        EndpointPool {
+            kind,
+            endpoints: Mutex::new(endpoints),
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("default TLS backend is available"),
+        }
+    }
+
+    // Upstreams to try, best first: those out of backoff ordered by latency,
+    // then those still backing off in case nothing else is left
+    fn candidates(&self) -> Vec<String> {
+        let now = Instant::now();
+        let endpoints = self.endpoints.lock().unwrap();
+
+        let mut ranked: Vec<&Endpoint> = endpoints.iter().collect();
+        ranked.sort_by(|a, b| {
+            let key = |e: &Endpoint| (e.in_backoff(now), e.latency_ms.unwrap_or(f64::MAX));
+            key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        ranked.into_iter().map(|e| e.url.clone()).collect()
+    }
+
+    fn record_success(&self, url: &str, latency: Duration) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+            let sample = latency.as_secs_f64() * 1000.0;
+            endpoint.latency_ms = Some(match endpoint.latency_ms {
+                Some(avg) => avg + LATENCY_ALPHA * (sample - avg),
+                None => sample,
+            });
+            endpoint.consecutive_failures = 0;
+            endpoint.backoff_until = None;
+            endpoint.last_checked = Some(Instant::now());
+        }
+    }
+
+    fn record_failure(&self, url: &str, error: String) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+            endpoint.consecutive_failures += 1;
+            let backoff = BACKOFF_BASE
+                .saturating_mul(1 << endpoint.consecutive_failures.min(16))
+                .min(BACKOFF_MAX);
+            endpoint.backoff_until = Some(Instant::now() + backoff);
+            endpoint.last_error = Some(error);
+            endpoint.last_checked = Some(Instant::now());
+        }
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        let now = Instant::now();
+        self.endpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| EndpointHealth {
+                kind: self.kind,
+                url: display_url(&e.url),
+                healthy: !e.in_backoff(now) && e.consecutive_failures == 0,
+                latency_ms: e.latency_ms.map(|ms| ms.round() as u64),
+                consecutive_failures: e.consecutive_failures,
+                backoff_remaining_ms: e
+                    .backoff_until
+                    .map(|until| until.saturating_duration_since(now).as_millis() as u64)
+                    .unwrap_or(0),
+                last_error: e.last_error.as_deref().map(redact_urls),
+                last_checked_ms_ago: e.last_checked.map(|t| now.duration_since(t).as_millis() as u64),
+            })
+            .collect()
+    }
+
+    // Sends one request upstream. Rejected API keys, rate limits and server
+    // errors count as failures so the next endpoint gets a turn. Other client
+    // errors are passed back, since they're about the request.
+    async fn send(
+        &self,
+        url: &str,
+        method: Method,
+        path: &str,
+        content_type: Option<&str>,
+        body: Bytes,
+    ) -> std::result::Result<(StatusCode, Option<String>, Bytes), String> {
+        let started = Instant::now();
+        let mut request = self.http.request(method, upstream_url(url, path)?).body(body);
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        if matches!(
+            status,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        ) || status.is_server_error()
+        {
+            return Err(format!("upstream returned {}", status));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+        self.record_success(url, started.elapsed());
+        Ok((status, content_type, bytes))
+    }
+
+    async fn forward(&self, method: Method, path: &str, headers: &HeaderMap, body: Bytes) -> Response {
+        let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+
+        let mut last_error = "no endpoints configured".to_string();
+        for url in self.candidates() {
+            match self.send(&url, method.clone(), path, content_type, body.clone()).await {
+                Ok((status, content_type, bytes)) => {
+                    let mut response = (status, bytes).into_response();
+                    if let Some(value) = content_type.and_then(|v| v.parse().ok()) {
+                        response.headers_mut().insert(header::CONTENT_TYPE, value);
+                    }
+                    return response;
+                }
+                Err(e) => {
+                    self.record_failure(&url, e.clone());
+                    last_error = e;
+                }
+            }
+        }
+        (StatusCode::BAD_GATEWAY, last_error).into_response()
+    }
+
+    // Probes every endpoint, including ones in backoff, so recovered
+    // endpoints are picked up again
+    async fn check_all(&self) {
+        let urls: Vec<String> = self.endpoints.lock().unwrap().iter().map(|e| e.url.clone()).collect();
+        for url in urls {
+            let result = match self.kind {
+                EndpointKind::Execution => {
+                    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": [] });
+                    self.send(&url, Method::POST, "", Some("application/json"), Bytes::from(body.to_string()))
+                        .await
+                }
+                EndpointKind::Consensus => {
+                    self.send(&url, Method::GET, "/eth/v1/beacon/light_client/finality_update", None, Bytes::new())
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                self.record_failure(&url, e);
+            }
+        }
+    }
+}
+
+// Scheme, host and port of an upstream URL
+fn display_url(url: &str) -> String {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return "invalid URL".to_string();
+    };
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}://{}:{}", url.scheme(), host, port),
+        (Some(host), None) => format!("{}://{}", url.scheme(), host),
+        (None, _) => format!("{}:", url.scheme()),
+    }
+}
+
+// Cuts every URL in an error message down to its `display_url`. HTTP errors
+// name the URL they were sending to, API key included.
+fn redact_urls(text: &str) -> String {
+    let mut redacted = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find("://") {
+        let start = rest[..at]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let end = rest[at..]
+            .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '\'' | '>' | ','))
+            .map_or(rest.len(), |i| at + i);
+        redacted.push_str(&rest[..start]);
+        redacted.push_str(&display_url(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+// Adds a proxied path and query to an upstream URL, keeping the upstream's
+// own path and query since either may hold an API key
+fn upstream_url(base: &str, path: &str) -> std::result::Result<reqwest::Url, String> {
+    let mut url = reqwest::Url::parse(base).map_err(|e| format!("invalid upstream URL: {}", e))?;
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (path, ""),
+    };
+    if !path.is_empty() {
+        let joined = format!("{}{}", url.path().trim_end_matches('/'), path);
+        url.set_path(&joined);
+    }
+    if !query.is_empty() {
+        let joined = match url.query() {
+            Some(existing) if !existing.is_empty() => format!("{}&{}", existing, query),
+            _ => query.to_string(),
+        };
+        url.set_query(Some(&joined));
+    }
+    Ok(url)
+}
+
+struct ProxyState {
+    pool: Arc<EndpointPool>,
+    token: String,
+}
+
+// Strips the leading `/<token>` from a request path, or returns None if the
+// request doesn't carry it
+fn strip_token<'a>(path: &'a str, token: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix('/')?.strip_prefix(token)?;
+    match rest.chars().next() {
+        None | Some('/') | Some('?') => Some(rest),
+        Some(_) => None,
+    }
+}
+
+async fn proxy(
+    State(state): State<Arc<ProxyState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let Some(path) = strip_token(path, &state.token) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Execution RPCs are usually served from the URL itself, not a subpath
+    let path = if path == "/" { "" } else { path };
+    state.pool.forward(method, path, &headers, body).await
+}
+
+// A running proxy and health checker for one pool. Both stop when it's dropped.
+pub struct PoolProxy {
+    pub pool: Arc<EndpointPool>,
+    // Includes the path token
+    pub url: String,
+    server: JoinHandle<()>,
+    health_checks: JoinHandle<()>,
+}
+
+impl PoolProxy {
+    pub async fn spawn(kind: EndpointKind, urls: Vec<String>) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(KromeError::InvalidConfig(format!("no {} RPC configured", kind.name())));
+        }
+
+        let pool = Arc::new(EndpointPool::new(kind, urls));
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).await?;
+        let token = hex::encode(rand::random::<[u8; 16]>());
+        let url = format!("http://{}/{}", listener.local_addr()?, token);
+
+        let state = Arc::new(ProxyState {
+            pool: pool.clone(),
+            token,
+        });
+        let router = Router::new().fallback(proxy).with_state(state);
+        let server = tauri::async_runtime::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        let checked = pool.clone();
+        let health_checks = tauri::async_runtime::spawn(async move {
+            let mut ticker = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                checked.check_all().await;
+            }
+        });
+
+        Ok(PoolProxy {
+            pool,
+            url,
+            server,
+            health_checks,
+        })
+    }
+}
+
+impl Drop for PoolProxy {
+    fn drop(&mut self) {
+        self.server.abort();
+        self.health_checks.abort();
+    }
+}
+
+// Proxies for the execution and consensus endpoints of a running client
+pub struct RpcRouter {
+    pub execution: PoolProxy,
+    pub consensus: PoolProxy,
+}
+
+impl RpcRouter {
+    pub async fn spawn(execution_rpcs: Vec<String>, consensus_rpcs: Vec<String>) -> Result<Self> {
+        Ok(RpcRouter {
+            execution: PoolProxy::spawn(EndpointKind::Execution, execution_rpcs).await?,
+            consensus: PoolProxy::spawn(EndpointKind::Consensus, consensus_rpcs).await?,
+        })
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        let mut health = self.execution.pool.health();
+        health.extend(self.consensus.pool.health());
+        health
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serves `status` and `body` for every request, returning the server's URL
+    async fn mock_upstream(status: StatusCode, body: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new().fallback(move || async move { (status, body) });
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    // A URL nothing is listening on
+    async fn closed_upstream() -> String {
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    async fn body_of(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_the_next_endpoint() {
+        let failing = mock_upstream(StatusCode::INTERNAL_SERVER_ERROR, "down").await;
+        let working = mock_upstream(StatusCode::OK, "ok").await;
+        let pool = EndpointPool::new(EndpointKind::Execution, vec![failing.clone(), working.clone()]);
+
+        let response = pool.forward(Method::POST, "", &HeaderMap::new(), Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_of(response).await, "ok");
+
+        let health = pool.health();
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert!(health[0].backoff_remaining_ms > 0);
+        assert!(health[1].healthy);
+        assert!(health[1].latency_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn rate_limits_and_refused_connections_count_as_failures() {
+        let limited = mock_upstream(StatusCode::TOO_MANY_REQUESTS, "slow down").await;
+        let closed = closed_upstream().await;
+        let pool = EndpointPool::new(EndpointKind::Execution, vec![limited, closed]);
+
+        let response = pool.forward(Method::POST, "", &HeaderMap::new(), Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(pool.health().iter().all(|e| e.consecutive_failures == 1));
+    }
+
+    #[tokio::test]
+    async fn rejected_api_keys_count_as_failures() {
+        let unauthorized = mock_upstream(StatusCode::UNAUTHORIZED, "bad key").await;
+        let forbidden = mock_upstream(StatusCode::FORBIDDEN, "over quota").await;
+        let working = mock_upstream(StatusCode::OK, "ok").await;
+        let pool = EndpointPool::new(EndpointKind::Execution, vec![unauthorized, forbidden, working]);
+
+        let response = pool.forward(Method::POST, "", &HeaderMap::new(), Bytes::new()).await;
+        assert_eq!(body_of(response).await, "ok");
+        let failures: Vec<u32> = pool.health().iter().map(|e| e.consecutive_failures).collect();
+        assert_eq!(failures, vec![1, 1, 0]);
+
+        // Other client errors are about the request, so they're passed back
+        let bad_request = mock_upstream(StatusCode::BAD_REQUEST, "bad request").await;
+        let pool = EndpointPool::new(EndpointKind::Consensus, vec![bad_request]);
+        let response = pool.forward(Method::GET, "/eth/v1/x", &HeaderMap::new(), Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(pool.health()[0].healthy);
+    }
+
+    #[test]
+    fn paths_and_queries_are_added_to_upstream_urls() {
+        let url = |base, path| upstream_url(base, path).unwrap().to_string();
+        assert_eq!(url("https://rpc.example/v2/KEY", ""), "https://rpc.example/v2/KEY");
+        assert_eq!(url("https://rpc.example/v2/KEY/", ""), "https://rpc.example/v2/KEY/");
+        assert_eq!(url("https://rpc.example/?apikey=KEY", ""), "https://rpc.example/?apikey=KEY");
+        assert_eq!(
+            url("https://beacon.example/KEY/", "/eth/v1/beacon/genesis"),
+            "https://beacon.example/KEY/eth/v1/beacon/genesis"
+        );
+        assert_eq!(
+            url("https://beacon.example?apikey=KEY", "/eth/v1/light_client/updates?start_period=1&count=1"),
+            "https://beacon.example/eth/v1/light_client/updates?apikey=KEY&start_period=1&count=1"
+        );
+        assert_eq!(url("https://rpc.example", "?x=1"), "https://rpc.example/?x=1");
+        assert!(upstream_url("not a url", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn health_leaves_out_api_keys() {
+        let limited = mock_upstream(StatusCode::TOO_MANY_REQUESTS, "slow down").await;
+        let closed = closed_upstream().await;
+        let pool = EndpointPool::new(
+            EndpointKind::Execution,
+            vec![format!("{}/v2/SECRETKEY", limited), format!("{}/?apikey=SECRETKEY", closed)],
+        );
+        pool.forward(Method::POST, "", &HeaderMap::new(), Bytes::new()).await;
+
+        let health = pool.health();
+        assert_eq!(health[0].url, limited);
+        assert_eq!(health[1].url, closed);
+        assert!(health.iter().all(|e| e.last_error.is_some()));
+        assert!(!serde_json::to_string(&health).unwrap().contains("SECRETKEY"));
+    }
+
+    #[test]
+    fn urls_are_cut_from_errors() {
+        assert_eq!(
+            redact_urls("error sending request for url (https://rpc.example/v2/KEY?x=1): timed out"),
+            "error sending request for url (https://rpc.example): timed out"
+        );
+        assert_eq!(
+            redact_urls("http://127.0.0.1:8545/KEY and wss://ws.example/KEY"),
+            "http://127.0.0.1:8545 and wss://ws.example"
+        );
+        assert_eq!(redact_urls("upstream returned 429"), "upstream returned 429");
+    }
+
+    #[tokio::test]
+    async fn endpoints_in_backoff_are_tried_last() {
+        let first = mock_upstream(StatusCode::BAD_GATEWAY, "down").await;
+        let second = mock_upstream(StatusCode::OK, "ok").await;
+        let pool = EndpointPool::new(EndpointKind::Execution, vec![first.clone(), second.clone()]);
+
+        pool.forward(Method::POST, "", &HeaderMap::new(), Bytes::new()).await;
+        assert_eq!(pool.candidates(), vec![second, first]);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let pool = EndpointPool::new(EndpointKind::Consensus, vec!["http://upstream".to_string()]);
+        let mut last = 0;
+        for _ in 0..3 {
+            pool.record_failure("http://upstream", "down".to_string());
+            let remaining = pool.health()[0].backoff_remaining_ms;
+            assert!(remaining > last);
+            last = remaining;
+        }
+        for _ in 0..20 {
+            pool.record_failure("http://upstream", "down".to_string());
+        }
+        assert!(pool.health()[0].backoff_remaining_ms <= BACKOFF_MAX.as_millis() as u64);
+
+        pool.record_success("http://upstream", Duration::from_millis(5));
+        let health = &pool.health()[0];
+        assert!(health.healthy);
+        assert_eq!(health.backoff_remaining_ms, 0);
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        assert_eq!(strip_token("/abc", "abc"), Some(""));
+        assert_eq!(strip_token("/abc/eth/v1/beacon", "abc"), Some("/eth/v1/beacon"));
+        assert_eq!(strip_token("/abc?x=1", "abc"), Some("?x=1"));
+        assert_eq!(strip_token("/", "abc"), None);
+        assert_eq!(strip_token("/abcd", "abc"), None);
+        assert_eq!(strip_token("/eth/v1/beacon", "abc"), None);
+    }
+
+    #[tokio::test]
+    async fn proxy_rejects_requests_without_the_token() {
+        let upstream = mock_upstream(StatusCode::OK, "ok").await;
+        let proxy = PoolProxy::spawn(EndpointKind::Execution, vec![upstream]).await.unwrap();
+        let http = reqwest::Client::new();
+
+        let response = http.post(&proxy.url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        let (base, _) = proxy.url.rsplit_once('/').unwrap();
+        let response = http.post(format!("{}/", base)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,190 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use alloy::primitives::{Address, Bytes, B256};
+use alloy::signers::local::LocalSignerError;
+use alloy::sol_types::decode_revert_reason;
+use helios::core::execution::errors::EvmError;
+
+// Errors returned by every Krome command. They reach the front end as
+// `{ code, message, details }`, where `code` is stable and safe to match on.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum KromeError {
+    #[error("client not started")]
+    NotStarted,
+    #[error("client not synced yet")]
+    NotSynced,
+    #[error("client already started")]
+    AlreadyStarted,
+    #[error("unsupported chain ID: {0}")]
+    UnsupportedNetwork(u64),
+    #[error("invalid network config: {0}")]
+    InvalidNetworkConfig(String),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("RPC unreachable: {0}")]
+    RpcUnreachable(String),
+    #[error("invalid checkpoint: {0}")]
+    InvalidCheckpoint(String),
+    #[error("checkpoint too old: {0}")]
+    CheckpointTooOld(String),
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+    #[error("execution reverted{}", reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default())]
+    ExecutionReverted { reason: Option<String>, data: Bytes },
+    #[error("no account {0} in the keystore")]
+    AccountNotFound(Address),
+    #[error("account {0} is already in the keystore")]
+    AccountExists(Address),
+    #[error("account {0} is locked")]
+    AccountLocked(Address),
+    #[error("wrong password")]
+    InvalidPassword,
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+    #[error("keystore error: {0}")]
+    Keystore(String),
+    #[error("secret store error: {0}")]
+    SecretStore(String),
+    #[error("signing failed: {0}")]
+    Signing(String),
+    #[error("transaction {0} is not in the queue")]
+    TransactionNotFound(B256),
+    #[error("user rejected the request")]
+    UserRejected,
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("approval window error: {0}")]
+    Approval(String),
+    #[error("simulation failed: {0}")]
+    Simulation(String),
+    #[error("fork error: {0}")]
+    Fork(String),
+    #[error("light client error: {0}")]
+    Helios(String),
+    #[error("serialization error: {0}")]
+    Serialization(String),
+    #[error("could not resolve the app {0} directory")]
+    Path(&'static str),
+    #[error("I/O error: {0}")]
+    Io(String),
+}
+
+pub type Result<T> = std::result::Result<T, KromeError>;
+
+impl KromeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            KromeError::NotStarted => "client_not_started",
+            KromeError::NotSynced => "client_not_synced",
+            KromeError::AlreadyStarted => "client_already_started",
+            KromeError::UnsupportedNetwork(_) => "unsupported_network",
+            KromeError::InvalidNetworkConfig(_) => "invalid_network_config",
+            KromeError::InvalidConfig(_) => "invalid_config",
+            KromeError::RpcUnreachable(_) => "rpc_unreachable",
+            KromeError::InvalidCheckpoint(_) => "invalid_checkpoint",
+            KromeError::CheckpointTooOld(_) => "checkpoint_too_old",
+            KromeError::InvalidParams(_) => "invalid_params",
+            KromeError::ExecutionReverted { .. } => "execution_reverted",
+            KromeError::AccountNotFound(_) => "account_not_found",
+            KromeError::AccountExists(_) => "account_exists",
+            KromeError::AccountLocked(_) => "account_locked",
+            KromeError::InvalidPassword => "invalid_password",
+            KromeError::InvalidMnemonic(_) => "invalid_mnemonic",
+            KromeError::Keystore(_) => "keystore_error",
+            KromeError::SecretStore(_) => "secret_store_error",
+            KromeError::Signing(_) => "signing_error",
+            KromeError::TransactionNotFound(_) => "transaction_not_found",
+            KromeError::UserRejected => "user_rejected",
+            KromeError::Unauthorized(_) => "unauthorized",
+            KromeError::Approval(_) => "approval_error",
+            KromeError::Simulation(_) => "simulation_error",
+            KromeError::Fork(_) => "fork_error",
+            KromeError::Helios(_) => "helios_error",
+            KromeError::Serialization(_) => "serialization_error",
+            KromeError::Path(_) => "path_error",
+            KromeError::Io(_) => "io_error",
+        }
+    }
+
+    // Extra context for the front end, beyond the message
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            KromeError::UnsupportedNetwork(chain_id) => Some(serde_json::json!({ "chainId": chain_id })),
+            KromeError::ExecutionReverted { reason, data } => {
+                Some(serde_json::json!({ "reason": reason, "data": data }))
+            }
+            KromeError::AccountNotFound(address)
+            | KromeError::AccountExists(address)
+            | KromeError::AccountLocked(address) => Some(serde_json::json!({ "address": address })),
+            KromeError::TransactionNotFound(hash) => Some(serde_json::json!({ "hash": hash })),
+            KromeError::Path(dir) => Some(serde_json::json!({ "directory": dir })),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for KromeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("KromeError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("details", &self.details())?;
+        s.end()
+    }
+}
+
+impl From<eyre::Report> for KromeError {
+    fn from(e: eyre::Report) -> Self {
+        let message = format!("{:#}", e);
+
+        // Reverts from calls Helios ran locally, with the reason decoded if
+        // the contract gave one
+        if let Some(EvmError::Revert(data)) = e.chain().find_map(|cause| cause.downcast_ref::<EvmError>()) {
+            let data = data.clone().unwrap_or_default();
+            return KromeError::ExecutionReverted {
+                reason: decode_revert_reason(&data),
+                data,
+            };
+        }
+
+        if e.chain().any(|cause| cause.downcast_ref::<reqwest::Error>().is_some()) {
+            return KromeError::RpcUnreachable(message);
+        }
+        // Helios surfaces this as a plain consensus error, so match on its text
+        if message.to_lowercase().contains("checkpoint too old") {
+            return KromeError::CheckpointTooOld(message);
+        }
+        KromeError::Helios(message)
+    }
+}
+
+impl From<serde_json::Error> for KromeError {
+    fn from(e: serde_json::Error) -> Self {
+        KromeError::Serialization(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for KromeError {
+    fn from(e: std::io::Error) -> Self {
+        KromeError::Io(e.to_string())
+    }
+}
+
+impl From<LocalSignerError> for KromeError {
+    fn from(e: LocalSignerError) -> Self {
+        let message = e.to_string();
+        // eth-keystore reports a wrong password as a MAC mismatch
+        if message.to_lowercase().contains("mac mismatch") {
+            return KromeError::InvalidPassword;
+        }
+        KromeError::Keystore(message)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<secret_service::Error> for KromeError {
+    fn from(e: secret_service::Error) -> Self {
+        KromeError::SecretStore(e.to_string())
+    }
+}
@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+use alloy::consensus::{Transaction as _, TxReceipt as _};
+use alloy::primitives::{Address, Bytes, B256, U256, U64};
+use alloy::rpc::types::{Block, Filter, TransactionReceipt, TransactionRequest};
+use helios::core::types::BlockTag;
+
+use crate::error::{KromeError, Result};
+use crate::helios::{HeliosClient, HeliosState};
+use crate::simulation;
+
+// Read-only Ethereum commands. Everything goes through the Helios client, so
+// each value is checked against the light client's proven state. Quantities
+// come back hex-encoded, as in the JSON-RPC spec.
+
+// Most blocks eth_feeHistory will look at in one call
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+
+// A block tag or number, as commands accept it: "latest", "finalized", "0x10"
+// or 16. Omitted means latest.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BlockParam {
+    Number(u64),
+    Tag(String),
+}
+
+impl BlockParam {
+    pub fn into_tag(self) -> Result<BlockTag> {
+        match self {
+            BlockParam::Number(number) => Ok(BlockTag::Number(number)),
+            BlockParam::Tag(tag) => parse_block_tag(&tag),
+        }
+    }
+}
+
+pub fn parse_block_tag(tag: &str) -> Result<BlockTag> {
+    match tag {
+        "latest" => Ok(BlockTag::Latest),
+        "finalized" => Ok(BlockTag::Finalized),
+        // Helios has no mempool view, doesn't track the safe head and only
+        // keeps recent blocks, so reading these as some other block would
+        // give the wrong answer
+        "pending" | "safe" | "earliest" => {
+            Err(KromeError::InvalidParams(format!("unsupported block tag: {}", tag)))
+        }
+        _ => parse_quantity(tag).map(BlockTag::Number),
+    }
+}
+
+pub fn parse_quantity(value: &str) -> Result<u64> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| KromeError::InvalidParams(format!("invalid block number or tag: {}", value)))
+}
+
+fn block_tag(block: Option<BlockParam>) -> Result<BlockTag> {
+    block.map(BlockParam::into_tag).transpose().map(|tag| tag.unwrap_or(BlockTag::Latest))
+}
+
+// Result of eth_feeHistory
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    pub oldest_block: U64,
+    // One entry per block plus the base fee of the block after the newest
+    pub base_fee_per_gas: Vec<U256>,
+    pub gas_used_ratio: Vec<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward: Option<Vec<Vec<U256>>>,
+}
+
+// Number of the block after `newest`, which the fee history also covers
+fn block_after(newest: u64) -> Result<u64> {
+    newest
+        .checked_add(1)
+        .ok_or_else(|| KromeError::InvalidParams(format!("block {} is past the last possible block", newest)))
+}
+
+// Builds eth_feeHistory from verified blocks, since Helios doesn't proxy it
+pub async fn build_fee_history(
+    client: &HeliosClient,
+    block_count: u64,
+    newest_block: BlockTag,
+    reward_percentiles: Option<Vec<f64>>,
+) -> Result<FeeHistory> {
+    if let Some(percentiles) = &reward_percentiles {
+        let sorted = percentiles.windows(2).all(|w| w[0] <= w[1]);
+        if !sorted || percentiles.iter().any(|p| !(0.0..=100.0).contains(p)) {
+            return Err(KromeError::InvalidParams(
+                "reward percentiles must be ascending values between 0 and 100".to_string(),
+            ));
+        }
+    }
+
+    let newest = match newest_block {
+        BlockTag::Number(number) => number,
+        tag => block_number_of(client, tag).await?,
+    };
+    let after_newest = block_after(newest)?;
+    let block_count = block_count.clamp(1, MAX_FEE_HISTORY_BLOCKS).min(after_newest);
+
+    let mut base_fee_per_gas = Vec::new();
+    let mut gas_used_ratio = Vec::new();
+    let mut reward = reward_percentiles.as_ref().map(|_| Vec::new());
+    let mut oldest = after_newest;
+
+    // Walks back from the newest block. The client only keeps a window of
+    // recent blocks, so the history stops at the first one it no longer has
+    // instead of failing.
+    for number in (after_newest - block_count..=newest).rev() {
+        let full_txs = reward_percentiles.is_some();
+        let block = match client.get_block_by_number(BlockTag::Number(number), full_txs).await {
+            Ok(Some(block)) => block,
+            Ok(None) | Err(_) if number < newest => break,
+            Ok(None) => return Err(KromeError::Helios(format!("block {} not found", number))),
+            Err(e) => return Err(e.into()),
+        };
+        let receipts = match &reward_percentiles {
+            Some(_) => match client.get_block_receipts(BlockTag::Number(number)).await {
+                Ok(Some(receipts)) => receipts,
+                Ok(None) | Err(_) if number < newest => break,
+                receipts => receipts?.unwrap_or_default(),
+            },
+            None => Vec::new(),
+        };
+        let header = &block.header;
+        let base_fee = header.base_fee_per_gas.unwrap_or_default();
+
+        // The base fee after the newest block is part of the response too
+        if number == newest {
+            base_fee_per_gas.push(U256::from(next_base_fee(header.gas_used, header.gas_limit, base_fee)));
+        }
+        base_fee_per_gas.push(U256::from(base_fee));
+        gas_used_ratio.push(if header.gas_limit == 0 {
+            0.0
+        } else {
+            header.gas_used as f64 / header.gas_limit as f64
+        });
+        if let (Some(percentiles), Some(reward)) = (&reward_percentiles, reward.as_mut()) {
+            reward.push(block_rewards(&block, &receipts, base_fee, percentiles));
+        }
+        oldest = number;
+    }
+
+    base_fee_per_gas.reverse();
+    gas_used_ratio.reverse();
+    if let Some(reward) = reward.as_mut() {
+        reward.reverse();
+    }
+
+    Ok(FeeHistory {
+        oldest_block: U64::from(oldest),
+        base_fee_per_gas,
+        gas_used_ratio,
+        reward,
+    })
+}
+
+async fn block_number_of(client: &HeliosClient, tag: BlockTag) -> Result<u64> {
+    let block = client
+        .get_block_by_number(tag, false)
+        .await?
+        .ok_or_else(|| KromeError::Helios(format!("block {} not found", tag)))?;
+    Ok(block.header.number)
+}
+
+// Effective priority fee at each percentile of gas used in the block, as
+// geth computes it
+fn block_rewards(
+    block: &Block,
+    receipts: &[TransactionReceipt],
+    base_fee: u64,
+    percentiles: &[f64],
+) -> Vec<U256> {
+    let txs = block.transactions.as_transactions().unwrap_or_default();
+    if txs.is_empty() || txs.len() != receipts.len() {
+        return vec![U256::ZERO; percentiles.len()];
+    }
+
+    // Receipts hold cumulative gas, so each tx used the difference
+    let mut previous = 0u64;
+    let mut tips: Vec<(u128, u64)> = txs
+        .iter()
+        .zip(receipts)
+        .map(|(tx, receipt)| {
+            let cumulative = receipt.inner.cumulative_gas_used();
+            let gas_used = cumulative.saturating_sub(previous);
+            previous = cumulative;
+            (tx.effective_tip_per_gas(base_fee).unwrap_or_default(), gas_used)
+        })
+        .collect();
+    tips.sort_by_key(|(tip, _)| *tip);
+
+    let gas_used = block.header.gas_used as f64;
+    percentiles
+        .iter()
+        .map(|percentile| {
+            let threshold = gas_used * percentile / 100.0;
+            let mut sum = 0u64;
+            let tip = tips
+                .iter()
+                .find(|(_, gas)| {
+                    sum += gas;
+                    sum as f64 >= threshold
+                })
+                .or(tips.last())
+                .map(|(tip, _)| *tip)
+                .unwrap_or_default();
+            U256::from(tip)
+        })
+        .collect()
+}
+
+// EIP-1559 base fee of the block after one with these values
+pub(crate) fn next_base_fee(gas_used: u64, gas_limit: u64, base_fee: u64) -> u64 {
+    let target = gas_limit / 2;
+    if target == 0 || gas_used == target {
+        return base_fee;
+    }
+    let delta = |diff: u64| (base_fee as u128 * diff as u128 / target as u128 / 8) as u64;
+    if gas_used > target {
+        base_fee + delta(gas_used - target).max(1)
+    } else {
+        base_fee.saturating_sub(delta(target - gas_used))
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_latest_block(state: State<'_, HeliosState>) -> Result<Value> {
+    let client = state.client().await?;
+    let block = client
+        .get_block_by_number(BlockTag::Latest, false)
+        .await?;
+
+    Ok(serde_json::to_value(block)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_balance(
+    state: State<'_, HeliosState>,
+    address: Address,
+    block: Option<BlockParam>,
+) -> Result<U256> {
+    let client = state.client().await?;
+    Ok(client.get_balance(address, block_tag(block)?).await?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_transaction_count(
+    state: State<'_, HeliosState>,
+    address: Address,
+    block: Option<BlockParam>,
+) -> Result<U64> {
+    let client = state.client().await?;
+    Ok(U64::from(client.get_nonce(address, block_tag(block)?).await?))
+}
+
+#[tauri::command]
+pub(crate) async fn get_code(
+    state: State<'_, HeliosState>,
+    address: Address,
+    block: Option<BlockParam>,
+) -> Result<Value> {
+    let client = state.client().await?;
+    Ok(serde_json::to_value(client.get_code(address, block_tag(block)?).await?)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_storage_at(
+    state: State<'_, HeliosState>,
+    address: Address,
+    slot: U256,
+    block: Option<BlockParam>,
+) -> Result<B256> {
+    let client = state.client().await?;
+    let value = client
+        .get_storage_at(address, B256::from(slot), block_tag(block)?)
+        .await?;
+    Ok(B256::from(value))
+}
+
+#[tauri::command]
+pub(crate) async fn get_block_by_number(
+    state: State<'_, HeliosState>,
+    block: Option<BlockParam>,
+    full_transactions: Option<bool>,
+) -> Result<Value> {
+    let client = state.client().await?;
+    let block = client
+        .get_block_by_number(block_tag(block)?, full_transactions.unwrap_or(false))
+        .await?;
+    Ok(serde_json::to_value(block)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_block_by_hash(
+    state: State<'_, HeliosState>,
+    hash: B256,
+    full_transactions: Option<bool>,
+) -> Result<Value> {
+    let client = state.client().await?;
+    let block = client
+        .get_block_by_hash(hash, full_transactions.unwrap_or(false))
+        .await?;
+    Ok(serde_json::to_value(block)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_transaction_by_hash(state: State<'_, HeliosState>, hash: B256) -> Result<Value> {
+    let client = state.client().await?;
+    Ok(serde_json::to_value(client.get_transaction_by_hash(hash).await)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_transaction_receipt(state: State<'_, HeliosState>, hash: B256) -> Result<Value> {
+    let client = state.client().await?;
+    Ok(serde_json::to_value(client.get_transaction_receipt(hash).await?)?)
+}
+
+#[tauri::command]
+pub(crate) async fn get_logs(state: State<'_, HeliosState>, filter: Filter) -> Result<Value> {
+    let client = state.client().await?;
+    Ok(serde_json::to_value(client.get_logs(&filter).await?)?)
+}
+
+#[tauri::command]
+pub(crate) async fn gas_price(state: State<'_, HeliosState>) -> Result<U256> {
+    let client = state.client().await?;
+    Ok(client.get_gas_price().await?)
+}
+
+#[tauri::command]
+pub(crate) async fn max_priority_fee(state: State<'_, HeliosState>) -> Result<U256> {
+    let client = state.client().await?;
+    Ok(client.get_priority_fee().await?)
+}
+
+#[tauri::command]
+pub(crate) async fn fee_history(
+    state: State<'_, HeliosState>,
+    block_count: u64,
+    newest_block: Option<BlockParam>,
+    reward_percentiles: Option<Vec<f64>>,
+) -> Result<FeeHistory> {
+    let client = state.client().await?;
+    build_fee_history(&client, block_count, block_tag(newest_block)?, reward_percentiles).await
+}
+
+#[tauri::command]
+pub(crate) async fn chain_id(state: State<'_, HeliosState>) -> Result<U64> {
+    let client = state.client().await?;
+    Ok(U64::from(client.chain_id().await))
+}
+
+#[tauri::command]
+pub(crate) async fn block_number(state: State<'_, HeliosState>) -> Result<U64> {
+    let client = state.client().await?;
+    Ok(U64::from(client.get_block_number().await?.to::<u64>()))
+}
+
+// Runs the call locally against proven state. Reverts come back as
+// KromeError::ExecutionReverted with the decoded reason.
+#[tauri::command]
+pub(crate) async fn call(
+    state: State<'_, HeliosState>,
+    tx: TransactionRequest,
+    block: Option<BlockParam>,
+) -> Result<Bytes> {
+    let client = state.client().await?;
+    Ok(client.call(&tx, block_tag(block)?).await?)
+}
+
+#[tauri::command]
+pub(crate) async fn estimate_gas(
+    state: State<'_, HeliosState>,
+    tx: TransactionRequest,
+    block: Option<BlockParam>,
+) -> Result<U64> {
+    let client = state.client().await?;
+    Ok(U64::from(simulation::estimate_gas(&client, tx, block_tag(block)?).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_the_base_fee_toward_the_gas_target() {
+        let limit = 30_000_000;
+        let base_fee = 1_000_000_000;
+        // At the target it stays, full and empty blocks move it by 1/8
+        assert_eq!(next_base_fee(limit / 2, limit, base_fee), base_fee);
+        assert_eq!(next_base_fee(limit, limit, base_fee), 1_125_000_000);
+        assert_eq!(next_base_fee(0, limit, base_fee), 875_000_000);
+        assert_eq!(next_base_fee(limit * 3 / 4, limit, base_fee), 1_062_500_000);
+        // Any block over the target raises it by at least 1 wei
+        assert_eq!(next_base_fee(limit / 2 + 1, limit, 7), 8);
+        assert_eq!(next_base_fee(0, limit, 7), 7);
+        assert_eq!(next_base_fee(100, 0, base_fee), base_fee);
+    }
+
+    #[test]
+    fn rejects_a_fee_history_past_the_last_block() {
+        assert_eq!(block_after(16).unwrap(), 17);
+        assert!(matches!(block_after(u64::MAX), Err(KromeError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn rejects_block_tags_the_client_cant_answer() {
+        assert!(matches!(parse_block_tag("latest"), Ok(BlockTag::Latest)));
+        assert!(matches!(parse_block_tag("finalized"), Ok(BlockTag::Finalized)));
+        assert!(matches!(parse_block_tag("0x10"), Ok(BlockTag::Number(16))));
+        assert!(matches!(parse_block_tag("16"), Ok(BlockTag::Number(16))));
+        for tag in ["pending", "safe", "earliest", "newest"] {
+            assert!(matches!(parse_block_tag(tag), Err(KromeError::InvalidParams(_))), "{}", tag);
+        }
+    }
+}
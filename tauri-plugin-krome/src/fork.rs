@@ -0,0 +1,1270 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime, State};
+use tokio::sync::Mutex;
+
+use alloy::consensus::TxEnvelope;
+use alloy::eips::eip2718::Decodable2718;
+use alloy::eips::BlockNumberOrTag;
+use alloy::primitives::{keccak256, logs_bloom, Address, Bytes, Log, TxKind, B256, U256, U64};
+use alloy::rpc::types::{Filter, FilterBlockOption, TransactionRequest};
+use alloy::sol_types::decode_revert_reason;
+use helios::core::types::BlockTag;
+use revm::db::CacheDB;
+use revm::primitives::{AccountInfo, Bytecode, EVMError, Env, ExecutionResult, TxEnv};
+use revm::{Database, DatabaseRef, Evm};
+
+use crate::approvals::{Connections, Origin};
+use crate::error::{KromeError, Result};
+use crate::helios::{HeliosClient, HeliosState};
+use crate::rpc::{Params, RpcError, RpcResult, UNSUPPORTED_METHOD};
+use crate::simulation::{self, spec_at, HeliosDb};
+use crate::subscriptions::Subscriptions;
+use crate::wallet::WalletState;
+use crate::KromeExt;
+
+// A local chain forked from the light client at a pinned block. Accounts and
+// storage are pulled lazily through Helios, so they're proof-verified, and
+// everything the fork changes stays in memory. While it runs, the EIP-1193
+// bridge sends chain methods here, plus anvil's cheat methods for tests.
+//
+// revm reads block on the client, so fork work runs in block_in_place on the
+// runtime's worker thread, keeping the chain borrowed across it.
+
+// Methods the fork leaves to the real handlers. Signing doesn't touch chain
+// state, subscriptions are fed the fork's blocks while it runs, and fee
+// history is the network's. Anything else the fork doesn't answer fails.
+const PASSTHROUGH: [&str; 7] = [
+    "personal_sign",
+    "eth_signTypedData_v4",
+    "personal_ecRecover",
+    "web3_clientVersion",
+    "eth_subscribe",
+    "eth_unsubscribe",
+    "eth_feeHistory",
+];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkStatus {
+    pub chain_id: u64,
+    // Block the fork was taken at. State the fork hasn't read yet is loaded
+    // at this block, which the light client can only prove while it's recent:
+    // once it isn't, reading a new account or slot fails and the fork has to
+    // be restarted.
+    pub fork_block: u64,
+    // Latest local block
+    pub block_number: u64,
+    pub automine: bool,
+    // Transactions waiting for the next mined block when automine is off
+    pub pending: usize,
+    pub impersonated: Vec<Address>,
+}
+
+// Local blocks aren't real headers; their hashes are keccak256 of what they
+// hold, which is enough to tell them apart
+#[derive(Clone)]
+struct LocalBlock {
+    number: u64,
+    hash: B256,
+    parent_hash: B256,
+    timestamp: u64,
+    gas_used: u64,
+    transactions: Vec<B256>,
+}
+
+#[derive(Clone)]
+struct LocalTx {
+    hash: B256,
+    from: Address,
+    // With nonce, gas and fees filled
+    tx: TransactionRequest,
+    block_number: u64,
+    block_hash: B256,
+    index: usize,
+    // Index in its block of the transaction's first log
+    first_log_index: usize,
+    success: bool,
+    gas_used: u64,
+    cumulative_gas_used: u64,
+    gas_price: u128,
+    contract_address: Option<Address>,
+    logs: Vec<Log>,
+}
+
+#[derive(Clone)]
+struct PendingTx {
+    hash: B256,
+    from: Address,
+    tx: TransactionRequest,
+}
+
+// Where state the fork hasn't loaded yet is read from: the light client at
+// the fork block, or an empty state in tests
+#[derive(Clone)]
+struct ForkDb {
+    source: Arc<dyn DatabaseRef<Error = KromeError> + Send + Sync>,
+    fork_block: u64,
+}
+
+impl ForkDb {
+    fn unavailable(&self, e: KromeError) -> KromeError {
+        KromeError::Fork(format!(
+            "couldn't load state at fork block {}: {}. The light client can only prove state at recent blocks, \
+             so once the fork block is too old, accounts and storage the fork hasn't read yet can't be loaded; \
+             restart the fork to read them",
+            self.fork_block, e
+        ))
+    }
+}
+
+impl DatabaseRef for ForkDb {
+    type Error = KromeError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>> {
+        self.source.basic_ref(address).map_err(|e| self.unavailable(e))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode> {
+        self.source.code_by_hash_ref(code_hash).map_err(|e| self.unavailable(e))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256> {
+        self.source.storage_ref(address, index).map_err(|e| self.unavailable(e))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256> {
+        self.source.block_hash_ref(number).map_err(|e| self.unavailable(e))
+    }
+}
+
+// Everything a snapshot saves and a revert restores
+#[derive(Clone)]
+struct Chain {
+    db: CacheDB<ForkDb>,
+    // Starts with the fork block itself
+    blocks: Vec<LocalBlock>,
+    txs: HashMap<B256, LocalTx>,
+    pending: Vec<PendingTx>,
+    // Seconds evm_increaseTime has moved the clock
+    time_offset: u64,
+    next_timestamp: Option<u64>,
+}
+
+// Where a read at a block tag is answered from
+enum At {
+    Local,
+    Remote(BlockTag),
+}
+
+pub struct Fork {
+    chain_id: u64,
+    fork_block: u64,
+    // Local blocks keep the fork block's limits, fee and beneficiary
+    gas_limit: u64,
+    base_fee: u64,
+    coinbase: Address,
+    prevrandao: B256,
+    chain: Chain,
+    snapshots: BTreeMap<u64, Chain>,
+    next_snapshot: u64,
+    impersonated: BTreeSet<Address>,
+    auto_impersonate: bool,
+    automine: bool,
+}
+
+impl Fork {
+    // Forks at `block_number`, or at the latest block. The client has to be
+    // able to prove state there, so it should be recent.
+    pub async fn new(client: HeliosClient, block_number: Option<u64>) -> Result<Self> {
+        let tag = block_number.map_or(BlockTag::Latest, BlockTag::Number);
+        let block = client
+            .get_block_by_number(tag, false)
+            .await?
+            .ok_or_else(|| KromeError::Fork("fork block not found".to_string()))?;
+        let header = block.header;
+
+        Ok(Fork {
+            chain_id: client.chain_id().await,
+            fork_block: header.number,
+            gas_limit: header.gas_limit,
+            base_fee: header.base_fee_per_gas.unwrap_or_default(),
+            coinbase: header.beneficiary,
+            prevrandao: header.mix_hash,
+            chain: Chain {
+                db: CacheDB::new(ForkDb {
+                    source: Arc::new(HeliosDb::new(&client, header.number)),
+                    fork_block: header.number,
+                }),
+                blocks: vec![LocalBlock {
+                    number: header.number,
+                    hash: header.hash,
+                    parent_hash: header.parent_hash,
+                    timestamp: header.timestamp,
+                    gas_used: header.gas_used,
+                    transactions: Vec::new(),
+                }],
+                txs: HashMap::new(),
+                pending: Vec::new(),
+                time_offset: 0,
+                next_timestamp: None,
+            },
+            snapshots: BTreeMap::new(),
+            next_snapshot: 1,
+            impersonated: BTreeSet::new(),
+            auto_impersonate: false,
+            automine: true,
+        })
+    }
+
+    pub fn status(&self) -> ForkStatus {
+        ForkStatus {
+            chain_id: self.chain_id,
+            fork_block: self.fork_block,
+            block_number: self.head().number,
+            automine: self.automine,
+            pending: self.chain.pending.len(),
+            impersonated: self.impersonated.iter().copied().collect(),
+        }
+    }
+
+    fn head(&self) -> &LocalBlock {
+        self.chain.blocks.last().expect("fork block is always there")
+    }
+
+    pub(crate) fn block_number(&self) -> u64 {
+        self.head().number
+    }
+
+    fn local_block(&self, number: u64) -> Option<&LocalBlock> {
+        let index = number.checked_sub(self.fork_block)?;
+        self.chain.blocks.get(usize::try_from(index).ok()?)
+    }
+
+    // State is only kept for the head, so earlier local blocks can't be read
+    fn at(&self, tag: BlockTag) -> Result<At> {
+        match tag {
+            BlockTag::Latest => Ok(At::Local),
+            BlockTag::Number(number) if number == self.head().number => Ok(At::Local),
+            BlockTag::Number(number) if number <= self.fork_block => Ok(At::Remote(tag)),
+            BlockTag::Finalized => Ok(At::Remote(tag)),
+            BlockTag::Number(number) => Err(KromeError::Fork(format!(
+                "state at local block {} isn't kept, only at the latest block",
+                number
+            ))),
+        }
+    }
+
+    fn next_timestamp(&mut self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let earliest = self.head().timestamp + 1;
+        self.chain
+            .next_timestamp
+            .take()
+            .unwrap_or(now + self.chain.time_offset)
+            .max(earliest)
+    }
+
+    fn env(&self, number: u64, timestamp: u64, tx: TxEnv) -> Box<Env> {
+        let mut env = Env::default();
+        env.cfg.chain_id = self.chain_id;
+        env.block.number = U256::from(number);
+        env.block.coinbase = self.coinbase;
+        env.block.timestamp = U256::from(timestamp);
+        env.block.gas_limit = U256::from(self.gas_limit);
+        env.block.basefee = U256::from(self.base_fee);
+        env.block.prevrandao = Some(self.prevrandao);
+        env.tx = tx;
+        Box::new(env)
+    }
+
+    // For calls and estimates: no balance or nonce checks, and no base fee
+    // unless the call sets a gas price, the way geth runs eth_call
+    fn call_env(&mut self, from: Address, tx: &TransactionRequest) -> Result<Box<Env>> {
+        let mut tx_env = self.tx_env(from, tx)?;
+        tx_env.nonce = None;
+        let number = self.head().number + 1;
+        let timestamp = self.chain.next_timestamp.unwrap_or(self.head().timestamp + 1);
+        let mut env = self.env(number, timestamp, tx_env);
+        env.cfg.disable_balance_check = true;
+        if env.tx.gas_price.is_zero() {
+            env.block.basefee = U256::ZERO;
+        }
+        Ok(env)
+    }
+
+    fn tx_env(&mut self, from: Address, tx: &TransactionRequest) -> Result<TxEnv> {
+        let nonce = match tx.nonce {
+            Some(nonce) => nonce,
+            None => self.chain.db.basic(from)?.map(|info| info.nonce).unwrap_or_default(),
+        };
+        Ok(TxEnv {
+            caller: from,
+            gas_limit: tx.gas.unwrap_or(self.gas_limit),
+            gas_price: U256::from(tx.max_fee_per_gas.or(tx.gas_price).unwrap_or_default()),
+            gas_priority_fee: tx.max_priority_fee_per_gas.map(U256::from),
+            transact_to: tx.to.unwrap_or(TxKind::Create),
+            value: tx.value.unwrap_or_default(),
+            data: tx.input.input().cloned().unwrap_or_default(),
+            nonce: Some(nonce),
+            chain_id: Some(self.chain_id),
+            access_list: tx.access_list.clone().map(|list| list.0).unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    // Runs with the rules of the network at the block's time
+    fn transact(&mut self, env: Box<Env>, commit: bool) -> Result<ExecutionResult> {
+        let spec = spec_at(self.chain_id, env.block.timestamp.to());
+        let mut evm = Evm::builder()
+            .with_db(&mut self.chain.db)
+            .with_env(env)
+            .with_spec_id(spec)
+            .build();
+        let result = if commit {
+            evm.transact_commit()
+        } else {
+            evm.transact().map(|result| result.result)
+        };
+        result.map_err(|e| match e {
+            EVMError::Database(e) => e,
+            e => KromeError::Fork(e.to_string()),
+        })
+    }
+
+    fn call(&mut self, from: Address, tx: &TransactionRequest) -> Result<Bytes> {
+        let env = self.call_env(from, tx)?;
+        match self.transact(env, false)? {
+            ExecutionResult::Success { output, .. } => Ok(output.into_data()),
+            ExecutionResult::Revert { output, .. } => Err(reverted(output)),
+            ExecutionResult::Halt { reason, .. } => Err(KromeError::Fork(format!("{:?}", reason))),
+        }
+    }
+
+    // Refunds are paid after execution and calls hold back 1/64 of their gas,
+    // so the limit needs headroom over what was used
+    fn estimate_gas(&mut self, from: Address, tx: &TransactionRequest) -> Result<u64> {
+        let mut tx = tx.clone();
+        tx.gas = Some(self.gas_limit);
+        let env = self.call_env(from, &tx)?;
+        match self.transact(env, false)? {
+            ExecutionResult::Success {
+                gas_used, gas_refunded, ..
+            } => Ok(((gas_used + gas_refunded) * 64 / 63).min(self.gas_limit)),
+            ExecutionResult::Revert { output, .. } => Err(reverted(output)),
+            ExecutionResult::Halt { reason, .. } => Err(KromeError::Fork(format!("{:?}", reason))),
+        }
+    }
+
+    // Fills what eth_sendTransaction leaves out. Gas is priced at the fork
+    // block's base fee.
+    fn fill(&mut self, from: Address, mut tx: TransactionRequest) -> Result<TransactionRequest> {
+        tx.from = Some(from);
+        if tx.nonce.is_none() {
+            let pending = self.chain.pending.iter().filter(|pending| pending.from == from).count() as u64;
+            let nonce = self.chain.db.basic(from)?.map(|info| info.nonce).unwrap_or_default();
+            tx.nonce = Some(nonce + pending);
+        }
+        if tx.gas.is_none() {
+            tx.gas = Some(self.estimate_gas(from, &tx)?);
+        }
+        if tx.gas_price.is_none() && tx.max_fee_per_gas.is_none() {
+            tx.gas_price = Some(self.base_fee.into());
+        }
+        tx.chain_id = Some(self.chain_id);
+        Ok(tx)
+    }
+
+    // Sends from an impersonated account; nothing is signed
+    fn send_transaction(&mut self, tx: TransactionRequest) -> Result<B256> {
+        let from = tx
+            .from
+            .ok_or_else(|| KromeError::InvalidParams("transaction has no from address".to_string()))?;
+        if !self.auto_impersonate && !self.impersonated.contains(&from) {
+            return Err(KromeError::Unauthorized(format!(
+                "{} isn't impersonated on the fork; call anvil_impersonateAccount first",
+                from
+            )));
+        }
+        let tx = self.fill(from, tx)?;
+        // Fork-only hash, since there's no signature to hash
+        let hash = keccak256(serde_json::to_vec(&tx)?);
+        self.submit(PendingTx { hash, from, tx })
+    }
+
+    fn send_raw_transaction(&mut self, raw: &Bytes) -> Result<B256> {
+        let envelope = TxEnvelope::decode_2718(&mut raw.as_ref())
+            .map_err(|e| KromeError::InvalidParams(format!("invalid transaction: {}", e)))?;
+        let from = envelope
+            .recover_signer()
+            .map_err(|e| KromeError::InvalidParams(format!("invalid signature: {}", e)))?;
+        let hash = *envelope.tx_hash();
+        let mut tx: TransactionRequest = envelope.into();
+        tx.from = Some(from);
+        self.submit(PendingTx { hash, from, tx })
+    }
+
+    // Mines the transaction straight away with automine on, failing if it
+    // can't be included; otherwise it waits for the next mined block
+    fn submit(&mut self, pending: PendingTx) -> Result<B256> {
+        let hash = pending.hash;
+        self.chain.pending.push(pending);
+        if self.automine {
+            let timestamp = self.next_timestamp();
+            let mut failed = self.mine_block(timestamp, false);
+            if let Some((_, e)) = failed.pop() {
+                return Err(e);
+            }
+        }
+        Ok(hash)
+    }
+
+    // Mines the pending transactions into a new block. Ones that can't be
+    // included, e.g. for a bad nonce or state that can't be loaded anymore,
+    // are dropped and returned with why; the rest still make the block. With
+    // `keep_empty` unset, no block is added if nothing made it in.
+    fn mine_block(&mut self, timestamp: u64, keep_empty: bool) -> Vec<(B256, KromeError)> {
+        let parent_hash = self.head().hash;
+        let number = self.head().number + 1;
+        let mut included: Vec<LocalTx> = Vec::new();
+        let mut failed = Vec::new();
+        let mut cumulative_gas_used = 0;
+        let mut log_count = 0;
+
+        for pending in std::mem::take(&mut self.chain.pending) {
+            let tx_env = match self.tx_env(pending.from, &pending.tx) {
+                Ok(tx_env) => tx_env,
+                Err(e) => {
+                    failed.push((pending.hash, e));
+                    continue;
+                }
+            };
+            let env = self.env(number, timestamp, tx_env);
+            let gas_price = env.tx.gas_priority_fee.map_or(env.tx.gas_price, |priority| {
+                env.tx.gas_price.min(priority + env.block.basefee)
+            });
+            let nonce = env.tx.nonce.unwrap_or_default();
+            let result = match self.transact(env, true) {
+                Ok(result) => result,
+                Err(e) => {
+                    failed.push((pending.hash, e));
+                    continue;
+                }
+            };
+
+            let success = result.is_success();
+            let gas_used = result.gas_used();
+            cumulative_gas_used += gas_used;
+            let logs = result.into_logs();
+            let first_log_index = log_count;
+            log_count += logs.len();
+            let contract_address = match pending.tx.to {
+                None | Some(TxKind::Create) if success => Some(pending.from.create(nonce)),
+                _ => None,
+            };
+            included.push(LocalTx {
+                hash: pending.hash,
+                from: pending.from,
+                tx: pending.tx,
+                block_number: number,
+                block_hash: B256::ZERO,
+                index: included.len(),
+                first_log_index,
+                success,
+                gas_used,
+                cumulative_gas_used,
+                gas_price: gas_price.to(),
+                contract_address,
+                logs,
+            });
+        }
+
+        if included.is_empty() && !keep_empty {
+            return failed;
+        }
+
+        let mut preimage = [parent_hash.as_slice(), &number.to_be_bytes(), &timestamp.to_be_bytes()].concat();
+        for tx in &included {
+            preimage.extend_from_slice(tx.hash.as_slice());
+        }
+        let hash = keccak256(preimage);
+        self.chain.db.block_hashes.insert(U256::from(number), hash);
+        self.chain.blocks.push(LocalBlock {
+            number,
+            hash,
+            parent_hash,
+            timestamp,
+            gas_used: cumulative_gas_used,
+            transactions: included.iter().map(|tx| tx.hash).collect(),
+        });
+        for mut tx in included {
+            tx.block_hash = hash;
+            self.chain.txs.insert(tx.hash, tx);
+        }
+        failed
+    }
+
+    // Mines `count` blocks `interval` seconds apart, the first with whatever
+    // is pending
+    fn mine(&mut self, count: u64, interval: u64, timestamp: Option<u64>) -> Result<()> {
+        if let Some(timestamp) = timestamp {
+            self.chain.next_timestamp = Some(timestamp);
+        }
+        let mut timestamp = self.next_timestamp();
+        for _ in 0..count.max(1) {
+            self.mine_block(timestamp, true);
+            timestamp += interval.max(1);
+        }
+        Ok(())
+    }
+
+    fn update_account(&mut self, address: Address, update: impl FnOnce(&mut AccountInfo)) -> Result<()> {
+        let mut info = self.chain.db.basic(address)?.unwrap_or_default();
+        update(&mut info);
+        self.chain.db.insert_account_info(address, info);
+        Ok(())
+    }
+
+    fn snapshot(&mut self) -> u64 {
+        let id = self.next_snapshot;
+        self.next_snapshot += 1;
+        self.snapshots.insert(id, self.chain.clone());
+        id
+    }
+
+    // Restores a snapshot, dropping it and every later one as anvil does
+    fn revert(&mut self, id: u64) -> bool {
+        let Some(chain) = self.snapshots.remove(&id) else {
+            return false;
+        };
+        self.snapshots.retain(|snapshot, _| *snapshot < id);
+        self.chain = chain;
+        true
+    }
+
+    fn block_txs<'a>(&'a self, block: &'a LocalBlock) -> impl Iterator<Item = &'a LocalTx> {
+        block.transactions.iter().filter_map(|hash| self.chain.txs.get(hash))
+    }
+
+    fn header_json(&self, block: &LocalBlock) -> Value {
+        let bloom = logs_bloom(self.block_txs(block).flat_map(|tx| &tx.logs));
+        json!({
+            "number": U64::from(block.number),
+            "hash": block.hash,
+            "parentHash": block.parent_hash,
+            "timestamp": U64::from(block.timestamp),
+            "gasLimit": U64::from(self.gas_limit),
+            "gasUsed": U64::from(block.gas_used),
+            "baseFeePerGas": U64::from(self.base_fee),
+            "miner": self.coinbase,
+            "mixHash": self.prevrandao,
+            "difficulty": U256::ZERO,
+            "nonce": "0x0000000000000000",
+            "extraData": Bytes::new(),
+            "logsBloom": bloom,
+        })
+    }
+
+    fn block_json(&self, block: &LocalBlock, full: bool) -> Value {
+        let transactions: Vec<Value> = block
+            .transactions
+            .iter()
+            .map(|hash| match (full, self.chain.txs.get(hash)) {
+                (true, Some(tx)) => tx_json(tx),
+                _ => json!(hash),
+            })
+            .collect();
+        let mut value = self.header_json(block);
+        if let Value::Object(fields) = &mut value {
+            fields.insert("transactions".to_string(), json!(transactions));
+            fields.insert("uncles".to_string(), json!([]));
+        }
+        value
+    }
+
+    // Header of a block mined on the fork, as newHeads sends it
+    pub(crate) fn header(&self, number: u64) -> Option<Value> {
+        let block = self.local_block(number).filter(|block| block.number > self.fork_block)?;
+        Some(self.header_json(block))
+    }
+
+    // Logs from blocks mined on the fork between `from` and `to`, both
+    // included. The filter's own block range is ignored.
+    pub(crate) fn local_logs(&self, filter: &Filter, from: u64, to: u64) -> Vec<Value> {
+        let blocks = self.chain.blocks.iter().skip(1);
+        blocks
+            .filter(|block| (from..=to).contains(&block.number))
+            .flat_map(|block| self.block_txs(block))
+            .flat_map(|tx| tx.logs.iter().enumerate().map(move |(index, log)| (tx, index, log)))
+            .filter(|(_, _, log)| log_matches(filter, log))
+            .map(|(tx, index, log)| log_json(tx, index, log))
+            .collect()
+    }
+
+    // Logs up to the fork block come from the network, later ones from the
+    // fork. The fork's blocks are never safe or finalized, so those tags mean
+    // the fork block.
+    async fn get_logs(&self, client: &HeliosClient, filter: Filter) -> Result<Vec<Value>> {
+        let (from, to) = match filter.block_option {
+            FilterBlockOption::AtBlockHash(hash) => match self.chain.blocks.iter().skip(1).find(|b| b.hash == hash) {
+                Some(block) => (block.number, block.number),
+                None => {
+                    let logs = client.get_logs(&filter).await?;
+                    return logs.into_iter().map(|log| Ok(serde_json::to_value(log)?)).collect();
+                }
+            },
+            FilterBlockOption::Range { from_block, to_block } => {
+                (self.filter_block(from_block), self.filter_block(to_block))
+            }
+        };
+
+        let mut logs = Vec::new();
+        if from <= self.fork_block {
+            let remote = filter.clone().from_block(from).to_block(to.min(self.fork_block));
+            for log in client.get_logs(&remote).await? {
+                logs.push(serde_json::to_value(log)?);
+            }
+        }
+        logs.extend(self.local_logs(&filter, from.max(self.fork_block + 1), to));
+        Ok(logs)
+    }
+
+    fn filter_block(&self, block: Option<BlockNumberOrTag>) -> u64 {
+        match block {
+            Some(BlockNumberOrTag::Number(number)) => number,
+            Some(BlockNumberOrTag::Earliest) => 0,
+            Some(BlockNumberOrTag::Safe | BlockNumberOrTag::Finalized) => self.fork_block,
+            Some(BlockNumberOrTag::Latest | BlockNumberOrTag::Pending) | None => self.head().number,
+        }
+    }
+
+    async fn request(&mut self, client: &HeliosClient, method: &str, params: &Params) -> RpcResult<Value> {
+        let result = match method {
+            "eth_chainId" => json!(U64::from(self.chain_id)),
+            "net_version" => json!(self.chain_id.to_string()),
+            "eth_blockNumber" => json!(U64::from(self.head().number)),
+            "eth_syncing" => json!(false),
+            "eth_gasPrice" => json!(U256::from(self.base_fee)),
+            "eth_maxPriorityFeePerGas" => json!(U256::ZERO),
+            "eth_getBalance" => {
+                let address: Address = params.get(0)?;
+                match self.at(params.block(1)?)? {
+                    At::Local => json!(blocking(|| self.chain.db.basic(address))?.unwrap_or_default().balance),
+                    At::Remote(tag) => json!(client.get_balance(address, tag).await?),
+                }
+            }
+            "eth_getTransactionCount" => {
+                let address: Address = params.get(0)?;
+                match self.at(params.block(1)?)? {
+                    At::Local => {
+                        let info = blocking(|| self.chain.db.basic(address))?.unwrap_or_default();
+                        json!(U64::from(info.nonce))
+                    }
+                    At::Remote(tag) => json!(U64::from(client.get_nonce(address, tag).await?)),
+                }
+            }
+            "eth_getCode" => {
+                let address: Address = params.get(0)?;
+                match self.at(params.block(1)?)? {
+                    At::Local => {
+                        let info = blocking(|| self.chain.db.basic(address))?.unwrap_or_default();
+                        json!(info.code.map(|code| code.original_bytes()).unwrap_or_default())
+                    }
+                    At::Remote(tag) => json!(client.get_code(address, tag).await?),
+                }
+            }
+            "eth_getStorageAt" => {
+                let address: Address = params.get(0)?;
+                let slot: U256 = params.get(1)?;
+                match self.at(params.block(2)?)? {
+                    At::Local => json!(B256::from(blocking(|| self.chain.db.storage(address, slot))?)),
+                    At::Remote(tag) => json!(client.get_storage_at(address, B256::from(slot), tag).await?),
+                }
+            }
+            "eth_getBlockByNumber" => {
+                let full_txs: Option<bool> = params.optional(1)?;
+                let block = match params.block(0)? {
+                    BlockTag::Latest => Some(self.head()),
+                    BlockTag::Number(number) if number > self.fork_block => self.local_block(number),
+                    tag => return Ok(json!(client.get_block_by_number(tag, full_txs.unwrap_or(false)).await?)),
+                };
+                json!(block.map(|block| self.block_json(block, full_txs.unwrap_or(false))))
+            }
+            "eth_getBlockByHash" => {
+                let hash: B256 = params.get(0)?;
+                let full_txs: Option<bool> = params.optional(1)?;
+                match self.chain.blocks.iter().skip(1).find(|block| block.hash == hash) {
+                    Some(block) => self.block_json(block, full_txs.unwrap_or(false)),
+                    None => json!(client.get_block_by_hash(hash, full_txs.unwrap_or(false)).await?),
+                }
+            }
+            "eth_getBlockTransactionCountByNumber" => {
+                let block = match params.block(0)? {
+                    BlockTag::Latest => Some(self.head()),
+                    BlockTag::Number(number) if number > self.fork_block => self.local_block(number),
+                    tag => {
+                        let block = client.get_block_by_number(tag, false).await?;
+                        return Ok(json!(block.map(|block| U64::from(block.transactions.len()))));
+                    }
+                };
+                json!(block.map(|block| U64::from(block.transactions.len())))
+            }
+            "eth_getBlockTransactionCountByHash" => {
+                let hash: B256 = params.get(0)?;
+                match self.chain.blocks.iter().skip(1).find(|block| block.hash == hash) {
+                    Some(block) => json!(U64::from(block.transactions.len())),
+                    None => {
+                        let block = client.get_block_by_hash(hash, false).await?;
+                        json!(block.map(|block| U64::from(block.transactions.len())))
+                    }
+                }
+            }
+            "eth_getBlockReceipts" => {
+                let block = match params.block(0)? {
+                    BlockTag::Latest => Some(self.head()),
+                    BlockTag::Number(number) if number > self.fork_block => self.local_block(number),
+                    tag => return Ok(json!(client.get_block_receipts(tag).await?)),
+                };
+                json!(block.map(|block| self.block_txs(block).map(receipt_json).collect::<Vec<_>>()))
+            }
+            "eth_getTransactionByHash" => {
+                let hash: B256 = params.get(0)?;
+                match self.chain.txs.get(&hash) {
+                    Some(tx) => tx_json(tx),
+                    None => json!(client.get_transaction_by_hash(hash).await?),
+                }
+            }
+            "eth_getTransactionReceipt" => {
+                let hash: B256 = params.get(0)?;
+                match self.chain.txs.get(&hash) {
+                    Some(tx) => receipt_json(tx),
+                    None => json!(client.get_transaction_receipt(hash).await?),
+                }
+            }
+            "eth_getLogs" => {
+                let filter: Filter = params.get(0)?;
+                json!(self.get_logs(client, filter).await?)
+            }
+            "eth_call" => {
+                let tx: TransactionRequest = params.get(0)?;
+                match self.at(params.block(1)?)? {
+                    At::Local => json!(blocking(|| self.call(tx.from.unwrap_or_default(), &tx))?),
+                    At::Remote(tag) => json!(client.call(&tx, tag).await?),
+                }
+            }
+            "eth_estimateGas" => {
+                let tx: TransactionRequest = params.get(0)?;
+                let gas = match self.at(params.block(1)?)? {
+                    At::Local => blocking(|| self.estimate_gas(tx.from.unwrap_or_default(), &tx))?,
+                    At::Remote(tag) => simulation::estimate_gas(client, tx, tag).await?,
+                };
+                json!(U64::from(gas))
+            }
+            "eth_sendTransaction" => {
+                let tx: TransactionRequest = params.get(0)?;
+                json!(blocking(|| self.send_transaction(tx))?)
+            }
+            "eth_sendRawTransaction" => {
+                let raw: Bytes = params.get(0)?;
+                json!(blocking(|| self.send_raw_transaction(&raw))?)
+            }
+
+            // Cheat methods
+            "anvil_setBalance" | "hardhat_setBalance" => {
+                let address: Address = params.get(0)?;
+                let balance: U256 = params.get(1)?;
+                blocking(|| self.update_account(address, |info| info.balance = balance))?;
+                json!(null)
+            }
+            "anvil_setNonce" | "hardhat_setNonce" => {
+                let address: Address = params.get(0)?;
+                let nonce = params.quantity(1)?;
+                blocking(|| self.update_account(address, |info| info.nonce = nonce))?;
+                json!(null)
+            }
+            "anvil_setCode" | "hardhat_setCode" => {
+                let address: Address = params.get(0)?;
+                let code: Bytes = params.get(1)?;
+                let code = Bytecode::new_raw(code);
+                blocking(|| {
+                    self.update_account(address, |info| {
+                        info.code_hash = code.hash_slow();
+                        info.code = Some(code);
+                    })
+                })?;
+                json!(null)
+            }
+            "anvil_setStorageAt" | "hardhat_setStorageAt" => {
+                let address: Address = params.get(0)?;
+                let slot: U256 = params.get(1)?;
+                let value: B256 = params.get(2)?;
+                blocking(|| self.chain.db.insert_account_storage(address, slot, U256::from_be_bytes(value.0)))?;
+                json!(true)
+            }
+            "anvil_impersonateAccount" | "hardhat_impersonateAccount" => {
+                self.impersonated.insert(params.get(0)?);
+                json!(null)
+            }
+            "anvil_stopImpersonatingAccount" | "hardhat_stopImpersonatingAccount" => {
+                self.impersonated.remove(&params.get::<Address>(0)?);
+                json!(null)
+            }
+            "anvil_autoImpersonateAccount" => {
+                self.auto_impersonate = params.get(0)?;
+                json!(null)
+            }
+            "anvil_getAutomine" | "hardhat_getAutomine" => json!(self.automine),
+            "anvil_setAutomine" | "evm_setAutomine" => {
+                self.automine = params.get(0)?;
+                json!(null)
+            }
+            "anvil_mine" | "hardhat_mine" => {
+                let count = params.optional_quantity(0)?.unwrap_or(1);
+                let interval = params.optional_quantity(1)?.unwrap_or(1);
+                blocking(|| self.mine(count, interval, None))?;
+                json!(null)
+            }
+            "evm_mine" => {
+                let timestamp = params.optional_quantity(0)?;
+                blocking(|| self.mine(1, 1, timestamp))?;
+                json!("0x0")
+            }
+            // Returns the total offset, as anvil does
+            "evm_increaseTime" => {
+                self.chain.time_offset += params.quantity(0)?;
+                json!(U64::from(self.chain.time_offset))
+            }
+            "evm_setNextBlockTimestamp" => {
+                let timestamp = params.quantity(0)?;
+                if timestamp <= self.head().timestamp {
+                    return Err(RpcError::invalid_params("timestamp must be after the latest block's"));
+                }
+                self.chain.next_timestamp = Some(timestamp);
+                json!(null)
+            }
+            "evm_snapshot" => json!(U64::from(self.snapshot())),
+            "evm_revert" => json!(self.revert(params.quantity(0)?)),
+            method => {
+                return Err(RpcError::new(
+                    UNSUPPORTED_METHOD,
+                    format!("the method {} is not supported while a fork is running", method),
+                ))
+            }
+        };
+        Ok(result)
+    }
+}
+
+// Runs fork work that may read through the client. Tauri's runtime is
+// multi-threaded, which block_in_place needs.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    tokio::task::block_in_place(f)
+}
+
+fn reverted(output: Bytes) -> KromeError {
+    KromeError::ExecutionReverted {
+        reason: decode_revert_reason(&output),
+        data: output,
+    }
+}
+
+fn tx_json(tx: &LocalTx) -> Value {
+    let mut value = json!(tx.tx);
+    if let Value::Object(fields) = &mut value {
+        fields.insert("hash".to_string(), json!(tx.hash));
+        fields.insert("from".to_string(), json!(tx.from));
+        fields.insert("blockHash".to_string(), json!(tx.block_hash));
+        fields.insert("blockNumber".to_string(), json!(U64::from(tx.block_number)));
+        fields.insert("transactionIndex".to_string(), json!(U64::from(tx.index as u64)));
+    }
+    value
+}
+
+// `index` is the log's index within the transaction
+fn log_json(tx: &LocalTx, index: usize, log: &Log) -> Value {
+    json!({
+        "address": log.address,
+        "topics": log.topics(),
+        "data": log.data.data,
+        "blockHash": tx.block_hash,
+        "blockNumber": U64::from(tx.block_number),
+        "transactionHash": tx.hash,
+        "transactionIndex": U64::from(tx.index as u64),
+        "logIndex": U64::from((tx.first_log_index + index) as u64),
+        "removed": false,
+    })
+}
+
+fn log_matches(filter: &Filter, log: &Log) -> bool {
+    let topics = log.topics();
+    filter.address.matches(&log.address)
+        && filter
+            .topics
+            .iter()
+            .enumerate()
+            .all(|(i, topic)| topic.is_empty() || topics.get(i).is_some_and(|t| topic.matches(t)))
+}
+
+fn receipt_json(tx: &LocalTx) -> Value {
+    let logs: Vec<Value> = tx.logs.iter().enumerate().map(|(index, log)| log_json(tx, index, log)).collect();
+    json!({
+        "transactionHash": tx.hash,
+        "transactionIndex": U64::from(tx.index as u64),
+        "blockHash": tx.block_hash,
+        "blockNumber": U64::from(tx.block_number),
+        "from": tx.from,
+        "to": tx.tx.to.and_then(|to| to.to().copied()),
+        "cumulativeGasUsed": U64::from(tx.cumulative_gas_used),
+        "gasUsed": U64::from(tx.gas_used),
+        "effectiveGasPrice": U256::from(tx.gas_price),
+        "contractAddress": tx.contract_address,
+        "logs": logs,
+        "logsBloom": logs_bloom(&tx.logs),
+        "status": U64::from(tx.success as u64),
+        "type": U64::from(tx.tx.transaction_type.unwrap_or_default()),
+    })
+}
+
+#[derive(Default)]
+pub struct ForkState(Mutex<Option<Fork>>);
+
+impl ForkState {
+    pub async fn status(&self) -> Option<ForkStatus> {
+        self.0.lock().await.as_ref().map(Fork::status)
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.0.lock().await.is_some()
+    }
+
+    pub async fn stop(&self) {
+        self.0.lock().await.take();
+    }
+}
+
+// Cheat methods and unsigned sends change the fork for every page
+fn is_privileged(method: &str) -> bool {
+    method == "eth_sendTransaction" || ["anvil_", "hardhat_", "evm_"].iter().any(|prefix| method.starts_with(prefix))
+}
+
+// Answers a bridge request from the fork while one is running. Returns None
+// to let the real network handle it. Blocks the request mined are sent to
+// subscribers. Only origins the user connected through eth_requestAccounts
+// can use privileged methods, and they see the impersonated accounts next to
+// the wallet's.
+pub async fn dispatch<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    origin: &Origin,
+    method: &str,
+    params: &Params,
+) -> RpcResult<Option<Value>> {
+    if PASSTHROUGH.contains(&method) {
+        return Ok(None);
+    }
+    let state = app_handle.state::<ForkState>();
+    let connections = app_handle.state::<Connections>();
+
+    if matches!(method, "eth_accounts" | "eth_requestAccounts") {
+        // Checked before the prompt, which mustn't hold the fork's lock
+        if !state.is_running().await {
+            return Ok(None);
+        }
+        let wallet = app_handle.state::<WalletState>();
+        let mut accounts = match method {
+            "eth_requestAccounts" => connections.request(app_handle, origin).await?,
+            _ => connections.accounts(origin, &wallet),
+        };
+        if connections.is_connected(origin) {
+            if let Some(fork) = state.0.lock().await.as_ref() {
+                let impersonated: Vec<Address> =
+                    fork.impersonated.iter().copied().filter(|address| !accounts.contains(address)).collect();
+                accounts.extend(impersonated);
+            }
+        }
+        return Ok(Some(json!(accounts)));
+    }
+
+    let mut guard = state.0.lock().await;
+    let Some(fork) = guard.as_mut() else {
+        return Ok(None);
+    };
+    if is_privileged(method) && !connections.is_connected(origin) {
+        return Err(KromeError::Unauthorized(format!("connect with eth_requestAccounts before calling {}", method)).into());
+    }
+    let client = app_handle.krome().client().await?;
+    let head = fork.block_number();
+    let result = fork.request(&client, method, params).await?;
+    if fork.block_number() > head {
+        app_handle.state::<Subscriptions>().notify_fork(app_handle, fork, head + 1);
+    }
+    Ok(Some(result))
+}
+
+// Replaces any running fork. The status says which block state is loaded
+// at, and so how long the fork can keep reading new accounts.
+#[tauri::command]
+pub(crate) async fn start_fork(
+    state: State<'_, HeliosState>,
+    forks: State<'_, ForkState>,
+    block_number: Option<u64>,
+) -> Result<ForkStatus> {
+    let fork = Fork::new(state.client().await?, block_number).await?;
+    let status = fork.status();
+    *forks.0.lock().await = Some(fork);
+    Ok(status)
+}
+
+#[tauri::command]
+pub(crate) async fn stop_fork(forks: State<'_, ForkState>) -> Result<()> {
+    forks.stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn get_fork_status(forks: State<'_, ForkState>) -> Result<Option<ForkStatus>> {
+    Ok(forks.status().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::db::EmptyDBTyped;
+
+    const FORK_BLOCK: u64 = 100;
+
+    // A fork over an empty state, so nothing goes to a client
+    fn fork() -> Fork {
+        fork_over(Arc::new(EmptyDBTyped::<KromeError>::new()))
+    }
+
+    fn fork_over(source: Arc<dyn DatabaseRef<Error = KromeError> + Send + Sync>) -> Fork {
+        Fork {
+            chain_id: 1,
+            fork_block: FORK_BLOCK,
+            gas_limit: 30_000_000,
+            base_fee: 0,
+            coinbase: Address::ZERO,
+            prevrandao: B256::ZERO,
+            chain: Chain {
+                db: CacheDB::new(ForkDb {
+                    source,
+                    fork_block: FORK_BLOCK,
+                }),
+                blocks: vec![LocalBlock {
+                    number: FORK_BLOCK,
+                    hash: B256::repeat_byte(1),
+                    parent_hash: B256::ZERO,
+                    timestamp: 1_700_000_000,
+                    gas_used: 0,
+                    transactions: Vec::new(),
+                }],
+                txs: HashMap::new(),
+                pending: Vec::new(),
+                time_offset: 0,
+                next_timestamp: None,
+            },
+            snapshots: BTreeMap::new(),
+            next_snapshot: 1,
+            impersonated: BTreeSet::new(),
+            auto_impersonate: false,
+            automine: true,
+        }
+    }
+
+    // Empty state in which one account can't be loaded, as happens once the
+    // fork block has left the light client's window
+    struct UnloadableAccount(Address);
+
+    impl DatabaseRef for UnloadableAccount {
+        type Error = KromeError;
+
+        fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>> {
+            if address == self.0 {
+                return Err(KromeError::Helios("block is too old to prove".to_string()));
+            }
+            Ok(None)
+        }
+
+        fn code_by_hash_ref(&self, _code_hash: B256) -> Result<Bytecode> {
+            Ok(Bytecode::default())
+        }
+
+        fn storage_ref(&self, _address: Address, _index: U256) -> Result<U256> {
+            Ok(U256::ZERO)
+        }
+
+        fn block_hash_ref(&self, _number: u64) -> Result<B256> {
+            Ok(B256::ZERO)
+        }
+    }
+
+    fn alice() -> Address {
+        Address::repeat_byte(0xa1)
+    }
+
+    fn bob() -> Address {
+        Address::repeat_byte(0xb0)
+    }
+
+    fn transfer(value: u64) -> TransactionRequest {
+        TransactionRequest::default().from(alice()).to(bob()).value(U256::from(value))
+    }
+
+    fn balance(fork: &mut Fork, address: Address) -> U256 {
+        fork.chain.db.basic(address).unwrap().unwrap_or_default().balance
+    }
+
+    fn funded() -> Fork {
+        let mut fork = fork();
+        fork.update_account(alice(), |info| info.balance = U256::from(1_000)).unwrap();
+        fork
+    }
+
+    #[test]
+    fn cheats_and_unsigned_sends_are_privileged() {
+        for method in ["anvil_setBalance", "hardhat_impersonateAccount", "evm_revert", "eth_sendTransaction"] {
+            assert!(is_privileged(method), "{}", method);
+        }
+        for method in ["eth_sendRawTransaction", "eth_call", "eth_getBalance"] {
+            assert!(!is_privileged(method), "{}", method);
+        }
+    }
+
+    #[test]
+    fn sends_only_from_impersonated_accounts() {
+        let mut fork = funded();
+        let err = fork.send_transaction(transfer(10)).unwrap_err();
+        assert!(matches!(err, KromeError::Unauthorized(_)));
+        assert_eq!(fork.block_number(), FORK_BLOCK);
+
+        fork.impersonated.insert(alice());
+        let hash = fork.send_transaction(transfer(10)).unwrap();
+        assert!(fork.chain.txs[&hash].success);
+        assert_eq!(fork.block_number(), FORK_BLOCK + 1);
+        assert_eq!(balance(&mut fork, alice()), U256::from(990));
+        assert_eq!(balance(&mut fork, bob()), U256::from(10));
+
+        fork.impersonated.clear();
+        assert!(fork.send_transaction(transfer(10)).is_err());
+        fork.auto_impersonate = true;
+        fork.send_transaction(transfer(10)).unwrap();
+        assert_eq!(fork.chain.db.basic(alice()).unwrap().unwrap().nonce, 2);
+    }
+
+    #[test]
+    fn mines_pending_transactions_when_automine_is_off() {
+        let mut fork = funded();
+        fork.auto_impersonate = true;
+        fork.automine = false;
+
+        let first = fork.send_transaction(transfer(10)).unwrap();
+        let second = fork.send_transaction(transfer(20)).unwrap();
+        assert_eq!(fork.block_number(), FORK_BLOCK);
+        assert_eq!(fork.status().pending, 2);
+
+        fork.mine(1, 1, None).unwrap();
+        let block = fork.head().clone();
+        assert_eq!(block.number, FORK_BLOCK + 1);
+        assert_eq!(block.transactions, vec![first, second]);
+        assert_eq!(fork.chain.txs[&second].index, 1);
+        assert_eq!(fork.status().pending, 0);
+        assert_eq!(balance(&mut fork, bob()), U256::from(30));
+
+        // Later blocks are empty and `interval` seconds apart
+        fork.mine(3, 12, None).unwrap();
+        assert_eq!(fork.block_number(), FORK_BLOCK + 4);
+        let timestamps: Vec<u64> = fork.chain.blocks[2..].iter().map(|block| block.timestamp).collect();
+        assert_eq!(timestamps[1] - timestamps[0], 12);
+        assert!(fork.chain.blocks[2..].iter().all(|block| block.transactions.is_empty()));
+    }
+
+    #[test]
+    fn finishes_the_block_when_state_cant_be_loaded() {
+        let carol = Address::repeat_byte(0xca);
+        let mut fork = fork_over(Arc::new(UnloadableAccount(carol)));
+        fork.update_account(alice(), |info| info.balance = U256::from(1_000)).unwrap();
+        let sends = [(alice(), 10), (carol, 5), (alice(), 20)];
+        for (i, (from, value)) in sends.into_iter().enumerate() {
+            fork.chain.pending.push(PendingTx {
+                hash: B256::with_last_byte(i as u8 + 1),
+                from,
+                tx: TransactionRequest::default().from(from).to(bob()).value(U256::from(value)),
+            });
+        }
+
+        let timestamp = fork.next_timestamp();
+        let failed = fork.mine_block(timestamp, true);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, B256::with_last_byte(2));
+        assert!(matches!(failed[0].1, KromeError::Fork(_)));
+
+        // Everything else made the block, and the state matches it
+        let included = vec![B256::with_last_byte(1), B256::with_last_byte(3)];
+        assert_eq!(fork.head().transactions, included);
+        assert!(included.iter().all(|hash| fork.chain.txs.contains_key(hash)));
+        assert!(fork.chain.pending.is_empty());
+        assert_eq!(balance(&mut fork, bob()), U256::from(30));
+    }
+
+    #[test]
+    fn reverts_to_a_snapshot() {
+        let mut fork = funded();
+        fork.auto_impersonate = true;
+
+        let id = fork.snapshot();
+        let hash = fork.send_transaction(transfer(10)).unwrap();
+        let later = fork.snapshot();
+        fork.send_transaction(transfer(20)).unwrap();
+        assert_eq!(fork.block_number(), FORK_BLOCK + 2);
+
+        assert!(fork.revert(id));
+        assert_eq!(fork.block_number(), FORK_BLOCK);
+        assert_eq!(balance(&mut fork, bob()), U256::ZERO);
+        assert!(!fork.chain.txs.contains_key(&hash));
+        // Reverting drops the snapshot and every later one
+        assert!(!fork.revert(id));
+        assert!(!fork.revert(later));
+
+        // The account's nonce was restored too, so it can send again
+        let again = fork.send_transaction(transfer(10)).unwrap();
+        assert_eq!(again, hash);
+    }
+
+    #[test]
+    fn serves_logs_from_local_blocks() {
+        let mut fork = funded();
+        fork.auto_impersonate = true;
+        // PUSH1 0 PUSH1 0 LOG0 STOP: logs once from the constructor
+        let deploy = TransactionRequest {
+            from: Some(alice()),
+            to: Some(TxKind::Create),
+            input: Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0xa0, 0x00]).into(),
+            ..Default::default()
+        };
+        fork.send_transaction(transfer(10)).unwrap();
+        let hash = fork.send_transaction(deploy).unwrap();
+        let contract = alice().create(1);
+
+        let receipt = receipt_json(&fork.chain.txs[&hash]);
+        assert_eq!(receipt["contractAddress"], json!(contract));
+        assert_eq!(receipt["logs"][0]["address"], json!(contract));
+        assert_eq!(receipt["logsBloom"], json!(logs_bloom(&fork.chain.txs[&hash].logs)));
+        assert_ne!(receipt["logsBloom"], json!(alloy::primitives::Bloom::ZERO));
+
+        let logs = fork.local_logs(&Filter::new().address(contract), FORK_BLOCK + 1, fork.block_number());
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["transactionHash"], json!(hash));
+        assert!(fork.local_logs(&Filter::new().address(bob()), FORK_BLOCK + 1, fork.block_number()).is_empty());
+        assert!(fork.local_logs(&Filter::new(), FORK_BLOCK + 1, FORK_BLOCK + 1).is_empty());
+
+        let header = fork.header(fork.block_number()).unwrap();
+        assert_eq!(header["logsBloom"], receipt["logsBloom"]);
+        assert!(fork.header(FORK_BLOCK).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tauri::async_runtime::JoinHandle;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use helios::ethereum::EthereumClient;
+use helios::ethereum::database::FileDB;
+use helios::ethereum::EthereumClientBuilder;
+
+use crate::error::{KromeError, Result};
+use crate::checkpoint::{self, StaleCheckpoint, CHECKPOINT_STALE_EVENT};
+use crate::config::NetworkSettings;
+use crate::endpoints::{EndpointHealth, RpcRouter};
+use crate::network::{self, NetworkSelection};
+use crate::Config;
+
+// Shared handle to a running client. Cloning is cheap, and queries made through
+// a handle don't hold any lock on HeliosState, so they can run in parallel.
+pub type HeliosClient = Arc<EthereumClient<FileDB>>;
+
+// Lifecycle of the Helios client, as reported to the front end
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "lowercase")]
+pub enum HeliosStatus {
+    Stopped,
+    Syncing,
+    Synced,
+    Failed(KromeError),
+}
+
+// Events emitted to the front end while the client starts up
+pub const SYNC_PROGRESS_EVENT: &str = "helios://sync-progress";
+pub const SYNCED_EVENT: &str = "helios://synced";
+pub const ERROR_EVENT: &str = "helios://error";
+
+// How often a sync-progress event is sent while waiting for sync
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub chain_id: u64,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Synced {
+    pub chain_id: u64,
+    pub elapsed_ms: u64,
+    pub block_number: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncError {
+    pub chain_id: u64,
+    pub error: KromeError,
+}
+
+struct HeliosInner {
+    client: Option<HeliosClient>,
+    status: HeliosStatus,
+    // Background startup task, present until the client has synced or failed
+    startup: Option<JoinHandle<()>>,
+    // Proxies Helios talks to instead of the configured RPCs; stopped on drop
+    router: Option<RpcRouter>,
+}
+
+// Global Helios client. Read commands only take the lock long enough to clone
+// the client handle; start and stop take the write lock for the whole transition.
+pub struct HeliosState(RwLock<HeliosInner>);
+
+impl Default for HeliosState {
+    fn default() -> Self {
+        HeliosState(RwLock::new(HeliosInner {
+            client: None,
+            status: HeliosStatus::Stopped,
+            startup: None,
+            router: None,
+        }))
+    }
+}
+
+impl HeliosState {
+    // Returns a handle to the client once it has synced
+    pub async fn client(&self) -> Result<HeliosClient> {
+        let guard = self.0.read().await;
+        match (&guard.client, &guard.status) {
+            (Some(client), HeliosStatus::Synced) => Ok(client.clone()),
+            (Some(_), _) => Err(KromeError::NotSynced),
+            (None, _) => Err(KromeError::NotStarted),
+        }
+    }
+
+    pub async fn status(&self) -> HeliosStatus {
+        self.0.read().await.status.clone()
+    }
+
+    // Health of every configured RPC, empty while stopped
+    pub async fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.0
+            .read()
+            .await
+            .router
+            .as_ref()
+            .map(RpcRouter::health)
+            .unwrap_or_default()
+    }
+
+    // Starts syncing in the background; progress is reported through events
+    pub async fn start<R: Runtime>(
+        &self,
+        app_handle: AppHandle<R>,
+        chain_id: u64,
+        settings: NetworkSettings,
+    ) -> Result<()> {
+        let data_dir = data_dir(&app_handle, chain_id)?;
+        let network = network::get_network(chain_id, &config_dir(&app_handle)?)?;
+
+        let mut guard = self.0.write().await;
+        if guard.client.is_some() || guard.startup.is_some() {
+            return Err(KromeError::AlreadyStarted);
+        }
+        launch(&mut guard, app_handle, data_dir, network, settings, chain_id);
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        shutdown_client(&mut *self.0.write().await).await;
+    }
+
+    // Swaps the running client for a new one without letting reads in between
+    pub async fn restart<R: Runtime>(
+        &self,
+        app_handle: AppHandle<R>,
+        chain_id: u64,
+        settings: NetworkSettings,
+    ) -> Result<()> {
+        let data_dir = data_dir(&app_handle, chain_id)?;
+        let network = network::get_network(chain_id, &config_dir(&app_handle)?)?;
+
+        let mut guard = self.0.write().await;
+        shutdown_client(&mut guard).await;
+        launch(&mut guard, app_handle, data_dir, network, settings, chain_id);
+        Ok(())
+    }
+}
+
+async fn build_client(
+    data_dir: PathBuf,
+    network: NetworkSelection,
+    settings: &NetworkSettings,
+    router: &RpcRouter,
+) -> Result<EthereumClient<FileDB>> {
+    let builder = match network {
+        NetworkSelection::Known(network) => EthereumClientBuilder::new().network(network),
+        NetworkSelection::Custom(config) => EthereumClientBuilder::new().config(config),
+    };
+
+    let mut builder = builder
+        .execution_rpc(&router.execution.url)
+        .consensus_rpc(&router.consensus.url)
+        .data_dir(data_dir);
+    if let Some(checkpoint) = settings.checkpoint {
+        builder = builder.checkpoint(checkpoint);
+    }
+    if let Some(fallback) = &settings.checkpoint_fallback {
+        builder = builder.fallback(fallback);
+    }
+    if settings.load_external_fallback {
+        builder = builder.load_external_fallback();
+    }
+    if settings.strict_checkpoint_age {
+        builder = builder.strict_checkpoint_age();
+    }
+
+    let mut client = builder.build()?;
+
+    client.start().await?;
+    Ok(client)
+}
+
+// Waits for the client to sync, reporting progress to the front end as it goes
+async fn wait_synced<R: Runtime>(app_handle: &AppHandle<R>, client: &EthereumClient<FileDB>, chain_id: u64) -> Synced {
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+
+    let synced = client.wait_synced();
+    tokio::pin!(synced);
+
+    loop {
+        tokio::select! {
+            _ = &mut synced => break,
+            _ = ticker.tick() => {
+                let _ = app_handle.emit(SYNC_PROGRESS_EVENT, SyncProgress {
+                    chain_id,
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                });
+            }
+        }
+    }
+
+    Synced {
+        chain_id,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        block_number: client.get_block_number().await.ok().map(|n| n.to::<u64>()),
+    }
+}
+
+async fn run_startup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    data_dir: PathBuf,
+    network: NetworkSelection,
+    settings: NetworkSettings,
+    chain_id: u64,
+) {
+    let state = app_handle.state::<HeliosState>();
+
+    let mut consensus_rpcs = settings.consensus_rpcs.clone();
+    if consensus_rpcs.is_empty() {
+        consensus_rpcs.push(app_handle.state::<Config>().default_consensus_rpc.clone());
+    }
+
+    let started = async {
+        let router = RpcRouter::spawn(settings.execution_rpcs.clone(), consensus_rpcs).await?;
+        warn_if_stale(&app_handle, chain_id, &network, &data_dir, &settings, &router.consensus.url).await;
+        let client = build_client(data_dir, network, &settings, &router).await?;
+        Ok::<_, KromeError>((router, client))
+    };
+
+    let client = match started.await {
+        Ok((router, client)) => {
+            state.0.write().await.router = Some(router);
+            Arc::new(client)
+        }
+        Err(e) => {
+            let mut guard = state.0.write().await;
+            guard.status = HeliosStatus::Failed(e.clone());
+            guard.startup = None;
+            drop(guard);
+            let _ = app_handle.emit(ERROR_EVENT, SyncError { chain_id, error: e });
+            return;
+        }
+    };
+
+    // Hand the client over right away so stop_helios can shut it down mid-sync
+    state.0.write().await.client = Some(client.clone());
+
+    let synced = wait_synced(&app_handle, &client, chain_id).await;
+
+    let mut guard = state.0.write().await;
+    guard.status = HeliosStatus::Synced;
+    guard.startup = None;
+    drop(guard);
+    let _ = app_handle.emit(SYNCED_EVENT, synced);
+}
+
+// Warns the front end before bootstrapping from a checkpoint that is past the
+// weak-subjectivity period. Helios refuses those when strictCheckpointAge is set.
+async fn warn_if_stale<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    chain_id: u64,
+    network: &NetworkSelection,
+    data_dir: &Path,
+    settings: &NetworkSettings,
+    consensus_rpc: &str,
+) {
+    let info = match checkpoint::info(chain_id, network, data_dir, settings.checkpoint, Some(consensus_rpc)).await {
+        Ok(info) => info,
+        Err(_) => return,
+    };
+    if let (true, Some(age_secs)) = (info.stale, info.age_secs) {
+        let _ = app_handle.emit(CHECKPOINT_STALE_EVENT, StaleCheckpoint {
+            chain_id,
+            checkpoint: info.active,
+            age_secs,
+            max_age_secs: info.max_age_secs,
+        });
+    }
+}
+
+// Must be called with the write lock held
+async fn shutdown_client(inner: &mut HeliosInner) {
+    if let Some(startup) = inner.startup.take() {
+        startup.abort();
+    }
+    if let Some(client) = inner.client.take() {
+        client.shutdown().await;
+    }
+    inner.router = None;
+    inner.status = HeliosStatus::Stopped;
+}
+
+// Each chain gets its own Helios data dir so their checkpoints don't mix
+pub(crate) fn data_dir<R: Runtime>(app_handle: &AppHandle<R>, chain_id: u64) -> Result<PathBuf> {
+    let helios_dir = app_data_dir(app_handle)?.join("helios");
+    let chain_dir = helios_dir.join(chain_id.to_string());
+
+    // Older versions kept a single mainnet checkpoint directly in helios/
+    let legacy = helios_dir.join("checkpoint");
+    if chain_id == 1 && legacy.exists() && !chain_dir.exists() {
+        fs::create_dir_all(&chain_dir)?;
+        fs::rename(&legacy, chain_dir.join("checkpoint"))?;
+    }
+    Ok(chain_dir)
+}
+
+pub(crate) fn app_data_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|_| KromeError::Path("data"))
+}
+
+pub(crate) fn config_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|_| KromeError::Path("config"))
+}
+
+// Kicks off startup in the background; progress is reported through events.
+// Must be called with the write lock held.
+fn launch<R: Runtime>(
+    inner: &mut HeliosInner,
+    app_handle: AppHandle<R>,
+    data_dir: PathBuf,
+    network: NetworkSelection,
+    settings: NetworkSettings,
+    chain_id: u64,
+) {
+    inner.status = HeliosStatus::Syncing;
+    inner.startup = Some(tauri::async_runtime::spawn(run_startup(
+        app_handle,
+        data_dir,
+        network,
+        settings,
+        chain_id,
+    )));
+}
@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::{Emitter, Manager, RunEvent, Runtime, WindowEvent};
+
+pub mod approvals;
+pub mod checkpoint;
+pub mod config;
+pub mod endpoints;
+pub mod error;
+pub mod eth;
+pub mod fork;
+pub mod helios;
+pub mod network;
+pub mod queue;
+pub mod rpc;
+pub mod secrets;
+pub mod signing;
+pub mod simulation;
+pub mod subscriptions;
+pub mod trace;
+pub mod transactions;
+pub mod wallet;
+
+mod commands;
+
+pub use approvals::{ApprovalBroker, ApprovalKind, ApprovalRequest, Connections, Origin};
+pub use config::{ConfigState, KromeConfig, NetworkSettings};
+pub use error::{KromeError, Result};
+pub use fork::{ForkState, ForkStatus};
+pub use helios::{HeliosClient, HeliosState, HeliosStatus};
+pub use queue::{QueueStatus, QueuedTx, TxQueue};
+pub use secrets::{SecretBackend, SecretStore, SecretStoreStatus, SharedSecretStore};
+pub use signing::message::TypedDataSummary;
+pub use signing::SignedTransaction;
+pub use simulation::Simulation;
+pub use subscriptions::Subscriptions;
+pub use trace::{CallTrace, TraceOptions};
+pub use transactions::{TrackedTx, TxStatus, TxTracker};
+pub use wallet::WalletState;
+
+// Plugin configuration. It can be passed to `init()` or set under
+// `plugins.krome` in tauri.conf.json, which takes precedence.
+// See `config.schema.json` for the JSON form.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    // Consensus RPC used when neither start_helios nor krome.json give any
+    pub default_consensus_rpc: String,
+    // Seconds an unlocked account can go unused before it locks; 0 disables it
+    pub wallet_auto_lock_secs: u64,
+    // Where wallet keystores and RPC credentials are kept
+    pub secret_store: SecretBackend,
+    // App page the approval window loads to show signing and sending requests
+    pub approval_url: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_consensus_rpc: "https://www.lightclientdata.org".to_string(),
+            wallet_auto_lock_secs: 300,
+            secret_store: SecretBackend::default(),
+            approval_url: "approval".to_string(),
+        }
+    }
+}
+
+// Access to Krome's state from Rust, e.g. `app.krome().client().await`
+pub trait KromeExt<R: Runtime> {
+    fn krome(&self) -> &HeliosState;
+}
+
+impl<R: Runtime, T: Manager<R>> KromeExt<R> for T {
+    fn krome(&self) -> &HeliosState {
+        self.state::<HeliosState>().inner()
+    }
+}
+
+pub fn init<R: Runtime>(config: Config) -> TauriPlugin<R, Option<Config>> {
+    Builder::<R, Option<Config>>::new("krome")
+        .invoke_handler(tauri::generate_handler![
+            commands::start_helios,
+            commands::stop_helios,
+            commands::restart_helios,
+            commands::helios_status,
+            commands::get_endpoint_health,
+            commands::get_networks,
+            commands::get_config,
+            commands::update_config,
+            commands::get_checkpoint,
+            commands::pin_checkpoint,
+            commands::unpin_checkpoint,
+            commands::export_checkpoint,
+            commands::import_checkpoint,
+            eth::get_latest_block,
+            eth::get_balance,
+            eth::get_transaction_count,
+            eth::get_code,
+            eth::get_storage_at,
+            eth::get_block_by_number,
+            eth::get_block_by_hash,
+            eth::get_transaction_by_hash,
+            eth::get_transaction_receipt,
+            eth::get_logs,
+            eth::gas_price,
+            eth::max_priority_fee,
+            eth::fee_history,
+            eth::chain_id,
+            eth::block_number,
+            eth::call,
+            eth::estimate_gas,
+            rpc::eth_request,
+            transactions::send_raw_transaction,
+            transactions::get_tracked_transactions,
+            transactions::get_tracked_transaction,
+            transactions::forget_transaction,
+            signing::sign_transaction,
+            signing::send_transaction,
+            signing::personal_sign,
+            signing::sign_typed_data,
+            signing::recover_message_signer,
+            signing::recover_typed_data_signer,
+            signing::summarize_typed_data,
+            simulation::simulate_transaction,
+            trace::trace_call,
+            fork::start_fork,
+            fork::stop_fork,
+            fork::get_fork_status,
+            queue::get_queue,
+            queue::speed_up_transaction,
+            queue::cancel_transaction,
+            queue::resync_nonces,
+            approvals::get_pending_approvals,
+            approvals::resolve_approval,
+            subscriptions::subscribe_new_heads,
+            subscriptions::subscribe_logs,
+            subscriptions::unsubscribe,
+            secrets::get_secret_store_status,
+            wallet::list_accounts,
+            wallet::create_account,
+            wallet::import_private_key,
+            wallet::import_keystore,
+            wallet::export_keystore,
+            wallet::unlock_account,
+            wallet::lock_account,
+            wallet::generate_mnemonic,
+            wallet::validate_mnemonic,
+            wallet::derive_addresses,
+            wallet::import_mnemonic,
+        ])
+        .setup(move |app, api| {
+            let config = api.config().clone().unwrap_or(config);
+            let secret_backend = config.secret_store;
+            app.manage(config);
+            app.manage(HeliosState::default());
+            app.manage(ApprovalBroker::default());
+            app.manage(Connections::default());
+            app.manage(ForkState::default());
+
+            let app_handle = app.app_handle().clone();
+            let data_dir = helios::app_data_dir(&app_handle)?;
+            let (store, store_status) = secrets::open(secret_backend, &app.config().identifier, &data_dir)?;
+            app.manage(store.clone());
+            app.manage(store_status);
+            let krome_config = ConfigState::load(&helios::config_dir(&app_handle)?, store.clone())?;
+            let saved = tauri::async_runtime::block_on(krome_config.get());
+            app.manage(krome_config);
+            app.manage(TxTracker::load(&data_dir)?);
+            tauri::async_runtime::spawn(transactions::watch(app_handle.clone()));
+            app.manage(TxQueue::load(&data_dir)?);
+            tauri::async_runtime::spawn(queue::watch(app_handle.clone()));
+            app.manage(Subscriptions::default());
+            tauri::async_runtime::spawn(subscriptions::watch(app_handle.clone()));
+            app.manage(wallet::load(&app_handle, store, &data_dir)?);
+            tauri::async_runtime::spawn(wallet::watch(app_handle.clone()));
+
+            if saved.auto_start {
+                let chain_id = saved.active_chain_id;
+                let settings = saved.network(chain_id);
+                tauri::async_runtime::spawn(async move {
+                    // Nobody is waiting on this start, so report failures as events
+                    if let Err(error) = app_handle.krome().start(app_handle.clone(), chain_id, settings).await {
+                        let _ = app_handle.emit(helios::ERROR_EVENT, helios::SyncError { chain_id, error });
+                    }
+                });
+            }
+            Ok(())
+        })
+        .on_event(|app, event| {
+            if let RunEvent::WindowEvent {
+                label,
+                event: WindowEvent::Destroyed,
+                ..
+            } = event
+            {
+                app.state::<Subscriptions>().remove_window(label);
+                // Closing the approval window answers everything in it with no
+                if label == approvals::APPROVAL_WINDOW_LABEL {
+                    app.state::<ApprovalBroker>().window_closed();
+                }
+            }
+        })
+        .build()
+}
@@ -0,0 +1,200 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use alloy::primitives::{FixedBytes, B256};
+use helios::ethereum::config::networks::Network;
+use helios::ethereum::config::{ChainConfig, Config, Fork, Forks};
+
+use crate::error::{KromeError, Result};
+
+// Networks Helios ships a config for, by chain ID
+pub const KNOWN_NETWORKS: &[(u64, &str)] = &[
+    (1, "mainnet"),
+    (11155111, "sepolia"),
+    (17000, "holesky"),
+];
+
+// Which network to point the client at: one Helios knows about, or a devnet
+// described by a JSON file the app supplies.
+pub enum NetworkSelection {
+    Known(Network),
+    Custom(Config),
+}
+
+impl NetworkSelection {
+    pub fn genesis_time(&self) -> u64 {
+        match self {
+            NetworkSelection::Known(network) => network.to_base_config().chain.genesis_time,
+            NetworkSelection::Custom(config) => config.chain.genesis_time,
+        }
+    }
+
+    // Checkpoint the network ships with, bootstrapped from when none is
+    // pinned or stored
+    pub fn default_checkpoint(&self) -> B256 {
+        match self {
+            NetworkSelection::Known(network) => network.to_base_config().default_checkpoint,
+            NetworkSelection::Custom(config) => config.default_checkpoint,
+        }
+    }
+
+    // Weak-subjectivity period, in seconds
+    pub fn max_checkpoint_age(&self) -> u64 {
+        match self {
+            NetworkSelection::Known(network) => network.to_base_config().max_checkpoint_age,
+            NetworkSelection::Custom(config) => config.max_checkpoint_age,
+        }
+    }
+}
+
+// Fork activation, as written in a custom network file
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkSpec {
+    pub epoch: u64,
+    pub fork_version: FixedBytes<4>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkSchedule {
+    pub genesis: ForkSpec,
+    pub altair: ForkSpec,
+    pub bellatrix: ForkSpec,
+    pub capella: ForkSpec,
+    pub deneb: ForkSpec,
+    #[serde(default)]
+    pub electra: Option<ForkSpec>,
+}
+
+// Contents of `networks/<chain_id>.json` in the app config dir
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomNetwork {
+    pub chain_id: u64,
+    pub genesis_time: u64,
+    pub genesis_root: B256,
+    pub default_checkpoint: B256,
+    pub forks: ForkSchedule,
+    #[serde(default)]
+    pub max_checkpoint_age: Option<u64>,
+}
+
+// Fork that never activates, for schedules that stop before it
+const NEVER: u64 = u64::MAX;
+
+impl From<ForkSpec> for Fork {
+    fn from(spec: ForkSpec) -> Self {
+        Fork {
+            epoch: spec.epoch,
+            fork_version: spec.fork_version,
+        }
+    }
+}
+
+impl CustomNetwork {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| KromeError::InvalidNetworkConfig(format!("{}: {}", path.display(), e)))
+    }
+
+    fn into_config(self) -> Config {
+        let forks = self.forks;
+        let electra = forks.electra.unwrap_or(ForkSpec {
+            epoch: NEVER,
+            fork_version: FixedBytes::ZERO,
+        });
+
+        let mut config = Config {
+            default_checkpoint: self.default_checkpoint,
+            chain: ChainConfig {
+                chain_id: self.chain_id,
+                genesis_time: self.genesis_time,
+                genesis_root: self.genesis_root,
+            },
+            forks: Forks {
+                genesis: forks.genesis.into(),
+                altair: forks.altair.into(),
+                bellatrix: forks.bellatrix.into(),
+                capella: forks.capella.into(),
+                deneb: forks.deneb.into(),
+                electra: electra.into(),
+            },
+            ..Default::default()
+        };
+        if let Some(age) = self.max_checkpoint_age {
+            config.max_checkpoint_age = age;
+        }
+        config
+    }
+}
+
+// Path of the custom network file for a chain ID
+pub fn custom_network_path(config_dir: &Path, chain_id: u64) -> PathBuf {
+    config_dir.join("networks").join(format!("{}.json", chain_id))
+}
+
+// Resolves a chain ID to a network, falling back to a custom network file
+pub fn get_network(chain_id: u64, config_dir: &Path) -> Result<NetworkSelection> {
+    if let Ok(network) = Network::from_chain_id(chain_id) {
+        return Ok(NetworkSelection::Known(network));
+    }
+
+    let path = custom_network_path(config_dir, chain_id);
+    if !path.exists() {
+        return Err(KromeError::UnsupportedNetwork(chain_id));
+    }
+
+    let custom = CustomNetwork::load(&path)?;
+    if custom.chain_id != chain_id {
+        return Err(KromeError::InvalidNetworkConfig(format!(
+            "{} is for chain ID {}, expected {}",
+            path.display(),
+            custom.chain_id,
+            chain_id
+        )));
+    }
+    Ok(NetworkSelection::Custom(custom.into_config()))
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInfo {
+    pub chain_id: u64,
+    pub name: String,
+    pub custom: bool,
+}
+
+// Lists every network that can be passed to start_helios
+pub fn list_networks(config_dir: &Path) -> Vec<NetworkInfo> {
+    let mut networks: Vec<NetworkInfo> = KNOWN_NETWORKS
+        .iter()
+        .map(|(chain_id, name)| NetworkInfo {
+            chain_id: *chain_id,
+            name: name.to_string(),
+            custom: false,
+        })
+        .collect();
+
+    let entries = match fs::read_dir(config_dir.join("networks")) {
+        Ok(entries) => entries,
+        Err(_) => return networks,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let chain_id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok());
+        if let (Some(chain_id), Some("json")) = (chain_id, path.extension().and_then(|e| e.to_str())) {
+            networks.push(NetworkInfo {
+                chain_id,
+                name: format!("custom-{}", chain_id),
+                custom: true,
+            });
+        }
+    }
+    networks
+}
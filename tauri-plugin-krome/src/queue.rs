@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State, WebviewWindow};
+
+use alloy::primitives::{Address, Bytes, TxKind, B256, U256};
+use alloy::rpc::types::{TransactionInput, TransactionRequest};
+use helios::core::types::BlockTag;
+
+use crate::approvals::{ApprovalKind, Origin};
+use crate::config::write_atomic;
+use crate::error::{KromeError, Result};
+use crate::helios::{HeliosClient, HeliosState};
+use crate::signing;
+use crate::transactions::{self, TxStatus, TxTracker};
+use crate::wallet::WalletState;
+use crate::KromeExt;
+
+// Outgoing transactions from wallet accounts. Nonces are handed out locally so
+// back-to-back sends don't collide, and each transaction stays in the queue,
+// saved in the app data dir, until the tracker is done with it. Transactions
+// that couldn't be broadcast are retried, and pending ones can be sped up or
+// cancelled by replacing them at the same nonce.
+
+// Sent with the QueuedTx whenever it changes or leaves the queue
+pub const QUEUE_EVENT: &str = "helios://queue";
+
+const QUEUE_FILE: &str = "queue.json";
+const RETRY_INTERVAL: Duration = Duration::from_secs(6);
+// Nodes only accept a replacement that raises fees by at least this much
+const MIN_FEE_BUMP_PERCENT: u128 = 10;
+const TRANSFER_GAS: u64 = 21_000;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    // Signed but not broadcast yet, e.g. because the RPC was unreachable
+    Queued,
+    // Broadcast and being tracked
+    Pending,
+    // Confirmed, replaced or dropped; only ever seen in the last event
+    Done,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTx {
+    // Hash of the latest version, which changes on speed-up or cancel
+    pub hash: B256,
+    // Hashes of the versions it replaced, oldest first
+    pub replaced: Vec<B256>,
+    pub chain_id: u64,
+    pub from: Address,
+    pub nonce: u64,
+    // The transaction as signed, with every field filled
+    pub tx: TransactionRequest,
+    pub raw: Bytes,
+    pub status: QueueStatus,
+    pub cancelled: bool,
+    pub confirmations: Option<u64>,
+    // Why a node rejected it, when it left the queue that way
+    pub error: Option<String>,
+    // Unix timestamps in seconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl QueuedTx {
+    fn matches(&self, hash: B256) -> bool {
+        self.hash == hash || self.replaced.contains(&hash)
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    txs: Vec<QueuedTx>,
+    // Next nonce to hand out, by chain and account. Not saved, so it's
+    // re-synced with the chain after a restart.
+    nonces: HashMap<(u64, Address), u64>,
+}
+
+pub struct TxQueue {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl TxQueue {
+    // Reads the saved queue. A missing file means it's empty.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(QUEUE_FILE);
+        let txs = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(TxQueue {
+            path,
+            inner: Mutex::new(Inner {
+                txs,
+                nonces: HashMap::new(),
+            }),
+        })
+    }
+
+    pub async fn list(&self, chain_id: Option<u64>, from: Option<Address>) -> Vec<QueuedTx> {
+        self.inner
+            .lock()
+            .await
+            .txs
+            .iter()
+            .filter(|tx| chain_id.map_or(true, |id| tx.chain_id == id))
+            .filter(|tx| from.map_or(true, |from| tx.from == from))
+            .cloned()
+            .collect()
+    }
+
+    // Forgets every handed-out nonce, so the next send reads it from the chain
+    pub async fn resync(&self) {
+        self.inner.lock().await.nonces.clear();
+    }
+
+    async fn reserve_nonce(&self, client: &HeliosClient, chain_id: u64, from: Address) -> Result<u64> {
+        let on_chain = client.get_nonce(from, BlockTag::Latest).await?;
+        Ok(self.take_nonce(chain_id, from, on_chain).await)
+    }
+
+    // The nonce reserve_nonce would hand out now, without taking it
+    async fn peek_nonce(&self, client: &HeliosClient, chain_id: u64, from: Address) -> Result<u64> {
+        let on_chain = client.get_nonce(from, BlockTag::Latest).await?;
+        Ok(next_nonce(&*self.inner.lock().await, chain_id, from, on_chain))
+    }
+
+    async fn take_nonce(&self, chain_id: u64, from: Address, on_chain: u64) -> u64 {
+        let mut inner = self.inner.lock().await;
+        let nonce = next_nonce(&inner, chain_id, from, on_chain);
+        inner.nonces.insert((chain_id, from), nonce + 1);
+        nonce
+    }
+
+    // Gives back a nonce that never made it into a transaction. If later ones
+    // were handed out meanwhile, the account is re-synced instead.
+    async fn release_nonce(&self, chain_id: u64, from: Address, nonce: u64) {
+        let mut inner = self.inner.lock().await;
+        match inner.nonces.get(&(chain_id, from)) {
+            Some(next) if *next == nonce + 1 => {
+                inner.nonces.insert((chain_id, from), nonce);
+            }
+            _ => {
+                inner.nonces.remove(&(chain_id, from));
+            }
+        }
+    }
+
+    // Signs `tx` with the unlocked account in `tx.from` at the next free
+    // nonce, queues it and broadcasts it. A transaction that can't be
+    // broadcast because the client is offline stays queued and is retried.
+    // The nonce is only taken once the user approves, so rejecting a prompt
+    // can't leave a gap below sends approved while it was open.
+    pub async fn send<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        client: &HeliosClient,
+        wallet: &WalletState,
+        origin: Origin,
+        mut tx: TransactionRequest,
+        confirmations: Option<u64>,
+    ) -> Result<QueuedTx> {
+        let from = tx
+            .from
+            .ok_or_else(|| KromeError::InvalidParams("transaction has no from address".to_string()))?;
+        // Fail before reserving a nonce if the account isn't unlocked
+        wallet.signer(from)?;
+        let chain_id = client.chain_id().await;
+
+        // An explicit nonce is used as is, e.g. to fill a gap. Otherwise the
+        // prompt shows the nonce that's next for now.
+        let reserved = tx.nonce.is_none();
+        if reserved {
+            tx.nonce = Some(self.peek_nonce(client, chain_id, from).await?);
+        }
+        let mut nonce = tx.nonce.unwrap_or_default();
+
+        let approval = |tx, simulation| ApprovalKind::SendTransaction { tx, simulation };
+        let mut typed = signing::approve_request(app_handle, client, wallet, origin, tx, approval).await?;
+        if reserved {
+            nonce = self.reserve_nonce(client, chain_id, from).await?;
+            signing::set_nonce(&mut typed, nonce);
+        }
+        let signed = match wallet.signer(from).and_then(|signer| signing::sign(&signer, typed)) {
+            Ok(signed) => signed,
+            Err(e) => {
+                if reserved {
+                    self.release_nonce(chain_id, from, nonce).await;
+                }
+                return Err(e);
+            }
+        };
+
+        let created_at = now();
+        let queued = QueuedTx {
+            hash: signed.hash,
+            replaced: Vec::new(),
+            chain_id,
+            from,
+            nonce,
+            tx: signed.tx,
+            raw: signed.raw,
+            status: QueueStatus::Queued,
+            cancelled: false,
+            confirmations,
+            error: None,
+            created_at,
+            updated_at: created_at,
+        };
+        self.upsert(app_handle, queued.clone()).await?;
+
+        match self.broadcast(app_handle, client, queued).await {
+            Err(e) if !is_retryable(&e) => {
+                self.remove(app_handle, signed.hash, Some(e.to_string())).await?;
+                if reserved {
+                    self.release_nonce(chain_id, from, nonce).await;
+                }
+                Err(e)
+            }
+            result => result,
+        }
+    }
+
+    // Replaces a queued or pending transaction with one at the same nonce and
+    // higher fees. With `cancel` the replacement is a zero-value send to self.
+    pub async fn replace<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        client: &HeliosClient,
+        wallet: &WalletState,
+        origin: Origin,
+        hash: B256,
+        cancel: bool,
+    ) -> Result<QueuedTx> {
+        let current = self
+            .inner
+            .lock()
+            .await
+            .txs
+            .iter()
+            .find(|tx| tx.matches(hash))
+            .cloned()
+            .ok_or(KromeError::TransactionNotFound(hash))?;
+
+        let mut tx = current.tx.clone();
+        if cancel {
+            tx.to = Some(TxKind::Call(current.from));
+            tx.value = Some(U256::ZERO);
+            tx.input = TransactionInput::default();
+            tx.gas = Some(TRANSFER_GAS);
+            if tx.access_list.is_some() {
+                tx.access_list = Some(Default::default());
+            }
+        }
+        bump_fees(client, &mut tx).await?;
+        let replaces = current.hash;
+        let approval = |tx, simulation| ApprovalKind::ReplaceTransaction {
+            tx,
+            simulation,
+            replaces,
+            cancel,
+        };
+        let signed = signing::sign_request(app_handle, client, wallet, origin, tx, approval).await?;
+
+        let mut replacement = current.clone();
+        replacement.replaced.push(current.hash);
+        replacement.hash = signed.hash;
+        replacement.tx = signed.tx;
+        replacement.raw = signed.raw;
+        replacement.cancelled |= cancel;
+
+        // Only swap it in once a node has accepted it, so a rejected
+        // replacement leaves the original as it was
+        transactions::send_raw(app_handle, client, &replacement.raw, replacement.confirmations).await?;
+        replacement.status = QueueStatus::Pending;
+        replacement.updated_at = now();
+        self.upsert(app_handle, replacement.clone()).await?;
+        Ok(replacement)
+    }
+
+    async fn broadcast<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        client: &HeliosClient,
+        mut queued: QueuedTx,
+    ) -> Result<QueuedTx> {
+        match transactions::send_raw(app_handle, client, &queued.raw, queued.confirmations).await {
+            Ok(_) => {
+                queued.status = QueueStatus::Pending;
+                queued.updated_at = now();
+                self.upsert(app_handle, queued.clone()).await?;
+                Ok(queued)
+            }
+            Err(e) if is_retryable(&e) => Ok(queued),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Saves a new or changed entry, matched by chain, account and nonce
+    async fn upsert<R: Runtime>(&self, app_handle: &AppHandle<R>, queued: QueuedTx) -> Result<()> {
+        let mut inner = self.inner.lock().await;
+        inner
+            .txs
+            .retain(|tx| !(tx.chain_id == queued.chain_id && tx.from == queued.from && tx.nonce == queued.nonce));
+        inner.txs.push(queued.clone());
+        inner.txs.sort_by_key(|tx| (tx.chain_id, tx.from, tx.nonce));
+        save(&self.path, &inner.txs)?;
+        drop(inner);
+        let _ = app_handle.emit(QUEUE_EVENT, queued);
+        Ok(())
+    }
+
+    async fn remove<R: Runtime>(&self, app_handle: &AppHandle<R>, hash: B256, error: Option<String>) -> Result<()> {
+        let mut inner = self.inner.lock().await;
+        let Some(index) = inner.txs.iter().position(|tx| tx.matches(hash)) else {
+            return Ok(());
+        };
+        let mut done = inner.txs.remove(index);
+        save(&self.path, &inner.txs)?;
+        drop(inner);
+        done.status = QueueStatus::Done;
+        done.error = error;
+        done.updated_at = now();
+        let _ = app_handle.emit(QUEUE_EVENT, done);
+        Ok(())
+    }
+
+    // Retries broadcasts and clears out transactions the tracker is done with
+    async fn poll<R: Runtime>(&self, app_handle: &AppHandle<R>, client: &HeliosClient) -> Result<()> {
+        let chain_id = client.chain_id().await;
+        let tracker = app_handle.state::<TxTracker>();
+
+        for queued in self.list(Some(chain_id), None).await {
+            match queued.status {
+                QueueStatus::Queued => {
+                    if let Err(e) = self.broadcast(app_handle, client, queued.clone()).await {
+                        // The node won't take it, so nothing after it can go
+                        // through either until the nonce is re-used
+                        self.remove(app_handle, queued.hash, Some(e.to_string())).await?;
+                        self.release_nonce(chain_id, queued.from, queued.nonce).await;
+                    }
+                }
+                QueueStatus::Pending => {
+                    let status = tracker.get(queued.hash).await.map(|tx| tx.status);
+                    if status.as_ref().map_or(true, TxStatus::is_final) {
+                        self.remove(app_handle, queued.hash, None).await?;
+                        // A dropped transaction leaves its nonce unused
+                        if status == Some(TxStatus::Dropped) {
+                            self.inner.lock().await.nonces.remove(&(chain_id, queued.from));
+                        }
+                    }
+                }
+                QueueStatus::Done => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// The next nonce for an account: whichever is higher of the chain's count
+// and what the queue has already handed out
+fn next_nonce(inner: &Inner, chain_id: u64, from: Address, on_chain: u64) -> u64 {
+    let local = match inner.nonces.get(&(chain_id, from)) {
+        Some(next) => *next,
+        None => inner
+            .txs
+            .iter()
+            .filter(|tx| tx.chain_id == chain_id && tx.from == from)
+            .map(|tx| tx.nonce + 1)
+            .max()
+            .unwrap_or(0),
+    };
+    local.max(on_chain)
+}
+
+// The client being offline is worth waiting out; anything else is a rejection
+fn is_retryable(e: &KromeError) -> bool {
+    matches!(
+        e,
+        KromeError::NotStarted | KromeError::NotSynced | KromeError::RpcUnreachable(_)
+    )
+}
+
+fn bump(fee: u128) -> u128 {
+    fee.saturating_add((fee.saturating_mul(MIN_FEE_BUMP_PERCENT)).div_ceil(100))
+}
+
+// Raises fees enough for nodes to accept the replacement, or to what the
+// network asks now if that's higher
+async fn bump_fees(client: &HeliosClient, tx: &mut TransactionRequest) -> Result<()> {
+    if let Some(gas_price) = tx.gas_price {
+        let current: u128 = client.get_gas_price().await?.saturating_to();
+        tx.gas_price = Some(bump(gas_price).max(current));
+        return Ok(());
+    }
+
+    let mut suggested = TransactionRequest::default();
+    signing::fill_eip1559_fees(client, &mut suggested).await?;
+    let tip = bump(tx.max_priority_fee_per_gas.unwrap_or_default())
+        .max(suggested.max_priority_fee_per_gas.unwrap_or_default());
+    let max_fee = bump(tx.max_fee_per_gas.unwrap_or_default())
+        .max(suggested.max_fee_per_gas.unwrap_or_default())
+        .max(tip);
+    tx.max_priority_fee_per_gas = Some(tip);
+    tx.max_fee_per_gas = Some(max_fee);
+    Ok(())
+}
+
+fn save(path: &Path, txs: &[QueuedTx]) -> Result<()> {
+    write_atomic(path, &serde_json::to_string_pretty(txs)?)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Works the queue for as long as the app runs. Nonces are re-synced with the
+// chain whenever the client comes back after being stopped or syncing.
+pub(crate) async fn watch<R: Runtime>(app_handle: AppHandle<R>) {
+    let mut ticker = tokio::time::interval(RETRY_INTERVAL);
+    let mut connected = false;
+    loop {
+        ticker.tick().await;
+        let Ok(client) = app_handle.krome().client().await else {
+            connected = false;
+            continue;
+        };
+        let queue = app_handle.state::<TxQueue>();
+        if !connected {
+            queue.resync().await;
+            connected = true;
+        }
+        let _ = queue.poll(&app_handle, &client).await;
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_queue(
+    queue: State<'_, TxQueue>,
+    chain_id: Option<u64>,
+    from: Option<Address>,
+) -> Result<Vec<QueuedTx>> {
+    Ok(queue.list(chain_id, from).await)
+}
+
+// Re-broadcasts at the same nonce with fees raised by at least 10%
+#[tauri::command]
+pub(crate) async fn speed_up_transaction<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    state: State<'_, HeliosState>,
+    wallet: State<'_, WalletState>,
+    queue: State<'_, TxQueue>,
+    hash: B256,
+) -> Result<QueuedTx> {
+    let client = state.client().await?;
+    queue.replace(&app_handle, &client, &wallet, Origin::of(&window), hash, false).await
+}
+
+// Replaces the transaction with a zero-value send to self at the same nonce
+#[tauri::command]
+pub(crate) async fn cancel_transaction<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    state: State<'_, HeliosState>,
+    wallet: State<'_, WalletState>,
+    queue: State<'_, TxQueue>,
+    hash: B256,
+) -> Result<QueuedTx> {
+    let client = state.client().await?;
+    queue.replace(&app_handle, &client, &wallet, Origin::of(&window), hash, true).await
+}
+
+#[tauri::command]
+pub(crate) async fn resync_nonces(queue: State<'_, TxQueue>) -> Result<()> {
+    queue.resync().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const ALICE: Address = Address::new([0xa1; 20]);
+    const BOB: Address = Address::new([0xb0; 20]);
+
+    fn queue() -> TxQueue {
+        TxQueue {
+            path: PathBuf::new(),
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    fn queued(chain_id: u64, from: Address, nonce: u64) -> QueuedTx {
+        QueuedTx {
+            hash: B256::with_last_byte(nonce as u8),
+            replaced: Vec::new(),
+            chain_id,
+            from,
+            nonce,
+            tx: TransactionRequest::default(),
+            raw: Bytes::new(),
+            status: QueueStatus::Pending,
+            cancelled: false,
+            confirmations: None,
+            error: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn hands_out_nonces_in_order() {
+        let queue = queue();
+        assert_eq!(queue.take_nonce(1, ALICE, 5).await, 5);
+        // Before the first is mined, the chain still says 5
+        assert_eq!(queue.take_nonce(1, ALICE, 5).await, 6);
+        // The chain wins once it's ahead, e.g. after sending from another wallet
+        assert_eq!(queue.take_nonce(1, ALICE, 10).await, 10);
+        // Accounts and chains are counted separately
+        assert_eq!(queue.take_nonce(1, BOB, 0).await, 0);
+        assert_eq!(queue.take_nonce(5, ALICE, 3).await, 3);
+    }
+
+    #[tokio::test]
+    async fn rejecting_an_earlier_send_leaves_no_gap() {
+        let queue = queue();
+        // Send A opens its prompt, then send B opens one while A's is still up
+        let shown_a = next_nonce(&*queue.inner.lock().await, 1, ALICE, 5);
+        let shown_b = next_nonce(&*queue.inner.lock().await, 1, ALICE, 5);
+        assert_eq!((shown_a, shown_b), (5, 5));
+
+        // B is approved and takes the nonce; A is rejected and never takes one
+        let b = queue.take_nonce(1, ALICE, 5).await;
+        assert_eq!(b, 5);
+        queue.inner.lock().await.txs.push(queued(1, ALICE, b));
+
+        // The next send follows B directly
+        assert_eq!(next_nonce(&*queue.inner.lock().await, 1, ALICE, 5), 6);
+        assert_eq!(queue.take_nonce(1, ALICE, 5).await, 6);
+    }
+
+    #[tokio::test]
+    async fn unsigned_sends_give_their_nonce_back() {
+        let queue = queue();
+        let nonce = queue.take_nonce(1, ALICE, 5).await;
+        queue.release_nonce(1, ALICE, nonce).await;
+        assert_eq!(queue.take_nonce(1, ALICE, 5).await, nonce);
+
+        // With a later nonce already out, the account is re-synced from the
+        // queue and the chain instead
+        let second = queue.take_nonce(1, ALICE, 5).await;
+        assert_eq!(second, 6);
+        queue.inner.lock().await.txs.push(queued(1, ALICE, second));
+        queue.release_nonce(1, ALICE, nonce).await;
+        assert_eq!(queue.take_nonce(1, ALICE, 5).await, 7);
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_saved_queue() {
+        let queue = queue();
+        queue.inner.lock().await.txs.extend([queued(1, ALICE, 7), queued(1, ALICE, 8), queued(5, ALICE, 20)]);
+        assert_eq!(queue.take_nonce(1, ALICE, 7).await, 9);
+
+        queue.resync().await;
+        assert_eq!(queue.take_nonce(1, ALICE, 12).await, 12);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_sends_get_distinct_nonces() {
+        let queue = Arc::new(queue());
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.take_nonce(1, ALICE, 3).await })
+            })
+            .collect();
+
+        let mut nonces = Vec::new();
+        for task in tasks {
+            nonces.push(task.await.unwrap());
+        }
+        nonces.sort();
+        assert_eq!(nonces, (3..35).collect::<Vec<u64>>());
+    }
+}
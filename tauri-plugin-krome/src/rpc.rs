@@ -0,0 +1,338 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime, WebviewWindow};
+
+use alloy::primitives::{Address, Bytes, B256, U256, U64};
+use alloy::rpc::types::trace::geth::{GethDebugBuiltInTracerType, GethDebugTracerType, GethDebugTracingCallOptions};
+use alloy::rpc::types::{Filter, TransactionRequest};
+use helios::core::types::BlockTag;
+
+use crate::approvals::{ApprovalKind, Connections, Origin};
+use crate::error::KromeError;
+use crate::eth::{self, BlockParam};
+use crate::fork;
+use crate::queue::TxQueue;
+use crate::signing::{self, message};
+use crate::simulation;
+use crate::subscriptions::{SubscriptionKind, Subscriptions};
+use crate::trace::{self, TraceOptions};
+use crate::transactions;
+use crate::wallet::WalletState;
+use crate::KromeExt;
+
+// EIP-1193 bridge: the webview sends `{ method, params }` and gets back the
+// JSON-RPC result, or an error shaped like a ProviderRpcError, so viem, ethers
+// and tevm can use the light client as their transport.
+
+// Error codes from EIP-1193 and JSON-RPC 2.0
+pub const USER_REJECTED: i64 = 4001;
+pub const UNAUTHORIZED: i64 = 4100;
+pub const UNSUPPORTED_METHOD: i64 = 4200;
+pub const DISCONNECTED: i64 = 4900;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+// Used by geth and others for reverts, with the revert data in `data`
+pub const EXECUTION_REVERTED: i64 = 3;
+
+// Request as sent by an EIP-1193 provider
+#[derive(Clone, Debug, Deserialize)]
+pub struct RpcRequest {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Clone, Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+pub type RpcResult<T> = std::result::Result<T, RpcError>;
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    pub fn unsupported(method: &str) -> Self {
+        RpcError::new(UNSUPPORTED_METHOD, format!("the method {} is not supported", method))
+    }
+}
+
+// Keeps the Krome error in `data` so the front end can still tell them apart.
+// Reverts carry the revert data instead, as viem and ethers expect.
+impl From<KromeError> for RpcError {
+    fn from(e: KromeError) -> Self {
+        if let KromeError::ExecutionReverted { data, .. } = &e {
+            return RpcError {
+                code: EXECUTION_REVERTED,
+                message: e.to_string(),
+                data: Some(json!(data)),
+            };
+        }
+
+        let code = match e {
+            KromeError::NotStarted | KromeError::NotSynced => DISCONNECTED,
+            KromeError::InvalidParams(_) => INVALID_PARAMS,
+            KromeError::UserRejected => USER_REJECTED,
+            // The dapp asked for an account the user hasn't made available
+            KromeError::AccountLocked(_) | KromeError::AccountNotFound(_) | KromeError::Unauthorized(_) => UNAUTHORIZED,
+            _ => INTERNAL_ERROR,
+        };
+        RpcError {
+            code,
+            message: e.to_string(),
+            data: serde_json::to_value(&e).ok(),
+        }
+    }
+}
+
+impl From<eyre::Report> for RpcError {
+    fn from(e: eyre::Report) -> Self {
+        KromeError::from(e).into()
+    }
+}
+
+impl From<serde_json::Error> for RpcError {
+    fn from(e: serde_json::Error) -> Self {
+        KromeError::from(e).into()
+    }
+}
+
+// Positional params of a request. Missing trailing params read as null, which
+// lets optional ones be left out.
+pub struct Params(Vec<Value>);
+
+impl Params {
+    pub fn new(params: Value) -> RpcResult<Self> {
+        match params {
+            Value::Null => Ok(Params(Vec::new())),
+            Value::Array(values) => Ok(Params(values)),
+            _ => Err(RpcError::invalid_params("params must be an array")),
+        }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, index: usize) -> RpcResult<T> {
+        let value = self.0.get(index).cloned().unwrap_or(Value::Null);
+        serde_json::from_value(value)
+            .map_err(|e| RpcError::invalid_params(format!("param {}: {}", index, e)))
+    }
+
+    pub fn optional<T: DeserializeOwned>(&self, index: usize) -> RpcResult<Option<T>> {
+        self.get(index)
+    }
+
+    pub fn block(&self, index: usize) -> RpcResult<BlockTag> {
+        let block: Option<BlockParam> = self.optional(index)?;
+        Ok(block.map(BlockParam::into_tag).transpose()?.unwrap_or(BlockTag::Latest))
+    }
+
+    // A quantity given either as hex or as a plain number
+    pub fn quantity(&self, index: usize) -> RpcResult<u64> {
+        match self.get::<Value>(index)? {
+            Value::Number(n) => n
+                .as_u64()
+                .ok_or_else(|| RpcError::invalid_params(format!("param {}: expected a quantity", index))),
+            Value::String(s) => Ok(eth::parse_quantity(&s)?),
+            _ => Err(RpcError::invalid_params(format!("param {}: expected a quantity", index))),
+        }
+    }
+
+    pub fn optional_quantity(&self, index: usize) -> RpcResult<Option<u64>> {
+        match self.get::<Value>(index)? {
+            Value::Null => Ok(None),
+            _ => self.quantity(index).map(Some),
+        }
+    }
+}
+
+// Runs one request against the light client, or the local fork while one is
+// running. `origin` is the webview it came from, which subscription
+// notifications are sent to and approval prompts name.
+pub async fn dispatch<R: Runtime>(app_handle: &AppHandle<R>, origin: &Origin, request: RpcRequest) -> RpcResult<Value> {
+    let params = Params::new(request.params)?;
+    if let Some(result) = fork::dispatch(app_handle, origin, &request.method, &params).await? {
+        return Ok(result);
+    }
+    let client = app_handle.krome().client().await?;
+
+    let result = match request.method.as_str() {
+        "eth_chainId" => json!(U64::from(client.chain_id().await)),
+        "net_version" => json!(client.chain_id().await.to_string()),
+        "eth_blockNumber" => json!(U64::from(client.get_block_number().await?.to::<u64>())),
+        // Requests are only served once the client has synced
+        "eth_syncing" => json!(false),
+        // Only what the user let this origin see through eth_requestAccounts
+        "eth_accounts" => {
+            let wallet = app_handle.state::<WalletState>();
+            json!(app_handle.state::<Connections>().accounts(origin, &wallet))
+        }
+        "eth_requestAccounts" => json!(app_handle.state::<Connections>().request(app_handle, origin).await?),
+        "web3_clientVersion" => json!(format!("krome/{}", env!("CARGO_PKG_VERSION"))),
+        "eth_getBalance" => {
+            let address: Address = params.get(0)?;
+            json!(client.get_balance(address, params.block(1)?).await?)
+        }
+        "eth_getTransactionCount" => {
+            let address: Address = params.get(0)?;
+            json!(U64::from(client.get_nonce(address, params.block(1)?).await?))
+        }
+        "eth_getCode" => {
+            let address: Address = params.get(0)?;
+            json!(client.get_code(address, params.block(1)?).await?)
+        }
+        "eth_getStorageAt" => {
+            let address: Address = params.get(0)?;
+            let slot: U256 = params.get(1)?;
+            let value = client
+                .get_storage_at(address, B256::from(slot), params.block(2)?)
+                .await?;
+            json!(B256::from(value))
+        }
+        "eth_getBlockByNumber" => {
+            let full_txs: Option<bool> = params.optional(1)?;
+            json!(client.get_block_by_number(params.block(0)?, full_txs.unwrap_or(false)).await?)
+        }
+        "eth_getBlockByHash" => {
+            let hash: B256 = params.get(0)?;
+            let full_txs: Option<bool> = params.optional(1)?;
+            json!(client.get_block_by_hash(hash, full_txs.unwrap_or(false)).await?)
+        }
+        "eth_getBlockReceipts" => json!(client.get_block_receipts(params.block(0)?).await?),
+        "eth_getTransactionByHash" => {
+            let hash: B256 = params.get(0)?;
+            json!(client.get_transaction_by_hash(hash).await)
+        }
+        "eth_getTransactionReceipt" => {
+            let hash: B256 = params.get(0)?;
+            json!(client.get_transaction_receipt(hash).await?)
+        }
+        "eth_getLogs" => {
+            let filter: Filter = params.get(0)?;
+            json!(client.get_logs(&filter).await?)
+        }
+        "eth_call" => {
+            let tx: TransactionRequest = params.get(0)?;
+            json!(client.call(&tx, params.block(1)?).await?)
+        }
+        "eth_estimateGas" => {
+            let tx: TransactionRequest = params.get(0)?;
+            json!(U64::from(simulation::estimate_gas(&client, tx, params.block(1)?).await?))
+        }
+        // The default struct logger and callTracer, on top of the latest block
+        "debug_traceCall" => {
+            let tx: TransactionRequest = params.get(0)?;
+            if !matches!(params.block(1)?, BlockTag::Latest) {
+                return Err(RpcError::invalid_params("calls can only be traced on the latest block"));
+            }
+            let call_options: Option<GethDebugTracingCallOptions> = params.optional(2)?;
+            let call_options = call_options.unwrap_or_default();
+            if call_options.state_overrides.is_some() || call_options.block_overrides.is_some() {
+                return Err(RpcError::invalid_params("state and block overrides are not supported"));
+            }
+            let tracing = call_options.tracing_options;
+            match tracing.tracer {
+                None => {
+                    let options = TraceOptions {
+                        with_logs: false,
+                        struct_logs: Some(tracing.config),
+                    };
+                    json!(trace::trace(&client, tx, options).await?.struct_logs)
+                }
+                Some(GethDebugTracerType::BuiltInTracer(GethDebugBuiltInTracerType::CallTracer)) => {
+                    let config = tracing.tracer_config.into_call_config()?;
+                    let options = TraceOptions {
+                        with_logs: config.with_log.unwrap_or(false),
+                        struct_logs: None,
+                    };
+                    json!(trace::trace(&client, tx, options).await?.call)
+                }
+                Some(_) => return Err(RpcError::invalid_params("only callTracer and the struct logger are supported")),
+            }
+        }
+        "eth_sendRawTransaction" => {
+            let raw: Bytes = params.get(0)?;
+            json!(transactions::send_raw(app_handle, &client, &raw, None).await?)
+        }
+        "eth_gasPrice" => json!(client.get_gas_price().await?),
+        "eth_maxPriorityFeePerGas" => json!(client.get_priority_fee().await?),
+        "eth_feeHistory" => {
+            let percentiles: Option<Vec<f64>> = params.optional(2)?;
+            json!(eth::build_fee_history(&client, params.quantity(0)?, params.block(1)?, percentiles).await?)
+        }
+        "eth_sendTransaction" => {
+            let tx: TransactionRequest = params.get(0)?;
+            let wallet = app_handle.state::<WalletState>();
+            let queued = app_handle
+                .state::<TxQueue>()
+                .send(app_handle, &client, &wallet, origin.clone(), tx, None)
+                .await?;
+            json!(queued.hash)
+        }
+        "eth_signTransaction" => {
+            let tx: TransactionRequest = params.get(0)?;
+            let wallet = app_handle.state::<WalletState>();
+            let approval = |tx, simulation| ApprovalKind::SignTransaction { tx, simulation };
+            let signed = signing::sign_request(app_handle, &client, &wallet, origin.clone(), tx, approval).await?;
+            json!(signed.raw)
+        }
+        "personal_sign" => {
+            let data: String = params.get(0)?;
+            let address: Address = params.get(1)?;
+            let wallet = app_handle.state::<WalletState>();
+            let message = message::message_bytes(&data);
+            json!(signing::sign_message_request(app_handle, &wallet, origin.clone(), address, message).await?)
+        }
+        "eth_signTypedData_v4" => {
+            let address: Address = params.get(0)?;
+            let typed_data: Value = params.get(1)?;
+            let wallet = app_handle.state::<WalletState>();
+            let chain_id = Some(client.chain_id().await);
+            json!(
+                signing::sign_typed_data_request(app_handle, &wallet, origin.clone(), address, typed_data, chain_id)
+                    .await?
+            )
+        }
+        "personal_ecRecover" => {
+            let data: String = params.get(0)?;
+            let signature: Bytes = params.get(1)?;
+            json!(message::recover_message_signer(&message::message_bytes(&data), &signature)?)
+        }
+        "eth_subscribe" => {
+            let kind = match params.get::<String>(0)?.as_str() {
+                "newHeads" => SubscriptionKind::NewHeads,
+                "logs" => SubscriptionKind::Logs(params.optional::<Filter>(1)?.unwrap_or_default()),
+                other => return Err(RpcError::invalid_params(format!("unsupported subscription: {}", other))),
+            };
+            json!(app_handle.state::<Subscriptions>().subscribe(kind, origin))
+        }
+        "eth_unsubscribe" => {
+            let id: String = params.get(0)?;
+            json!(app_handle.state::<Subscriptions>().unsubscribe(origin, &id))
+        }
+        method => return Err(RpcError::unsupported(method)),
+    };
+    Ok(result)
+}
+
+#[tauri::command]
+pub(crate) async fn eth_request<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    request: RpcRequest,
+) -> RpcResult<Value> {
+    dispatch(&app_handle, &Origin::of(&window), request).await
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use alloy::hex;
+
+use crate::config::write_atomic;
+use crate::error::{KromeError, Result};
+use super::SecretStore;
+
+// Fallback for platforms without a usable keychain. Each secret is sealed with
+// AES-256-GCM under a random key kept in a separate file readable only by the
+// user. That keeps secrets out of plain sight and out of krome.json, but
+// anyone who can read both files can decrypt them.
+
+const SECRETS_FILE: &str = "secrets.json";
+const KEY_FILE: &str = "secrets.key";
+const NONCE_LEN: usize = 12;
+
+#[derive(Default, Deserialize, Serialize)]
+struct SecretsFile {
+    // Hex of nonce followed by ciphertext, by key
+    entries: BTreeMap<String, String>,
+}
+
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    cipher: Aes256Gcm,
+    // Serializes read-modify-write cycles on the file
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    // Opens the store in `dir`, creating its key on first use
+    pub fn open(dir: &Path) -> Result<Self> {
+        let key_path = dir.join(KEY_FILE);
+        let key = if key_path.exists() {
+            let key = Zeroizing::new(fs::read(&key_path)?);
+            if key.len() != 32 {
+                return Err(KromeError::SecretStore(format!("{} is corrupt", key_path.display())));
+            }
+            key
+        } else {
+            let key = Zeroizing::new(Aes256Gcm::generate_key(&mut OsRng).to_vec());
+            write_private(&key_path, &key)?;
+            key
+        };
+        Ok(Self::with_key(dir, &key))
+    }
+
+    // Uses a 32-byte key the caller manages, e.g. one derived from a user password
+    pub fn with_key(dir: &Path, key: &[u8]) -> Self {
+        EncryptedFileStore {
+            path: dir.join(SECRETS_FILE),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Result<SecretsFile> {
+        if !self.path.exists() {
+            return Ok(SecretsFile::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+
+    fn write(&self, file: &SecretsFile) -> Result<()> {
+        write_atomic(&self.path, &serde_json::to_string_pretty(file)?)
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn backend(&self) -> &'static str {
+        "file"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let _guard = self.lock.lock().unwrap();
+        let Some(sealed) = self.read()?.entries.remove(key) else {
+            return Ok(None);
+        };
+        let sealed = hex::decode(sealed).map_err(|e| KromeError::SecretStore(e.to_string()))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(KromeError::SecretStore(format!("entry {} is corrupt", key)));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        // The key name is bound in as associated data so entries can't be swapped
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: key.as_bytes() })
+            .map_err(|_| KromeError::SecretStore(format!("entry {} could not be decrypted", key)))?;
+        Ok(Some(Zeroizing::new(plaintext)))
+    }
+
+    fn set(&self, key: &str, secret: &[u8]) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: secret, aad: key.as_bytes() })
+            .map_err(|e| KromeError::SecretStore(e.to_string()))?;
+
+        let mut file = self.read()?;
+        file.entries
+            .insert(key.to_string(), hex::encode([nonce.as_slice(), &ciphertext].concat()));
+        self.write(&file)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut file = self.read()?;
+        if file.entries.remove(key).is_some() {
+            self.write(&file)?;
+        }
+        Ok(())
+    }
+
+    fn keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self
+            .read()?
+            .entries
+            .into_keys()
+            .filter(|key| key.starts_with(prefix))
+            .collect())
+    }
+}
+
+// Creates a file only the current user can read
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(path)?, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::TempDir;
+
+    #[test]
+    fn round_trips_across_reopens() {
+        let dir = TempDir::new("krome-secrets");
+        let store = EncryptedFileStore::open(&dir.0).unwrap();
+        store.set("wallet/keystore/a", b"first").unwrap();
+        store.set("config/rpc/1/execution/0", b"https://rpc.example/?apikey=secret").unwrap();
+        assert_eq!(store.get("wallet/keystore/a").unwrap().unwrap().as_slice(), b"first");
+        assert!(store.get("wallet/keystore/b").unwrap().is_none());
+
+        // The key file is reused, so a new instance reads the same entries
+        let store = EncryptedFileStore::open(&dir.0).unwrap();
+        assert_eq!(store.get("wallet/keystore/a").unwrap().unwrap().as_slice(), b"first");
+        assert_eq!(store.keys("wallet/").unwrap(), vec!["wallet/keystore/a".to_string()]);
+
+        store.set("wallet/keystore/a", b"second").unwrap();
+        assert_eq!(store.get("wallet/keystore/a").unwrap().unwrap().as_slice(), b"second");
+        store.delete("wallet/keystore/a").unwrap();
+        store.delete("wallet/keystore/a").unwrap();
+        assert!(store.get("wallet/keystore/a").unwrap().is_none());
+    }
+
+    #[test]
+    fn secrets_stay_out_of_the_file() {
+        let dir = TempDir::new("krome-secrets");
+        let store = EncryptedFileStore::open(&dir.0).unwrap();
+        store.set("config/rpc/1/execution/0", b"https://rpc.example/?apikey=secret").unwrap();
+        let contents = fs::read_to_string(dir.0.join(SECRETS_FILE)).unwrap();
+        assert!(!contents.contains("apikey"));
+    }
+
+    #[test]
+    fn wrong_key_fails_to_decrypt() {
+        let dir = TempDir::new("krome-secrets");
+        EncryptedFileStore::with_key(&dir.0, &[1; 32]).set("key", b"secret").unwrap();
+
+        let error = EncryptedFileStore::with_key(&dir.0, &[2; 32]).get("key").unwrap_err();
+        assert!(matches!(error, KromeError::SecretStore(_)));
+        assert_eq!(
+            EncryptedFileStore::with_key(&dir.0, &[1; 32]).get("key").unwrap().unwrap().as_slice(),
+            b"secret"
+        );
+    }
+
+    #[test]
+    fn swapped_entries_fail_to_decrypt() {
+        let dir = TempDir::new("krome-secrets");
+        let store = EncryptedFileStore::with_key(&dir.0, &[1; 32]);
+        store.set("a", b"for a").unwrap();
+        store.set("b", b"for b").unwrap();
+
+        let mut file = store.read().unwrap();
+        let a = file.entries["a"].clone();
+        file.entries.insert("b".to_string(), a);
+        store.write(&file).unwrap();
+        assert!(store.get("b").is_err());
+    }
+
+    #[test]
+    fn rejects_a_truncated_key_file() {
+        let dir = TempDir::new("krome-secrets");
+        fs::write(dir.0.join(KEY_FILE), [0u8; 16]).unwrap();
+        assert!(EncryptedFileStore::open(&dir.0).is_err());
+    }
+}
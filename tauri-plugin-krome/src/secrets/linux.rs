@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+use secret_service::blocking::{Collection, SecretService};
+use secret_service::EncryptionType;
+
+use crate::error::{KromeError, Result};
+use super::SecretStore;
+
+// Freedesktop Secret Service (GNOME Keyring, KWallet) over D-Bus. Each secret
+// is an item in the default collection tagged with the app's service name and
+// the key. A connection is opened per call since they can't be shared across
+// threads. zbus's blocking API drives its own runtime, which panics when
+// entered from one of Tauri's tokio threads, so each call runs on a thread
+// of its own.
+
+const SERVICE_ATTRIBUTE: &str = "service";
+const KEY_ATTRIBUTE: &str = "key";
+
+pub struct SecretServiceStore {
+    service: String,
+}
+
+impl SecretServiceStore {
+    // Fails if no Secret Service is running, so callers can fall back
+    pub fn connect(service: &str) -> Result<Self> {
+        let store = SecretServiceStore {
+            service: service.to_string(),
+        };
+        store.with_collection(|_| Ok(()))?;
+        Ok(store)
+    }
+
+    fn with_collection<T: Send>(&self, f: impl FnOnce(&Collection) -> Result<T> + Send) -> Result<T> {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let ss = SecretService::connect(EncryptionType::Dh)?;
+                    let collection = ss.get_default_collection()?;
+                    collection.ensure_unlocked()?;
+                    f(&collection)
+                })
+                .join()
+                .map_err(|_| KromeError::SecretStore("secret service call panicked".to_string()))?
+        })
+    }
+
+    fn attributes<'a>(&'a self, key: &'a str) -> HashMap<&'a str, &'a str> {
+        HashMap::from([(SERVICE_ATTRIBUTE, self.service.as_str()), (KEY_ATTRIBUTE, key)])
+    }
+}
+
+impl SecretStore for SecretServiceStore {
+    fn backend(&self) -> &'static str {
+        "secret-service"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        self.with_collection(|collection| {
+            match collection.search_items(self.attributes(key))?.first() {
+                Some(item) => Ok(Some(Zeroizing::new(item.get_secret()?))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn set(&self, key: &str, secret: &[u8]) -> Result<()> {
+        self.with_collection(|collection| {
+            let label = format!("{} ({})", self.service, key);
+            collection.create_item(&label, self.attributes(key), secret, true, "application/octet-stream")?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.with_collection(|collection| {
+            for item in collection.search_items(self.attributes(key))? {
+                item.delete()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn keys(&self, prefix: &str) -> Result<Vec<String>> {
+        self.with_collection(|collection| {
+            let items = collection.search_items(HashMap::from([(SERVICE_ATTRIBUTE, self.service.as_str())]))?;
+            let mut keys = Vec::new();
+            for item in items {
+                if let Some(key) = item.get_attributes()?.remove(KEY_ATTRIBUTE) {
+                    if key.starts_with(prefix) {
+                        keys.push(key);
+                    }
+                }
+            }
+            keys.sort();
+            Ok(keys)
+        })
+    }
+}
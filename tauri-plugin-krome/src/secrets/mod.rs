@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use zeroize::Zeroizing;
+
+use crate::error::{KromeError, Result};
+
+mod file;
+#[cfg(target_os = "linux")]
+mod linux;
+
+pub use file::EncryptedFileStore;
+#[cfg(target_os = "linux")]
+pub use linux::SecretServiceStore;
+
+// Wallet keys and RPC credentials are kept in a SecretStore rather than in
+// krome.json or loose files. Keys are `/`-separated names such as
+// `wallet/keystore/<address>`.
+pub trait SecretStore: Send + Sync {
+    // Short name of the backend, for diagnostics
+    fn backend(&self) -> &'static str;
+    fn get(&self, key: &str) -> Result<Option<Zeroizing<Vec<u8>>>>;
+    fn set(&self, key: &str, secret: &[u8]) -> Result<()>;
+    // Deleting a missing key is not an error
+    fn delete(&self, key: &str) -> Result<()>;
+    fn keys(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+pub type SharedSecretStore = Arc<dyn SecretStore>;
+
+// Which backend `open` picks, set as `secretStore` in the plugin config
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    // The OS keychain where there is one, the encrypted file otherwise
+    #[default]
+    Auto,
+    Keychain,
+    File,
+    // Nothing survives a restart; meant for tests
+    Memory,
+}
+
+// The backend `open` picked, for the front end to show
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStoreStatus {
+    pub backend: &'static str,
+    // Why `auto` fell back to the encrypted file. Secrets saved to the
+    // keychain on an earlier launch are missing until it's reachable again.
+    pub fallback_reason: Option<String>,
+}
+
+// Opens the configured backend. `service` namespaces keychain entries so apps
+// built on Krome don't see each other's secrets.
+pub fn open(backend: SecretBackend, service: &str, data_dir: &Path) -> Result<(SharedSecretStore, SecretStoreStatus)> {
+    let (store, fallback_reason) = match backend {
+        SecretBackend::Auto => match keychain(service) {
+            Ok(store) => (store, None),
+            Err(e) => (file_store(data_dir)?, Some(e.to_string())),
+        },
+        SecretBackend::Keychain => (keychain(service)?, None),
+        SecretBackend::File => (file_store(data_dir)?, None),
+        SecretBackend::Memory => (Arc::new(MemoryStore::default()) as SharedSecretStore, None),
+    };
+    let status = SecretStoreStatus {
+        backend: store.backend(),
+        fallback_reason,
+    };
+    Ok((store, status))
+}
+
+// Runs store access off the async runtime. Keychain calls can wait on D-Bus
+// or on the user answering an unlock prompt.
+pub async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| KromeError::SecretStore(e.to_string()))?
+}
+
+#[cfg(target_os = "linux")]
+fn keychain(service: &str) -> Result<SharedSecretStore> {
+    Ok(Arc::new(SecretServiceStore::connect(service)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn keychain(_service: &str) -> Result<SharedSecretStore> {
+    Err(KromeError::SecretStore("no keychain backend on this platform".to_string()))
+}
+
+fn file_store(data_dir: &Path) -> Result<SharedSecretStore> {
+    Ok(Arc::new(EncryptedFileStore::open(data_dir)?))
+}
+
+#[derive(Default)]
+pub struct MemoryStore(Mutex<BTreeMap<String, Zeroizing<Vec<u8>>>>);
+
+impl SecretStore for MemoryStore {
+    fn backend(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        Ok(self.0.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &[u8]) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Zeroizing::new(secret.to_vec()));
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+// Reads a secret that was stored as UTF-8
+pub fn get_string(store: &dyn SecretStore, key: &str) -> Result<Option<String>> {
+    store
+        .get(key)?
+        .map(|bytes| {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|_| KromeError::SecretStore(format!("{} is not valid UTF-8", key)))
+        })
+        .transpose()
+}
+
+#[tauri::command]
+pub(crate) async fn get_secret_store_status(status: State<'_, SecretStoreStatus>) -> Result<SecretStoreStatus> {
+    Ok(status.inner().clone())
+}
+
+// A fresh directory under the system temp dir, removed on drop. Shared by
+// the tests that need real files.
+#[cfg(test)]
+pub(crate) struct TempDir(pub std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new(prefix: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("{}-{}", prefix, alloy::hex::encode(rand::random::<[u8; 8]>())));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
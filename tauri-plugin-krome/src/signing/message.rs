@@ -0,0 +1,517 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use alloy::dyn_abi::TypedData;
+use alloy::hex;
+use alloy::primitives::{Address, Bytes, PrimitiveSignature, B256, I256, U256};
+use alloy::signers::local::PrivateKeySigner;
+use alloy::signers::SignerSync;
+
+use crate::error::{KromeError, Result};
+
+// EIP-191 personal messages and EIP-712 typed data. Signatures are 65 bytes,
+// r ‖ s ‖ v with v as 27 or 28, the way wallets return them.
+
+// Struct nesting deeper than this is cut off in summaries
+const MAX_SUMMARY_DEPTH: usize = 8;
+// Field names that hold an expiry timestamp in common permit and order formats
+const EXPIRY_FIELDS: [&str; 5] = ["deadline", "expiry", "expiration", "sigDeadline", "endTime"];
+const YEAR_SECS: u64 = 365 * 24 * 60 * 60;
+
+// Typed data laid out for an approval prompt
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedDataSummary {
+    pub primary_type: String,
+    pub domain: DomainSummary,
+    // Message fields in order, with nested structs and arrays flattened to
+    // paths like `details.token` or `tokens[1]`
+    pub fields: Vec<SummaryField>,
+    // Things worth a second look before signing
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainSummary {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub chain_id: Option<U256>,
+    pub verifying_contract: Option<Address>,
+    pub salt: Option<B256>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryField {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    // Addresses checksummed, integers in decimal
+    pub value: String,
+}
+
+// Just the parts of the JSON the summary walks; TypedData does the validation
+#[derive(Deserialize)]
+struct RawTypedData {
+    types: BTreeMap<String, Vec<RawField>>,
+    message: Value,
+}
+
+#[derive(Deserialize)]
+struct RawField {
+    name: String,
+    #[serde(rename = "type")]
+    type_name: String,
+}
+
+// personal_sign takes either 0x-prefixed hex or plain text
+pub fn message_bytes(message: &str) -> Vec<u8> {
+    match message.strip_prefix("0x").and_then(|digits| hex::decode(digits).ok()) {
+        Some(bytes) => bytes,
+        None => message.as_bytes().to_vec(),
+    }
+}
+
+// eth_signTypedData_v4 callers pass the typed data either as an object or as
+// a JSON string of one
+fn unwrap_json(value: Value) -> Result<Value> {
+    match value {
+        Value::String(json) => serde_json::from_str(&json)
+            .map_err(|e| KromeError::InvalidParams(format!("invalid typed data: {}", e))),
+        value => Ok(value),
+    }
+}
+
+pub fn parse_typed_data(value: Value) -> Result<TypedData> {
+    serde_json::from_value(unwrap_json(value)?)
+        .map_err(|e| KromeError::InvalidParams(format!("invalid typed data: {}", e)))
+}
+
+// The EIP-712 digest: keccak256(0x1901 ‖ domainSeparator ‖ hashStruct(message))
+pub fn typed_data_hash(typed_data: &TypedData) -> Result<B256> {
+    typed_data
+        .eip712_signing_hash()
+        .map_err(|e| KromeError::InvalidParams(format!("invalid typed data: {}", e)))
+}
+
+fn parse_signature(signature: &Bytes) -> Result<PrimitiveSignature> {
+    PrimitiveSignature::try_from(signature.as_ref())
+        .map_err(|e| KromeError::InvalidParams(format!("invalid signature: {}", e)))
+}
+
+// Signs with the "\x19Ethereum Signed Message:\n" prefix
+pub fn sign_message(signer: &PrivateKeySigner, message: &[u8]) -> Result<Bytes> {
+    let signature = signer
+        .sign_message_sync(message)
+        .map_err(|e| KromeError::Signing(e.to_string()))?;
+    Ok(signature.as_bytes().into())
+}
+
+pub fn sign_typed_data(signer: &PrivateKeySigner, typed_data: &TypedData) -> Result<Bytes> {
+    let signature = signer
+        .sign_hash_sync(&typed_data_hash(typed_data)?)
+        .map_err(|e| KromeError::Signing(e.to_string()))?;
+    Ok(signature.as_bytes().into())
+}
+
+pub fn recover_message_signer(message: &[u8], signature: &Bytes) -> Result<Address> {
+    parse_signature(signature)?
+        .recover_address_from_msg(message)
+        .map_err(|e| KromeError::InvalidParams(format!("invalid signature: {}", e)))
+}
+
+pub fn recover_typed_data_signer(typed_data: &TypedData, signature: &Bytes) -> Result<Address> {
+    parse_signature(signature)?
+        .recover_address_from_prehash(&typed_data_hash(typed_data)?)
+        .map_err(|e| KromeError::InvalidParams(format!("invalid signature: {}", e)))
+}
+
+// Fails if the domain names a chain other than `chain_id`, since a signature
+// for another chain is almost always a phishing attempt or a dapp bug
+pub fn check_chain(typed_data: &TypedData, chain_id: u64) -> Result<()> {
+    match typed_data.domain.chain_id {
+        Some(domain_chain) if domain_chain != U256::from(chain_id) => Err(KromeError::InvalidParams(format!(
+            "typed data is for chain {} but the client is on chain {}",
+            domain_chain, chain_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// Builds the approval prompt view of typed data. `chain_id` is the active
+// chain, if the client is running.
+pub fn summarize(value: Value, chain_id: Option<u64>) -> Result<TypedDataSummary> {
+    let value = unwrap_json(value)?;
+    let typed_data = parse_typed_data(value.clone())?;
+    typed_data_hash(&typed_data)?;
+    let raw: RawTypedData = serde_json::from_value(value)?;
+
+    let domain = &typed_data.domain;
+    let mut warnings = Vec::new();
+    if let (Some(domain_chain), Some(chain_id)) = (domain.chain_id, chain_id) {
+        if domain_chain != U256::from(chain_id) {
+            warnings.push(format!("signature is for chain {} but the client is on chain {}", domain_chain, chain_id));
+        }
+    }
+    if domain.verifying_contract.is_none() {
+        warnings.push("domain names no verifying contract".to_string());
+    }
+
+    let mut fields = Vec::new();
+    walk(&raw.types, &typed_data.primary_type, &raw.message, String::new(), 0, &mut fields);
+    warnings.extend(fields.iter().filter_map(field_warning));
+
+    Ok(TypedDataSummary {
+        primary_type: typed_data.primary_type.clone(),
+        domain: DomainSummary {
+            name: domain.name.as_ref().map(|name| name.to_string()),
+            version: domain.version.as_ref().map(|version| version.to_string()),
+            chain_id: domain.chain_id,
+            verifying_contract: domain.verifying_contract,
+            salt: domain.salt,
+        },
+        fields,
+        warnings,
+    })
+}
+
+fn walk(
+    types: &BTreeMap<String, Vec<RawField>>,
+    type_name: &str,
+    value: &Value,
+    path: String,
+    depth: usize,
+    out: &mut Vec<SummaryField>,
+) {
+    if depth > MAX_SUMMARY_DEPTH {
+        out.push(SummaryField {
+            path,
+            type_name: type_name.to_string(),
+            value: "…".to_string(),
+        });
+        return;
+    }
+
+    if let Some(element_type) = type_name.strip_suffix(']').and_then(|t| t.rsplit_once('[')).map(|(t, _)| t) {
+        for (i, item) in value.as_array().into_iter().flatten().enumerate() {
+            walk(types, element_type, item, format!("{}[{}]", path, i), depth + 1, out);
+        }
+    } else if let Some(fields) = types.get(type_name) {
+        for field in fields {
+            let path = if path.is_empty() {
+                field.name.clone()
+            } else {
+                format!("{}.{}", path, field.name)
+            };
+            let value = value.get(&field.name).unwrap_or(&Value::Null);
+            walk(types, &field.type_name, value, path, depth + 1, out);
+        }
+    } else {
+        out.push(SummaryField {
+            path,
+            type_name: type_name.to_string(),
+            value: format_value(type_name, value),
+        });
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn format_value(type_name: &str, value: &Value) -> String {
+    let text = as_text(value);
+    if type_name == "address" {
+        if let Ok(address) = text.parse::<Address>() {
+            return address.to_checksum(None);
+        }
+    } else if type_name.starts_with("uint") {
+        if let Ok(n) = text.parse::<U256>() {
+            return n.to_string();
+        }
+    } else if type_name.starts_with("int") {
+        if let Ok(n) = text.parse::<I256>() {
+            return n.to_string();
+        }
+    }
+    text
+}
+
+// Name of the field a path ends in, past any array indices, so `amounts[1]`
+// is named `amounts` and `details[0].amount` is named `amount`
+fn field_name(path: &str) -> &str {
+    let mut name = path;
+    while let Some((array, _)) = name.strip_suffix(']').and_then(|name| name.rsplit_once('[')) {
+        name = array;
+    }
+    name.rsplit('.').next().unwrap_or_default()
+}
+
+fn field_warning(field: &SummaryField) -> Option<String> {
+    if !field.type_name.starts_with("uint") {
+        return None;
+    }
+    let value = field.value.parse::<U256>().ok()?;
+
+    // Max uint256, or max uint160 as Permit2 uses
+    let unlimited = value == U256::MAX || value == U256::MAX >> 96;
+    let name = field_name(&field.path);
+    if unlimited && !EXPIRY_FIELDS.contains(&name) && name != "nonce" {
+        return Some(format!("{} is an unlimited amount", field.path));
+    }
+    if EXPIRY_FIELDS.contains(&name) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        if value > U256::from(now + YEAR_SECS) {
+            return Some(format!("{} is more than a year away", field.path));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, b256};
+    use serde_json::json;
+
+    // The example from EIP-712, signed by keccak256("cow")
+    fn mail() -> Value {
+        json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallet", "type": "address" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                "contents": "Hello, Bob!"
+            }
+        })
+    }
+
+    fn cow() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&b256!("c85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4")).unwrap()
+    }
+
+    #[test]
+    fn hashes_and_signs_the_eip712_example() {
+        let typed_data = parse_typed_data(mail()).unwrap();
+        assert_eq!(
+            typed_data_hash(&typed_data).unwrap(),
+            b256!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+
+        let signature = sign_typed_data(&cow(), &typed_data).unwrap();
+        assert_eq!(
+            hex::encode(&signature),
+            "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c"
+        );
+        assert_eq!(
+            recover_typed_data_signer(&typed_data, &signature).unwrap(),
+            address!("CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826")
+        );
+
+        // The same data passed as a JSON string, as some dapps do
+        let as_string = parse_typed_data(Value::String(mail().to_string())).unwrap();
+        assert_eq!(typed_data_hash(&as_string).unwrap(), typed_data_hash(&typed_data).unwrap());
+        assert!(check_chain(&typed_data, 1).is_ok());
+        assert!(matches!(check_chain(&typed_data, 5), Err(KromeError::InvalidParams(_))));
+    }
+
+    const MAX_UINT160: &str = "1461501637330902918203684832716283019655932542975";
+    // Far past a year from now
+    const FAR_FUTURE: u64 = 99_999_999_999;
+
+    // Typed data on mainnet for a made-up token, with these message types
+    fn typed_data(mut types: Value, primary_type: &str, message: Value) -> Value {
+        types["EIP712Domain"] = json!([
+            { "name": "name", "type": "string" },
+            { "name": "chainId", "type": "uint256" },
+            { "name": "verifyingContract", "type": "address" }
+        ]);
+        json!({
+            "types": types,
+            "primaryType": primary_type,
+            "domain": {
+                "name": "Token",
+                "chainId": 1,
+                "verifyingContract": "0x1111111111111111111111111111111111111111"
+            },
+            "message": message
+        })
+    }
+
+    fn paths(summary: &TypedDataSummary) -> Vec<&str> {
+        summary.fields.iter().map(|field| field.path.as_str()).collect()
+    }
+
+    #[test]
+    fn warns_about_unlimited_permits() {
+        let permit = typed_data(
+            json!({
+                "Permit": [
+                    { "name": "owner", "type": "address" },
+                    { "name": "spender", "type": "address" },
+                    { "name": "value", "type": "uint256" },
+                    { "name": "nonce", "type": "uint256" },
+                    { "name": "deadline", "type": "uint256" }
+                ]
+            }),
+            "Permit",
+            json!({
+                "owner": "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826",
+                "spender": "0x2222222222222222222222222222222222222222",
+                "value": U256::MAX.to_string(),
+                "nonce": 0,
+                "deadline": FAR_FUTURE
+            }),
+        );
+        let summary = summarize(permit, Some(1)).unwrap();
+        assert_eq!(summary.primary_type, "Permit");
+        assert_eq!(paths(&summary), vec!["owner", "spender", "value", "nonce", "deadline"]);
+        assert_eq!(summary.fields[0].value, "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826");
+        assert_eq!(summary.fields[2].value, U256::MAX.to_string());
+        assert_eq!(
+            summary.warnings,
+            vec!["value is an unlimited amount".to_string(), "deadline is more than a year away".to_string()]
+        );
+
+        // Typed data for another chain
+        let summary = summarize(mail(), Some(5)).unwrap();
+        assert_eq!(summary.warnings, vec!["signature is for chain 1 but the client is on chain 5".to_string()]);
+    }
+
+    #[test]
+    fn flattens_nested_structs() {
+        let permit = typed_data(
+            json!({
+                "PermitSingle": [
+                    { "name": "details", "type": "PermitDetails" },
+                    { "name": "spender", "type": "address" },
+                    { "name": "sigDeadline", "type": "uint256" }
+                ],
+                "PermitDetails": [
+                    { "name": "token", "type": "address" },
+                    { "name": "amount", "type": "uint160" },
+                    { "name": "expiration", "type": "uint48" },
+                    { "name": "nonce", "type": "uint48" }
+                ]
+            }),
+            "PermitSingle",
+            json!({
+                "details": {
+                    "token": "0x3333333333333333333333333333333333333333",
+                    "amount": MAX_UINT160,
+                    "expiration": 1,
+                    "nonce": 7
+                },
+                "spender": "0x2222222222222222222222222222222222222222",
+                "sigDeadline": 1
+            }),
+        );
+        let summary = summarize(permit, Some(1)).unwrap();
+        assert_eq!(
+            paths(&summary),
+            vec!["details.token", "details.amount", "details.expiration", "details.nonce", "spender", "sigDeadline"]
+        );
+        assert_eq!(summary.fields[3].value, "7");
+        assert_eq!(summary.warnings, vec!["details.amount is an unlimited amount".to_string()]);
+    }
+
+    #[test]
+    fn names_array_members_after_their_field() {
+        let order = typed_data(
+            json!({
+                "Order": [
+                    { "name": "tokens", "type": "address[]" },
+                    { "name": "amounts", "type": "uint256[]" },
+                    { "name": "nonce", "type": "uint256[]" },
+                    { "name": "expiry", "type": "uint256[]" },
+                    { "name": "legs", "type": "Leg[]" }
+                ],
+                "Leg": [
+                    { "name": "amount", "type": "uint256" },
+                    { "name": "deadline", "type": "uint256" }
+                ]
+            }),
+            "Order",
+            json!({
+                "tokens": ["0x3333333333333333333333333333333333333333"],
+                "amounts": ["1", U256::MAX.to_string()],
+                "nonce": [U256::MAX.to_string()],
+                "expiry": [FAR_FUTURE],
+                "legs": [{ "amount": "5", "deadline": 1 }, { "amount": U256::MAX.to_string(), "deadline": FAR_FUTURE }]
+            }),
+        );
+        let summary = summarize(order, Some(1)).unwrap();
+        assert_eq!(
+            paths(&summary),
+            vec![
+                "tokens[0]",
+                "amounts[0]",
+                "amounts[1]",
+                "nonce[0]",
+                "expiry[0]",
+                "legs[0].amount",
+                "legs[0].deadline",
+                "legs[1].amount",
+                "legs[1].deadline"
+            ]
+        );
+        assert_eq!(
+            summary.warnings,
+            vec![
+                "amounts[1] is an unlimited amount".to_string(),
+                "expiry[0] is more than a year away".to_string(),
+                "legs[1].amount is an unlimited amount".to_string(),
+                "legs[1].deadline is more than a year away".to_string(),
+            ]
+        );
+
+        assert_eq!(field_name("amounts[1]"), "amounts");
+        assert_eq!(field_name("grid[0][2]"), "grid");
+        assert_eq!(field_name("legs[0].deadline"), "deadline");
+        assert_eq!(field_name("nonce"), "nonce");
+    }
+
+    #[test]
+    fn signs_personal_messages() {
+        let signer = PrivateKeySigner::from_bytes(&B256::repeat_byte(0x46)).unwrap();
+        let message = message_bytes("hello world");
+        assert_eq!(message_bytes("0x68656c6c6f20776f726c64"), message);
+
+        let signature = sign_message(&signer, &message).unwrap();
+        assert_eq!(
+            hex::encode(&signature),
+            "78dc245805f4363bd546a771502385e03c40995b13fbab75de9258c6515db8d92e831df32c6898bc590d0fb69945a72f6e31f1a70a325bf047ff5d557b1542ff1b"
+        );
+        assert_eq!(recover_message_signer(&message, &signature).unwrap(), signer.address());
+        assert_ne!(recover_message_signer(b"hello", &signature).unwrap(), signer.address());
+    }
+}
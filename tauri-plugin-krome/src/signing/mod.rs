@@ -0,0 +1,462 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager, Runtime, State, WebviewWindow};
+
+use alloy::consensus::{SignableTransaction, Signed, Transaction as _, TxEnvelope, TypedTransaction};
+use alloy::eips::eip2718::Encodable2718;
+use alloy::primitives::{Address, Bytes, PrimitiveSignature, B256, U256};
+use alloy::rpc::types::TransactionRequest;
+use alloy::signers::local::PrivateKeySigner;
+use alloy::signers::SignerSync;
+use helios::core::types::BlockTag;
+
+use crate::approvals::{ApprovalBroker, ApprovalKind, Origin};
+use crate::error::{KromeError, Result};
+use crate::eth::build_fee_history;
+use crate::helios::{HeliosClient, HeliosState};
+use crate::queue::TxQueue;
+use crate::simulation::{self, Simulation};
+use crate::wallet::WalletState;
+use message::TypedDataSummary;
+
+pub mod message;
+
+// Builds, signs and encodes transactions so private keys never leave Rust.
+// Fields the caller leaves out are filled from the light client: the nonce
+// from the account's latest state, the chain ID from the running network,
+// fees from recent blocks and the gas limit from a local estimate.
+
+// Blocks of fee history the priority fee suggestion looks at
+const FEE_HISTORY_BLOCKS: u64 = 10;
+const PRIORITY_FEE_PERCENTILE: f64 = 50.0;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedTransaction {
+    pub hash: B256,
+    // EIP-2718 encoding, ready for eth_sendRawTransaction
+    pub raw: Bytes,
+    pub from: Address,
+    pub nonce: u64,
+    #[serde(rename = "type")]
+    pub tx_type: u8,
+    // The transaction as signed, with every field filled
+    pub tx: TransactionRequest,
+}
+
+// Fills in everything `tx` leaves out and picks its type: legacy when a gas
+// price is given, EIP-2930 when an access list comes with it, EIP-1559
+// otherwise. An explicit `type` is honoured.
+pub async fn fill(client: &HeliosClient, from: Address, mut tx: TransactionRequest) -> Result<TypedTransaction> {
+    if tx.authorization_list.is_some() || tx.sidecar.is_some() || tx.max_fee_per_blob_gas.is_some() {
+        return Err(KromeError::InvalidParams(
+            "only legacy, EIP-2930 and EIP-1559 transactions can be signed".to_string(),
+        ));
+    }
+    tx.from = Some(from);
+
+    let chain_id = client.chain_id().await;
+    match tx.chain_id {
+        Some(id) if id != chain_id => {
+            return Err(KromeError::InvalidParams(format!(
+                "transaction is for chain {} but the client is on chain {}",
+                id, chain_id
+            )))
+        }
+        _ => tx.chain_id = Some(chain_id),
+    }
+    if tx.nonce.is_none() {
+        tx.nonce = Some(client.get_nonce(from, BlockTag::Latest).await?);
+    }
+
+    let has_1559_fees = tx.max_fee_per_gas.is_some() || tx.max_priority_fee_per_gas.is_some();
+    let legacy_fees = match tx.transaction_type {
+        Some(0) if tx.access_list.is_some() => {
+            return Err(KromeError::InvalidParams("legacy transactions can't have an access list".to_string()))
+        }
+        Some(0 | 1) => true,
+        Some(2) => false,
+        Some(other) => {
+            return Err(KromeError::InvalidParams(format!("unsupported transaction type {}", other)))
+        }
+        None => tx.gas_price.is_some() && !has_1559_fees,
+    };
+    if legacy_fees {
+        if has_1559_fees {
+            return Err(KromeError::InvalidParams(
+                "legacy and EIP-2930 transactions take gasPrice, not EIP-1559 fees".to_string(),
+            ));
+        }
+        if tx.transaction_type == Some(1) {
+            tx.access_list.get_or_insert_with(Default::default);
+        }
+        if tx.gas_price.is_none() {
+            tx.gas_price = Some(client.get_gas_price().await?.saturating_to());
+        }
+    } else {
+        if tx.gas_price.is_some() {
+            return Err(KromeError::InvalidParams(
+                "EIP-1559 transactions take maxFeePerGas and maxPriorityFeePerGas, not gasPrice".to_string(),
+            ));
+        }
+        fill_eip1559_fees(client, &mut tx).await?;
+    }
+
+    if tx.gas.is_none() {
+        tx.gas = Some(client.estimate_gas(&tx).await?);
+    }
+
+    let expected_type = tx.transaction_type;
+    let typed = tx
+        .build_typed_tx()
+        .map_err(|_| KromeError::InvalidParams("transaction is missing required fields".to_string()))?;
+    if let Some(expected) = expected_type {
+        if expected != typed.tx_type() as u8 {
+            return Err(KromeError::InvalidParams(format!("fields don't match transaction type {}", expected)));
+        }
+    }
+    Ok(typed)
+}
+
+// Suggests a priority fee from the median tip paid over recent blocks and a
+// max fee that leaves room for the base fee to double
+pub(crate) async fn fill_eip1559_fees(client: &HeliosClient, tx: &mut TransactionRequest) -> Result<()> {
+    if tx.max_fee_per_gas.is_some() && tx.max_priority_fee_per_gas.is_some() {
+        return Ok(());
+    }
+
+    let history = build_fee_history(
+        client,
+        FEE_HISTORY_BLOCKS,
+        BlockTag::Latest,
+        Some(vec![PRIORITY_FEE_PERCENTILE]),
+    )
+    .await?;
+    let base_fee = history.base_fee_per_gas.last().copied().unwrap_or_default();
+    let mut tips: Vec<U256> = history
+        .reward
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|reward| reward.first().copied())
+        .filter(|tip| !tip.is_zero())
+        .collect();
+    tips.sort();
+    let suggested_tip = match tips.get(tips.len() / 2) {
+        Some(tip) => *tip,
+        None => client.get_priority_fee().await?,
+    };
+
+    let tip = match (tx.max_priority_fee_per_gas, tx.max_fee_per_gas) {
+        (Some(tip), _) => U256::from(tip),
+        // Never tip more than the max fee allows
+        (None, Some(max_fee)) => suggested_tip.min(U256::from(max_fee)),
+        (None, None) => suggested_tip,
+    };
+    tx.max_priority_fee_per_gas = Some(tip.saturating_to());
+    if tx.max_fee_per_gas.is_none() {
+        tx.max_fee_per_gas = Some((base_fee.saturating_mul(U256::from(2)).saturating_add(tip)).saturating_to());
+    }
+    Ok(())
+}
+
+fn sign_with<T: SignableTransaction<PrimitiveSignature>>(signer: &PrivateKeySigner, tx: T) -> Result<Signed<T>> {
+    let signature = signer
+        .sign_hash_sync(&tx.signature_hash())
+        .map_err(|e| KromeError::Signing(e.to_string()))?;
+    Ok(tx.into_signed(signature))
+}
+
+pub fn sign(signer: &PrivateKeySigner, tx: TypedTransaction) -> Result<SignedTransaction> {
+    let mut request: TransactionRequest = tx.clone().into();
+    request.from = Some(signer.address());
+    let envelope: TxEnvelope = match tx {
+        TypedTransaction::Legacy(tx) => sign_with(signer, tx)?.into(),
+        TypedTransaction::Eip2930(tx) => sign_with(signer, tx)?.into(),
+        TypedTransaction::Eip1559(tx) => sign_with(signer, tx)?.into(),
+        _ => {
+            return Err(KromeError::InvalidParams(
+                "only legacy, EIP-2930 and EIP-1559 transactions can be signed".to_string(),
+            ))
+        }
+    };
+    Ok(SignedTransaction {
+        hash: *envelope.tx_hash(),
+        raw: envelope.encoded_2718().into(),
+        from: signer.address(),
+        nonce: envelope.nonce(),
+        tx_type: envelope.tx_type() as u8,
+        tx: request,
+    })
+}
+
+// Fills `tx`, has the user approve it as filled along with a simulation of
+// it, then signs with the unlocked account in `tx.from`. `approval` says what
+// the user is asked.
+pub async fn sign_request<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    client: &HeliosClient,
+    wallet: &WalletState,
+    origin: Origin,
+    tx: TransactionRequest,
+    approval: impl FnOnce(TransactionRequest, Option<Simulation>) -> ApprovalKind,
+) -> Result<SignedTransaction> {
+    let from = tx
+        .from
+        .ok_or_else(|| KromeError::InvalidParams("transaction has no from address".to_string()))?;
+    let typed = approve_request(app_handle, client, wallet, origin, tx, approval).await?;
+    sign(&wallet.signer(from)?, typed)
+}
+
+// The part of sign_request up to the user's approval. Returns the filled
+// transaction as approved, for callers that still change the nonce before
+// signing it.
+pub async fn approve_request<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    client: &HeliosClient,
+    wallet: &WalletState,
+    origin: Origin,
+    tx: TransactionRequest,
+    approval: impl FnOnce(TransactionRequest, Option<Simulation>) -> ApprovalKind,
+) -> Result<TypedTransaction> {
+    let from = tx
+        .from
+        .ok_or_else(|| KromeError::InvalidParams("transaction has no from address".to_string()))?;
+    // Fail before any RPC work if the account isn't unlocked
+    wallet.signer(from)?;
+    let typed = fill(client, from, tx).await?;
+
+    let simulation = simulation::simulate_typed(client, from, &typed).await.ok();
+
+    let mut filled: TransactionRequest = typed.clone().into();
+    filled.from = Some(from);
+    app_handle
+        .state::<ApprovalBroker>()
+        .request(app_handle, origin, approval(filled, simulation))
+        .await?;
+    Ok(typed)
+}
+
+// Moves a filled transaction to another nonce. Only the types `sign` takes
+// have one to move.
+pub(crate) fn set_nonce(tx: &mut TypedTransaction, nonce: u64) {
+    match tx {
+        TypedTransaction::Legacy(tx) => tx.nonce = nonce,
+        TypedTransaction::Eip2930(tx) => tx.nonce = nonce,
+        TypedTransaction::Eip1559(tx) => tx.nonce = nonce,
+        _ => {}
+    }
+}
+
+// EIP-191 signature over `message`, once the user approves it
+pub async fn sign_message_request<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    wallet: &WalletState,
+    origin: Origin,
+    address: Address,
+    message: Vec<u8>,
+) -> Result<Bytes> {
+    wallet.signer(address)?;
+    let kind = ApprovalKind::PersonalSign {
+        address,
+        text: String::from_utf8(message.clone()).ok(),
+        message: message.clone().into(),
+    };
+    app_handle.state::<ApprovalBroker>().request(app_handle, origin, kind).await?;
+    message::sign_message(&wallet.signer(address)?, &message)
+}
+
+// EIP-712 signature, once the user approves the summary. Typed data for a
+// chain other than `chain_id` is refused.
+pub async fn sign_typed_data_request<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    wallet: &WalletState,
+    origin: Origin,
+    address: Address,
+    typed_data: Value,
+    chain_id: Option<u64>,
+) -> Result<Bytes> {
+    let parsed = message::parse_typed_data(typed_data.clone())?;
+    if let Some(chain_id) = chain_id {
+        message::check_chain(&parsed, chain_id)?;
+    }
+    wallet.signer(address)?;
+    let kind = ApprovalKind::SignTypedData {
+        address,
+        summary: message::summarize(typed_data.clone(), chain_id)?,
+        typed_data,
+    };
+    app_handle.state::<ApprovalBroker>().request(app_handle, origin, kind).await?;
+    message::sign_typed_data(&wallet.signer(address)?, &parsed)
+}
+
+// Returns the signed transaction without broadcasting it
+#[tauri::command]
+pub(crate) async fn sign_transaction<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    state: State<'_, HeliosState>,
+    wallet: State<'_, WalletState>,
+    tx: TransactionRequest,
+) -> Result<SignedTransaction> {
+    let client = state.client().await?;
+    sign_request(&app_handle, &client, &wallet, Origin::of(&window), tx, |tx, simulation| {
+        ApprovalKind::SignTransaction { tx, simulation }
+    })
+    .await
+}
+
+// Signs at the next nonce the queue hands out, then broadcasts and tracks
+// the transaction like send_raw_transaction
+#[tauri::command]
+pub(crate) async fn send_transaction<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    state: State<'_, HeliosState>,
+    wallet: State<'_, WalletState>,
+    queue: State<'_, TxQueue>,
+    tx: TransactionRequest,
+    confirmations: Option<u64>,
+) -> Result<B256> {
+    let client = state.client().await?;
+    let queued = queue
+        .send(&app_handle, &client, &wallet, Origin::of(&window), tx, confirmations)
+        .await?;
+    Ok(queued.hash)
+}
+
+// EIP-191 signature over `message`, which is 0x-prefixed hex or plain text
+#[tauri::command]
+pub(crate) async fn personal_sign<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    wallet: State<'_, WalletState>,
+    address: Address,
+    message: String,
+) -> Result<Bytes> {
+    let message = message::message_bytes(&message);
+    sign_message_request(&app_handle, &wallet, Origin::of(&window), address, message).await
+}
+
+// EIP-712 signature, as eth_signTypedData_v4 returns. Typed data for a chain
+// other than the running one is refused.
+#[tauri::command]
+pub(crate) async fn sign_typed_data<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    state: State<'_, HeliosState>,
+    wallet: State<'_, WalletState>,
+    address: Address,
+    typed_data: Value,
+) -> Result<Bytes> {
+    let chain_id = match state.client().await {
+        Ok(client) => Some(client.chain_id().await),
+        Err(_) => None,
+    };
+    sign_typed_data_request(&app_handle, &wallet, Origin::of(&window), address, typed_data, chain_id).await
+}
+
+#[tauri::command]
+pub(crate) async fn recover_message_signer(message: String, signature: Bytes) -> Result<Address> {
+    message::recover_message_signer(&message::message_bytes(&message), &signature)
+}
+
+#[tauri::command]
+pub(crate) async fn recover_typed_data_signer(typed_data: Value, signature: Bytes) -> Result<Address> {
+    message::recover_typed_data_signer(&message::parse_typed_data(typed_data)?, &signature)
+}
+
+#[tauri::command]
+pub(crate) async fn summarize_typed_data(state: State<'_, HeliosState>, typed_data: Value) -> Result<TypedDataSummary> {
+    let chain_id = match state.client().await {
+        Ok(client) => Some(client.chain_id().await),
+        Err(_) => None,
+    };
+    message::summarize(typed_data, chain_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus::{TxEip1559, TxEip2930, TxLegacy};
+    use alloy::eips::eip2930::{AccessList, AccessListItem};
+    use alloy::hex;
+    use alloy::primitives::{address, b256, TxKind};
+
+    // The key and recipient from the EIP-155 example
+    fn signer() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&B256::repeat_byte(0x46)).unwrap()
+    }
+
+    const TO: Address = address!("3535353535353535353535353535353535353535");
+
+    fn ether() -> U256 {
+        U256::from(1_000_000_000_000_000_000u128)
+    }
+
+    #[test]
+    fn signs_legacy_transactions_with_eip155() {
+        let tx = TxLegacy {
+            chain_id: Some(1),
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: TxKind::Call(TO),
+            value: ether(),
+            ..Default::default()
+        };
+        let signed = sign(&signer(), TypedTransaction::Legacy(tx)).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(signed.hash, b256!("33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"));
+        assert_eq!(signed.from, address!("9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"));
+        assert_eq!((signed.tx_type, signed.nonce), (0, 9));
+    }
+
+    #[test]
+    fn signs_eip2930_transactions() {
+        let tx = TxEip2930 {
+            chain_id: 1,
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 30_000,
+            to: TxKind::Call(TO),
+            value: ether(),
+            access_list: AccessList(vec![AccessListItem {
+                address: TO,
+                storage_keys: vec![B256::with_last_byte(1)],
+            }]),
+            ..Default::default()
+        };
+        let signed = sign(&signer(), TypedTransaction::Eip2930(tx)).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "01f8a701098504a817c800827530943535353535353535353535353535353535353535880de0b6b3a764000080f838f7943535353535353535353535353535353535353535e1a0000000000000000000000000000000000000000000000000000000000000000180a0b5e47cb4dfd887b1a53276a7c0678f75671ed249e17742c84331694fdf893e2aa0388f7991161bec99e8e8287a4465ec396ded0ee5ab83f980e4b82bc4c28838b4"
+        );
+        assert_eq!(signed.hash, b256!("9e57286688ee3455f1001b2654685aa223212aca7aaacec1122b0204f6d3cd64"));
+        assert_eq!(signed.tx_type, 1);
+    }
+
+    #[test]
+    fn signs_eip1559_transactions() {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce: 9,
+            gas_limit: 21_000,
+            max_fee_per_gas: 50_000_000_000,
+            max_priority_fee_per_gas: 2_000_000_000,
+            to: TxKind::Call(TO),
+            value: ether(),
+            ..Default::default()
+        };
+        let signed = sign(&signer(), TypedTransaction::Eip1559(tx)).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "02f87301098477359400850ba43b7400825208943535353535353535353535353535353535353535880de0b6b3a764000080c080a09f7c62ac22c53bbce926f6347672672a94b66cae19bb6db8087505365d12be84a06f7b882105463218f6c7af16c766d4b3dc64be310f922651db38c5d593452187"
+        );
+        assert_eq!(signed.hash, b256!("4e880b4ac3cf811337cca9b1d76a0bb2c9eda9816529a2b10bb4899d3efa2761"));
+        assert_eq!(signed.tx_type, 2);
+        assert_eq!(signed.tx.from, Some(signed.from));
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use tauri::State;
+use tokio::runtime::Handle;
+
+use alloy::consensus::{Transaction as _, TypedTransaction};
+use alloy::primitives::{Address, Bytes, Log, TxKind, B256, I256, U256};
+use alloy::rpc::types::{Block, TransactionRequest};
+use alloy::sol;
+use alloy::sol_types::{decode_revert_reason, SolCall, SolEvent};
+use helios::core::types::BlockTag;
+use revm::db::CacheDB;
+use revm::inspectors::NoOpInspector;
+use revm::primitives::{AccountInfo, Bytecode, EVMError, Env, ExecutionResult, ResultAndState, SpecId};
+use revm::{inspector_handle_register, DatabaseRef, Evm, Inspector};
+
+use crate::error::{KromeError, Result};
+use crate::eth;
+use crate::helios::{HeliosClient, HeliosState};
+use crate::signing;
+
+// Runs a transaction in revm on top of the latest block without sending it.
+// Every account, code and storage read goes through the Helios client, so the
+// state it runs against is proof-verified, and the result is laid out for an
+// approval prompt: what the sender sends and receives, and what else changes.
+
+// Hardfork activation times of the networks Helios ships configs for, newest
+// first
+const HARDFORKS: &[(u64, &[(u64, SpecId)])] = &[
+    (
+        1,
+        &[(1746612311, SpecId::PRAGUE), (1710338135, SpecId::CANCUN), (1681338455, SpecId::SHANGHAI)],
+    ),
+    (
+        11155111,
+        &[(1741159776, SpecId::PRAGUE), (1706655072, SpecId::CANCUN), (1677557088, SpecId::SHANGHAI)],
+    ),
+    (
+        17000,
+        &[(1740434112, SpecId::PRAGUE), (1707305664, SpecId::CANCUN), (1696000704, SpecId::SHANGHAI)],
+    ),
+];
+
+// Newest rules revm can run; devnets and other chains get these
+const LATEST_SPEC: SpecId = SpecId::PRAGUE;
+
+// Seconds between blocks
+pub(crate) const SLOT_SECONDS: u64 = 12;
+
+// Hardfork rules a block at `timestamp` runs with
+pub(crate) fn spec_at(chain_id: u64, timestamp: u64) -> SpecId {
+    match HARDFORKS.iter().find(|(id, _)| *id == chain_id) {
+        Some((_, forks)) => forks
+            .iter()
+            .find(|(activation, _)| timestamp >= *activation)
+            .map_or(SpecId::MERGE, |(_, spec)| *spec),
+        None => LATEST_SPEC,
+    }
+}
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value);
+    event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values);
+
+    function symbol() external view returns (string);
+    function decimals() external view returns (uint8);
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Simulation {
+    // Block whose state it ran on top of
+    pub block_number: u64,
+    pub success: bool,
+    pub gas_used: u64,
+    // What gas_used costs at the transaction's effective gas price
+    pub gas_cost: U256,
+    // Return data, or revert data if it reverted
+    pub output: Bytes,
+    // Decoded revert reason, or why the EVM halted
+    pub error: Option<String>,
+    pub logs: Vec<SimulatedLog>,
+    // The sender's gains (positive) and losses (negative), gas not included
+    pub balance_changes: Vec<BalanceChange>,
+    // Every account the transaction touched
+    pub state_diff: Vec<AccountDiff>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(tag = "standard", rename_all = "lowercase", rename_all_fields = "camelCase")]
+pub enum Asset {
+    Native,
+    Erc20 { token: Address },
+    Erc721 { token: Address, token_id: U256 },
+    Erc1155 { token: Address, token_id: U256 },
+}
+
+impl Asset {
+    fn token(&self) -> Option<Address> {
+        match self {
+            Asset::Native => None,
+            Asset::Erc20 { token } | Asset::Erc721 { token, .. } | Asset::Erc1155 { token, .. } => Some(*token),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceChange {
+    #[serde(flatten)]
+    pub asset: Asset,
+    // In the token's smallest unit, or wei
+    pub amount: I256,
+    // Read from the token contract when it has them
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiff {
+    pub address: Address,
+    pub balance_before: U256,
+    pub balance_after: U256,
+    pub nonce_before: u64,
+    pub nonce_after: u64,
+    pub code_changed: bool,
+    // Slots read or written, sorted by slot
+    pub storage: Vec<StorageDiff>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiff {
+    pub slot: U256,
+    pub before: U256,
+    pub after: U256,
+}
+
+// revm's view of chain state, read through the light client at one block.
+// revm is synchronous, so it runs on a blocking thread and each read blocks
+// on the client there.
+#[derive(Clone)]
+pub(crate) struct HeliosDb {
+    client: HeliosClient,
+    handle: Handle,
+    block: BlockTag,
+    // Accounts as first read, for the before side of the diff
+    originals: Arc<Mutex<HashMap<Address, AccountInfo>>>,
+}
+
+impl HeliosDb {
+    // Must be used from inside the Tokio runtime
+    pub(crate) fn new(client: &HeliosClient, block_number: u64) -> Self {
+        HeliosDb {
+            client: client.clone(),
+            handle: Handle::current(),
+            block: BlockTag::Number(block_number),
+            originals: Default::default(),
+        }
+    }
+}
+
+impl DatabaseRef for HeliosDb {
+    type Error = KromeError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>> {
+        let (balance, nonce, code) = self.handle.block_on(async {
+            tokio::try_join!(
+                self.client.get_balance(address, self.block),
+                self.client.get_nonce(address, self.block),
+                self.client.get_code(address, self.block),
+            )
+        })?;
+        // Empty accounts don't exist as far as the EVM is concerned, which
+        // matters for gas and for EIP-161 cleanup
+        if balance.is_zero() && nonce == 0 && code.is_empty() {
+            return Ok(None);
+        }
+        let code = Bytecode::new_raw(code);
+        let info = AccountInfo::new(balance, nonce, code.hash_slow(), code);
+        self.originals.lock().unwrap().insert(address, info.clone());
+        Ok(Some(info))
+    }
+
+    // Code always comes with the account from basic_ref, which CacheDB keeps
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode> {
+        Err(KromeError::Simulation(format!("no code with hash {}", code_hash)))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256> {
+        let value = self
+            .handle
+            .block_on(self.client.get_storage_at(address, B256::from(index), self.block))?;
+        Ok(U256::from(value))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256> {
+        let block = self
+            .handle
+            .block_on(self.client.get_block_by_number(BlockTag::Number(number), false))?
+            .ok_or_else(|| KromeError::Helios(format!("block {} not found", number)))?;
+        Ok(block.header.hash)
+    }
+}
+
+// A transaction ready to run, and the block it runs on top of
+pub(crate) struct Prepared {
+    pub(crate) from: Address,
+    pub(crate) tx: TypedTransaction,
+    pub(crate) block: Block,
+    // Set when a reverting transaction runs without a gas estimate
+    skip_balance_check: bool,
+}
+
+// Fills what `tx` leaves out the way signing would. A transaction that
+// reverts can't be gas-estimated, so it gets the block gas limit and no
+// balance check instead.
+pub(crate) async fn prepare(client: &HeliosClient, mut tx: TransactionRequest) -> Result<Prepared> {
+    let from = tx
+        .from
+        .ok_or_else(|| KromeError::InvalidParams("transaction has no from address".to_string()))?;
+    let block = latest_block(client).await?;
+
+    let (tx, skip_balance_check) = match signing::fill(client, from, tx.clone()).await {
+        Ok(typed) => (typed, false),
+        Err(KromeError::ExecutionReverted { .. }) if tx.gas.is_none() => {
+            tx.gas = Some(block.header.gas_limit);
+            (signing::fill(client, from, tx).await?, true)
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(Prepared {
+        from,
+        tx,
+        block,
+        skip_balance_check,
+    })
+}
+
+pub async fn simulate(client: &HeliosClient, tx: TransactionRequest) -> Result<Simulation> {
+    run(client, prepare(client, tx).await?).await
+}
+
+// Simulates a transaction that's already filled, e.g. one about to be signed
+pub async fn simulate_typed(client: &HeliosClient, from: Address, tx: &TypedTransaction) -> Result<Simulation> {
+    let prepared = Prepared {
+        from,
+        tx: tx.clone(),
+        block: latest_block(client).await?,
+        skip_balance_check: false,
+    };
+    run(client, prepared).await
+}
+
+async fn latest_block(client: &HeliosClient) -> Result<Block> {
+    client
+        .get_block_by_number(BlockTag::Latest, false)
+        .await?
+        .ok_or_else(|| KromeError::Helios("latest block not found".to_string()))
+}
+
+// Gas `tx` needs on top of the block at `tag`. Helios only estimates against
+// the latest block, so other blocks run in the embedded EVM with the block gas
+// limit and no balance check, and get headroom for refunds and the 1/64 of
+// gas calls hold back.
+pub async fn estimate_gas(client: &HeliosClient, mut tx: TransactionRequest, tag: BlockTag) -> Result<u64> {
+    if matches!(tag, BlockTag::Latest) {
+        return Ok(client.estimate_gas(&tx).await?);
+    }
+    let from = tx.from.unwrap_or_default();
+    let block = client
+        .get_block_by_number(tag, false)
+        .await?
+        .ok_or_else(|| KromeError::Helios(format!("block {} not found", tag)))?;
+    let gas_limit = block.header.gas_limit;
+
+    tx.chain_id = Some(client.chain_id().await);
+    tx.nonce.get_or_insert(0);
+    tx.gas = Some(gas_limit);
+    if tx.max_fee_per_gas.is_some() {
+        tx.max_priority_fee_per_gas.get_or_insert(0);
+    } else if tx.gas_price.is_none() {
+        tx.gas_price = Some(next_base_fee(&block).into());
+    }
+    let tx = tx
+        .build_typed_tx()
+        .map_err(|_| KromeError::InvalidParams("transaction can't be estimated as given".to_string()))?;
+    let prepared = Prepared {
+        from,
+        tx,
+        block,
+        skip_balance_check: true,
+    };
+
+    let (ResultAndState { result, .. }, _, _) = execute(client, &prepared, NoOpInspector).await?;
+    match result {
+        ExecutionResult::Success {
+            gas_used, gas_refunded, ..
+        } => Ok(((gas_used + gas_refunded) * 64 / 63).min(gas_limit)),
+        ExecutionResult::Revert { output, .. } => Err(KromeError::ExecutionReverted {
+            reason: decode_revert_reason(&output),
+            data: output,
+        }),
+        ExecutionResult::Halt { reason, .. } => Err(KromeError::Simulation(format!("{:?}", reason))),
+    }
+}
+
+// Accounts as the EVM first read them
+pub(crate) type Originals = HashMap<Address, AccountInfo>;
+
+// Runs `prepared` in revm with `inspector` watching, and hands the inspector
+// back along with the result. It runs as the next block would: a slot after
+// the latest, at the base fee the latest sets for it. The nonce isn't
+// checked, so a transaction queued behind others from the same account can
+// still be previewed.
+pub(crate) async fn execute<I>(
+    client: &HeliosClient,
+    prepared: &Prepared,
+    inspector: I,
+) -> Result<(ResultAndState, Originals, I)>
+where
+    I: Inspector<CacheDB<HeliosDb>> + Send + 'static,
+{
+    let header = &prepared.block.header;
+    let tx = &prepared.tx;
+    let db = HeliosDb::new(client, header.number);
+    let originals = db.originals.clone();
+
+    let chain_id = client.chain_id().await;
+    let timestamp = header.timestamp + SLOT_SECONDS;
+    let spec = spec_at(chain_id, timestamp);
+
+    let mut env = Env::default();
+    env.cfg.chain_id = chain_id;
+    env.cfg.disable_balance_check = prepared.skip_balance_check;
+    env.block.number = U256::from(header.number + 1);
+    env.block.coinbase = header.beneficiary;
+    env.block.timestamp = U256::from(timestamp);
+    env.block.gas_limit = U256::from(header.gas_limit);
+    env.block.basefee = U256::from(next_base_fee(&prepared.block));
+    env.block.difficulty = header.difficulty;
+    env.block.prevrandao = Some(header.mix_hash);
+    env.tx.caller = prepared.from;
+    env.tx.transact_to = tx.kind();
+    env.tx.value = tx.value();
+    env.tx.data = tx.input().clone();
+    env.tx.nonce = None;
+    env.tx.chain_id = tx.chain_id();
+    env.tx.gas_limit = tx.gas_limit();
+    env.tx.gas_price = U256::from(tx.max_fee_per_gas());
+    env.tx.gas_priority_fee = tx.max_priority_fee_per_gas().map(U256::from);
+    env.tx.access_list = tx.access_list().map(|list| list.0.clone()).unwrap_or_default();
+    let env = Box::new(env);
+
+    let (result, inspector) = tokio::task::spawn_blocking(move || {
+        let mut evm = Evm::builder()
+            .with_db(CacheDB::new(db))
+            .with_external_context(inspector)
+            .with_env(env)
+            .with_spec_id(spec)
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let result = evm.transact();
+        (result, evm.into_context().external)
+    })
+    .await
+    .map_err(|e| KromeError::Simulation(e.to_string()))?;
+    let result = result.map_err(|e| match e {
+        EVMError::Database(e) => e,
+        e => KromeError::Simulation(e.to_string()),
+    })?;
+
+    let originals = originals.lock().unwrap().clone();
+    Ok((result, originals, inspector))
+}
+
+fn next_base_fee(block: &Block) -> u64 {
+    let header = &block.header;
+    eth::next_base_fee(header.gas_used, header.gas_limit, header.base_fee_per_gas.unwrap_or_default())
+}
+
+async fn run(client: &HeliosClient, prepared: Prepared) -> Result<Simulation> {
+    let (ResultAndState { result, state }, originals, _) = execute(client, &prepared, NoOpInspector).await?;
+    let from = prepared.from;
+    let block_number = prepared.block.header.number;
+    let base_fee = next_base_fee(&prepared.block);
+    let tx = &prepared.tx;
+
+    let gas_used = result.gas_used();
+    let gas_cost = U256::from(tx.effective_gas_price(Some(base_fee))) * U256::from(gas_used);
+    let (success, output, error, logs) = match result {
+        ExecutionResult::Success { output, logs, .. } => (true, output.into_data(), None, logs),
+        ExecutionResult::Revert { output, .. } => {
+            let reason = decode_revert_reason(&output).or_else(|| Some("execution reverted".to_string()));
+            (false, output, reason, Vec::new())
+        }
+        ExecutionResult::Halt { reason, .. } => (false, Bytes::new(), Some(format!("{:?}", reason)), Vec::new()),
+    };
+
+    let mut state_diff: Vec<AccountDiff> = state
+        .iter()
+        .filter(|(_, account)| account.is_touched())
+        .map(|(address, account)| {
+            let before = originals.get(address).cloned().unwrap_or_default();
+            let mut storage: Vec<StorageDiff> = account
+                .storage
+                .iter()
+                .map(|(slot, value)| StorageDiff {
+                    slot: *slot,
+                    before: value.original_value,
+                    after: value.present_value,
+                })
+                .collect();
+            storage.sort_by_key(|diff| diff.slot);
+            AccountDiff {
+                address: *address,
+                balance_before: before.balance,
+                balance_after: account.info.balance,
+                nonce_before: before.nonce,
+                nonce_after: account.info.nonce,
+                code_changed: before.code_hash != account.info.code_hash,
+                storage,
+            }
+        })
+        .collect();
+    state_diff.sort_by_key(|diff| diff.address);
+
+    let mut deltas = token_deltas(from, &logs);
+    if let Some(sender) = state_diff.iter().find(|diff| diff.address == from) {
+        let native = I256::from_raw(sender.balance_after) - I256::from_raw(sender.balance_before)
+            + I256::from_raw(gas_cost);
+        if !native.is_zero() {
+            deltas.insert(Asset::Native, native);
+        }
+    }
+    let balance_changes = describe(client, deltas, block_number).await;
+
+    Ok(Simulation {
+        block_number,
+        success,
+        gas_used,
+        gas_cost,
+        output,
+        error,
+        logs: logs
+            .into_iter()
+            .map(|log| SimulatedLog {
+                address: log.address,
+                topics: log.topics().to_vec(),
+                data: log.data.data,
+            })
+            .collect(),
+        balance_changes,
+        state_diff,
+    })
+}
+
+// Net token movements in and out of `owner`, from the standard transfer events
+fn token_deltas(owner: Address, logs: &[Log]) -> BTreeMap<Asset, I256> {
+    let mut deltas: BTreeMap<Asset, I256> = BTreeMap::new();
+    let mut add = |asset: Asset, from: Address, to: Address, amount: U256| {
+        let amount = I256::from_raw(amount);
+        if from == owner {
+            *deltas.entry(asset.clone()).or_default() -= amount;
+        }
+        if to == owner {
+            *deltas.entry(asset).or_default() += amount;
+        }
+    };
+
+    for log in logs {
+        let token = log.address;
+        let topics = log.topics();
+        match topics.first() {
+            // ERC-20 and ERC-721 share the event; only the latter indexes the amount
+            Some(topic) if *topic == Transfer::SIGNATURE_HASH => {
+                let from = Address::from_word(topics.get(1).copied().unwrap_or_default());
+                let to = Address::from_word(topics.get(2).copied().unwrap_or_default());
+                match topics.len() {
+                    3 if log.data.data.len() >= 32 => {
+                        add(Asset::Erc20 { token }, from, to, U256::from_be_slice(&log.data.data[..32]))
+                    }
+                    4 => {
+                        let token_id = U256::from_be_bytes(topics[3].0);
+                        add(Asset::Erc721 { token, token_id }, from, to, U256::from(1))
+                    }
+                    _ => {}
+                }
+            }
+            Some(topic) if *topic == TransferSingle::SIGNATURE_HASH => {
+                if let Ok(event) = TransferSingle::decode_raw_log(topics.iter().copied(), &log.data.data, true) {
+                    let asset = Asset::Erc1155 { token, token_id: event.id };
+                    add(asset, event.from, event.to, event.value);
+                }
+            }
+            Some(topic) if *topic == TransferBatch::SIGNATURE_HASH => {
+                if let Ok(event) = TransferBatch::decode_raw_log(topics.iter().copied(), &log.data.data, true) {
+                    for (token_id, value) in event.ids.iter().zip(&event.values) {
+                        let asset = Asset::Erc1155 { token, token_id: *token_id };
+                        add(asset, event.from, event.to, *value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    deltas.retain(|_, delta| !delta.is_zero());
+    deltas
+}
+
+// Adds token symbols and decimals, where the contracts have them
+async fn describe(client: &HeliosClient, deltas: BTreeMap<Asset, I256>, block_number: u64) -> Vec<BalanceChange> {
+    let mut changes = Vec::new();
+    for (asset, amount) in deltas {
+        let (symbol, decimals) = match (&asset, asset.token()) {
+            (Asset::Native, _) | (_, None) => (Some("ETH".to_string()), Some(18)),
+            (Asset::Erc20 { .. }, Some(token)) => (
+                call_view(client, token, symbolCall {}, block_number).await.map(|r| r._0),
+                call_view(client, token, decimalsCall {}, block_number).await.map(|r| r._0),
+            ),
+            (_, Some(token)) => (
+                call_view(client, token, symbolCall {}, block_number).await.map(|r| r._0),
+                None,
+            ),
+        };
+        changes.push(BalanceChange {
+            asset,
+            amount,
+            symbol,
+            decimals,
+        });
+    }
+    changes
+}
+
+async fn call_view<C: SolCall>(client: &HeliosClient, to: Address, call: C, block_number: u64) -> Option<C::Return> {
+    let tx = TransactionRequest {
+        to: Some(TxKind::Call(to)),
+        input: call.abi_encode().into(),
+        ..Default::default()
+    };
+    let output = client.call(&tx, BlockTag::Number(block_number)).await.ok()?;
+    C::abi_decode_returns(&output, true).ok()
+}
+
+// Simulates `tx` from `tx.from` on top of the latest block. Nothing is signed
+// or sent.
+#[tauri::command]
+pub(crate) async fn simulate_transaction(state: State<'_, HeliosState>, tx: TransactionRequest) -> Result<Simulation> {
+    let client = state.client().await?;
+    simulate(&client, tx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> Address {
+        Address::repeat_byte(0x0e)
+    }
+
+    fn other() -> Address {
+        Address::repeat_byte(0x07)
+    }
+
+    fn token() -> Address {
+        Address::repeat_byte(0x70)
+    }
+
+    fn log(event: &impl SolEvent) -> Log {
+        Log {
+            address: token(),
+            data: event.encode_log_data(),
+        }
+    }
+
+    #[test]
+    fn picks_the_hardfork_by_time() {
+        assert_eq!(spec_at(1, 1710338134), SpecId::SHANGHAI);
+        assert_eq!(spec_at(1, 1710338135), SpecId::CANCUN);
+        assert_eq!(spec_at(1, 1746612311), SpecId::PRAGUE);
+        assert_eq!(spec_at(11155111, 1741159775), SpecId::CANCUN);
+        assert_eq!(spec_at(1, 1_600_000_000), SpecId::MERGE);
+        assert_eq!(spec_at(1337, 0), LATEST_SPEC);
+    }
+
+    #[test]
+    fn nets_erc20_transfers() {
+        let value = |value: u64| Transfer {
+            from: owner(),
+            to: other(),
+            value: U256::from(value),
+        };
+        let back = Transfer {
+            from: other(),
+            to: owner(),
+            value: U256::from(30),
+        };
+        let deltas = token_deltas(owner(), &[log(&value(100)), log(&back)]);
+        assert_eq!(deltas.get(&Asset::Erc20 { token: token() }), Some(&I256::try_from(-70).unwrap()));
+        assert_eq!(deltas.len(), 1);
+
+        // Transfers the owner isn't part of, and ones that cancel out, are left out
+        let elsewhere = Transfer {
+            from: other(),
+            to: token(),
+            value: U256::from(5),
+        };
+        let undo = Transfer {
+            from: other(),
+            to: owner(),
+            value: U256::from(100),
+        };
+        assert!(token_deltas(owner(), &[log(&value(100)), log(&undo), log(&elsewhere)]).is_empty());
+    }
+
+    #[test]
+    fn counts_erc721_tokens_by_id() {
+        // ERC-721 indexes the token ID, so it has a fourth topic and no data
+        let topics = vec![
+            Transfer::SIGNATURE_HASH,
+            other().into_word(),
+            owner().into_word(),
+            B256::from(U256::from(42)),
+        ];
+        let nft = Log::new_unchecked(token(), topics, Bytes::new());
+        let deltas = token_deltas(owner(), &[nft]);
+        let asset = Asset::Erc721 {
+            token: token(),
+            token_id: U256::from(42),
+        };
+        assert_eq!(deltas.get(&asset), Some(&I256::ONE));
+        assert_eq!(deltas.len(), 1);
+    }
+
+    #[test]
+    fn splits_erc1155_batches_by_id() {
+        let single = TransferSingle {
+            operator: other(),
+            from: owner(),
+            to: other(),
+            id: U256::from(1),
+            value: U256::from(5),
+        };
+        let batch = TransferBatch {
+            operator: other(),
+            from: other(),
+            to: owner(),
+            ids: vec![U256::from(1), U256::from(2)],
+            values: vec![U256::from(2), U256::from(7)],
+        };
+        let deltas = token_deltas(owner(), &[log(&single), log(&batch)]);
+        let asset = |id: u64| Asset::Erc1155 {
+            token: token(),
+            token_id: U256::from(id),
+        };
+        assert_eq!(deltas.get(&asset(1)), Some(&I256::try_from(-3).unwrap()));
+        assert_eq!(deltas.get(&asset(2)), Some(&I256::try_from(7).unwrap()));
+        assert_eq!(deltas.len(), 2);
+    }
+}
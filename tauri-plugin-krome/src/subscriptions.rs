@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, EventTarget, Manager, Runtime, State, WebviewWindow};
+
+use alloy::rpc::types::Filter;
+use helios::core::types::BlockTag;
+
+use crate::approvals::Origin;
+use crate::error::{KromeError, Result};
+use crate::fork::{Fork, ForkState};
+use crate::helios::HeliosClient;
+use crate::KromeExt;
+
+// New heads and logs are pushed to the window that subscribed, shaped like
+// the `params` of an eth_subscription notification so the EIP-1193 bridge can
+// pass them straight through.
+
+pub const SUBSCRIPTION_EVENT: &str = "helios://subscription";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Most blocks caught up on at once, e.g. after the client was stopped for a while
+const MAX_CATCH_UP_BLOCKS: u64 = 32;
+
+#[derive(Clone, Debug)]
+pub enum SubscriptionKind {
+    NewHeads,
+    Logs(Filter),
+}
+
+struct Subscription {
+    kind: SubscriptionKind,
+    // Last block this subscription was notified of
+    notified: Option<u64>,
+}
+
+// Subscriptions belong to the window and page origin that made them, so one
+// page can't cancel or receive another's
+type SubscriptionKey = (Origin, String);
+
+// Payload of SUBSCRIPTION_EVENT
+#[derive(Clone, Debug, Serialize)]
+pub struct SubscriptionMessage {
+    pub subscription: String,
+    pub result: Value,
+}
+
+#[derive(Default)]
+pub struct Subscriptions {
+    subs: Mutex<HashMap<SubscriptionKey, Subscription>>,
+    next_id: AtomicU64,
+    // Chain the notified blocks are on
+    chain_id: Mutex<u64>,
+}
+
+impl Subscriptions {
+    pub fn subscribe(&self, kind: SubscriptionKind, origin: &Origin) -> String {
+        let id = format!("{:#x}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        self.subs
+            .lock()
+            .unwrap()
+            .insert((origin.clone(), id.clone()), Subscription { kind, notified: None });
+        id
+    }
+
+    // Returns whether `origin` had a subscription with this ID
+    pub fn unsubscribe(&self, origin: &Origin, id: &str) -> bool {
+        self.subs.lock().unwrap().remove(&(origin.clone(), id.to_string())).is_some()
+    }
+
+    // Drops everything a closed window subscribed to
+    pub fn remove_window(&self, window: &str) {
+        self.subs.lock().unwrap().retain(|(owner, _), _| owner.window != window);
+    }
+
+    // Subscriptions still owned by the page in their window, with the last
+    // block each was notified of. Those whose window has since navigated to
+    // another origin are dropped.
+    fn snapshot<R: Runtime>(&self, app_handle: &AppHandle<R>) -> Vec<(SubscriptionKey, SubscriptionKind, Option<u64>)> {
+        let mut subs = self.subs.lock().unwrap();
+        subs.retain(|(owner, _), _| {
+            app_handle
+                .get_webview_window(&owner.window)
+                .map_or(true, |window| Origin::of(&window) == *owner)
+        });
+        subs.iter()
+            .map(|(key, sub)| (key.clone(), sub.kind.clone(), sub.notified))
+            .collect()
+    }
+
+    fn mark_notified(&self, key: &SubscriptionKey, number: u64) {
+        if let Some(sub) = self.subs.lock().unwrap().get_mut(key) {
+            sub.notified = Some(number);
+        }
+    }
+
+    // Sends each subscription its notifications for the blocks since it was
+    // last notified. A subscription whose notifications fail is retried from
+    // where it stopped on the next poll without holding up the others.
+    async fn poll<R: Runtime>(&self, app_handle: &AppHandle<R>, client: &HeliosClient) -> Result<()> {
+        let chain_id = client.chain_id().await;
+        let latest = client.get_block_number().await?.to::<u64>();
+
+        // Start from the tip after a chain switch instead of replaying
+        let switched = {
+            let mut current = self.chain_id.lock().unwrap();
+            std::mem::replace(&mut *current, chain_id) != chain_id
+        };
+        if switched {
+            self.skip_to_tip();
+        }
+
+        let mut heads = Vec::new();
+        let mut logs = Vec::new();
+        for (key, kind, notified) in self.snapshot(app_handle) {
+            let Some(from) = next_block(notified, latest) else {
+                continue;
+            };
+            match kind {
+                SubscriptionKind::NewHeads => heads.push((key, from)),
+                SubscriptionKind::Logs(filter) => logs.push((key, filter, from)),
+            }
+        }
+
+        let mut result = Ok(());
+        if let Some(first) = heads.iter().map(|(_, from)| *from).min() {
+            for number in first..=latest {
+                let header = match client.get_block_by_number(BlockTag::Number(number), false).await {
+                    Ok(Some(block)) => serde_json::to_value(&block.header)?,
+                    Ok(None) => {
+                        result = Err(KromeError::Helios(format!("block {} not found", number)));
+                        break;
+                    }
+                    Err(e) => {
+                        result = Err(e.into());
+                        break;
+                    }
+                };
+                for (key, _) in heads.iter().filter(|(_, from)| *from <= number) {
+                    send(app_handle, &key.0.window, &key.1, header.clone());
+                    self.mark_notified(key, number);
+                }
+            }
+        }
+
+        for (key, filter, from) in logs {
+            let filter = filter.from_block(from).to_block(latest);
+            match client.get_logs(&filter).await {
+                Ok(found) => {
+                    for log in found {
+                        send(app_handle, &key.0.window, &key.1, serde_json::to_value(log)?);
+                    }
+                    self.mark_notified(&key, latest);
+                }
+                Err(e) => result = Err(e.into()),
+            }
+        }
+        result
+    }
+
+    // Sends notifications for blocks mined on a running fork, from `from` to
+    // its latest. The fork's blocks stand in for the network's while it runs.
+    pub(crate) fn notify_fork<R: Runtime>(&self, app_handle: &AppHandle<R>, fork: &Fork, from: u64) {
+        let to = fork.block_number();
+        for ((owner, id), kind, _) in self.snapshot(app_handle) {
+            match kind {
+                SubscriptionKind::NewHeads => {
+                    for header in (from..=to).filter_map(|number| fork.header(number)) {
+                        send(app_handle, &owner.window, &id, header);
+                    }
+                }
+                SubscriptionKind::Logs(filter) => {
+                    for log in fork.local_logs(&filter, from, to) {
+                        send(app_handle, &owner.window, &id, log);
+                    }
+                }
+            }
+        }
+    }
+
+    // Starts every subscription from the tip on the next poll instead of
+    // catching up
+    fn skip_to_tip(&self) {
+        for sub in self.subs.lock().unwrap().values_mut() {
+            sub.notified = None;
+        }
+    }
+}
+
+// First block to notify a subscription of, or None if it's up to date. New
+// subscriptions start at the tip.
+fn next_block(notified: Option<u64>, latest: u64) -> Option<u64> {
+    match notified {
+        Some(last) if last >= latest => None,
+        Some(last) => Some((last + 1).max(latest.saturating_sub(MAX_CATCH_UP_BLOCKS - 1))),
+        None => Some(latest),
+    }
+}
+
+fn send<R: Runtime>(app_handle: &AppHandle<R>, window: &str, id: &str, result: Value) {
+    let _ = app_handle.emit_to(
+        EventTarget::webview_window(window),
+        SUBSCRIPTION_EVENT,
+        SubscriptionMessage {
+            subscription: id.to_string(),
+            result,
+        },
+    );
+}
+
+// Watches for new blocks for as long as the app runs. Nothing happens while
+// the client is stopped or syncing, or while a fork runs.
+pub(crate) async fn watch<R: Runtime>(app_handle: AppHandle<R>) {
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let Ok(client) = app_handle.krome().client().await else {
+            continue;
+        };
+        let subscriptions = app_handle.state::<Subscriptions>();
+        // A running fork sends its own blocks, and the network's mined
+        // meanwhile aren't replayed after it stops
+        if app_handle.state::<ForkState>().is_running().await {
+            subscriptions.skip_to_tip();
+            continue;
+        }
+        let _ = subscriptions.poll(&app_handle, &client).await;
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn subscribe_new_heads<R: Runtime>(
+    subscriptions: State<'_, Subscriptions>,
+    window: WebviewWindow<R>,
+) -> Result<String> {
+    Ok(subscriptions.subscribe(SubscriptionKind::NewHeads, &Origin::of(&window)))
+}
+
+// Block range fields in the filter are ignored; every new block is matched
+#[tauri::command]
+pub(crate) async fn subscribe_logs<R: Runtime>(
+    subscriptions: State<'_, Subscriptions>,
+    window: WebviewWindow<R>,
+    filter: Filter,
+) -> Result<String> {
+    Ok(subscriptions.subscribe(SubscriptionKind::Logs(filter), &Origin::of(&window)))
+}
+
+#[tauri::command]
+pub(crate) async fn unsubscribe<R: Runtime>(
+    subscriptions: State<'_, Subscriptions>,
+    window: WebviewWindow<R>,
+    id: String,
+) -> Result<bool> {
+    Ok(subscriptions.unsubscribe(&Origin::of(&window), &id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(window: &str, url: &str) -> Origin {
+        Origin {
+            window: window.to_string(),
+            url: Some(url.to_string()),
+        }
+    }
+
+    #[test]
+    fn only_the_subscriber_can_unsubscribe() {
+        let subscriptions = Subscriptions::default();
+        let dapp = origin("main", "https://dapp.example");
+        let id = subscriptions.subscribe(SubscriptionKind::NewHeads, &dapp);
+
+        assert!(!subscriptions.unsubscribe(&origin("main", "https://evil.example"), &id));
+        assert!(!subscriptions.unsubscribe(&origin("other", "https://dapp.example"), &id));
+        assert!(subscriptions.unsubscribe(&dapp, &id));
+        assert!(!subscriptions.unsubscribe(&dapp, &id));
+    }
+
+    #[test]
+    fn closing_a_window_drops_its_subscriptions() {
+        let subscriptions = Subscriptions::default();
+        let main = origin("main", "https://dapp.example");
+        let other = origin("other", "https://dapp.example");
+        let dropped = subscriptions.subscribe(SubscriptionKind::NewHeads, &main);
+        let kept = subscriptions.subscribe(SubscriptionKind::Logs(Filter::new()), &other);
+        assert_ne!(dropped, kept);
+
+        subscriptions.remove_window("main");
+        assert!(!subscriptions.unsubscribe(&main, &dropped));
+        assert!(subscriptions.unsubscribe(&other, &kept));
+    }
+
+    #[test]
+    fn subscriptions_resume_where_they_stopped() {
+        assert_eq!(next_block(None, 100), Some(100));
+        assert_eq!(next_block(Some(100), 100), None);
+        assert_eq!(next_block(Some(97), 100), Some(98));
+        // Long gaps are cut to the most recent blocks
+        assert_eq!(next_block(Some(10), 100), Some(100 - MAX_CATCH_UP_BLOCKS + 1));
+
+        let subscriptions = Subscriptions::default();
+        let dapp = origin("main", "https://dapp.example");
+        let id = subscriptions.subscribe(SubscriptionKind::NewHeads, &dapp);
+        let key = (dapp, id);
+        subscriptions.mark_notified(&key, 42);
+        assert_eq!(subscriptions.subs.lock().unwrap()[&key].notified, Some(42));
+        subscriptions.skip_to_tip();
+        assert_eq!(subscriptions.subs.lock().unwrap()[&key].notified, None);
+    }
+}
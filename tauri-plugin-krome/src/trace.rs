@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use alloy::rpc::types::trace::geth::{CallConfig, CallFrame, DefaultFrame, GethDefaultTracingOptions};
+use alloy::rpc::types::TransactionRequest;
+use revm::primitives::ExecutionResult;
+use revm_inspectors::tracing::{TracingInspector, TracingInspectorConfig};
+
+use crate::error::Result;
+use crate::helios::{HeliosClient, HeliosState};
+use crate::simulation;
+
+// Traces a transaction the way simulation runs it, against proof-verified
+// state on top of the latest block. Output is in geth's debug_traceCall
+// formats, so existing trace viewers can read it.
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TraceOptions {
+    // Include the logs each frame emitted
+    pub with_logs: bool,
+    // Record every opcode, with geth's struct logger options
+    pub struct_logs: Option<GethDefaultTracingOptions>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallTrace {
+    // Block whose state it ran on top of
+    pub block_number: u64,
+    // The call tree, as geth's callTracer returns it
+    pub call: CallFrame,
+    // As geth's default struct logger returns it, if asked for
+    pub struct_logs: Option<DefaultFrame>,
+}
+
+pub async fn trace(client: &HeliosClient, tx: TransactionRequest, options: TraceOptions) -> Result<CallTrace> {
+    let (call_config, config) = configs(&options);
+    let prepared = simulation::prepare(client, tx).await?;
+    let (result, _, inspector) = simulation::execute(client, &prepared, TracingInspector::new(config)).await?;
+    Ok(build(prepared.block.header.number, result.result, inspector, call_config, options))
+}
+
+fn configs(options: &TraceOptions) -> (CallConfig, TracingInspectorConfig) {
+    let call_config = CallConfig {
+        only_top_call: None,
+        with_log: Some(options.with_logs),
+    };
+    let config = match &options.struct_logs {
+        Some(struct_logs) => TracingInspectorConfig::from_geth_config(struct_logs).set_record_logs(options.with_logs),
+        None => TracingInspectorConfig::from_geth_call_config(&call_config),
+    };
+    (call_config, config)
+}
+
+fn build(
+    block_number: u64,
+    result: ExecutionResult,
+    inspector: TracingInspector,
+    call_config: CallConfig,
+    options: TraceOptions,
+) -> CallTrace {
+    let gas_used = result.gas_used();
+    let output = match result {
+        ExecutionResult::Success { output, .. } => output.into_data(),
+        ExecutionResult::Revert { output, .. } => output,
+        ExecutionResult::Halt { .. } => Default::default(),
+    };
+    let builder = inspector.into_geth_builder();
+    CallTrace {
+        block_number,
+        call: builder.geth_call_traces(call_config, gas_used),
+        struct_logs: options
+            .struct_logs
+            .map(|struct_logs| builder.geth_traces(gas_used, output, struct_logs)),
+    }
+}
+
+// Traces `tx` from `tx.from` on top of the latest block. Nothing is signed
+// or sent.
+#[tauri::command]
+pub(crate) async fn trace_call(
+    state: State<'_, HeliosState>,
+    tx: TransactionRequest,
+    options: Option<TraceOptions>,
+) -> Result<CallTrace> {
+    let client = state.client().await?;
+    trace(&client, tx, options.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, bytes, Address, Bytes, TxKind, U256};
+    use revm::db::{CacheDB, EmptyDB};
+    use revm::primitives::{AccountInfo, Bytecode};
+    use revm::{inspector_handle_register, Evm};
+
+    const CALLER: Address = address!("1000000000000000000000000000000000000001");
+    const CONTRACT: Address = address!("2000000000000000000000000000000000000002");
+
+    #[test]
+    fn traces_calls_and_opcodes() {
+        // Returns 2 + 3 as a word
+        let code = bytes!("600260030160005260206000f3");
+        let mut db = CacheDB::new(EmptyDB::default());
+        let code = Bytecode::new_raw(code);
+        db.insert_account_info(CONTRACT, AccountInfo::new(U256::ZERO, 1, code.hash_slow(), code));
+
+        let options = TraceOptions {
+            with_logs: false,
+            struct_logs: Some(GethDefaultTracingOptions::default()),
+        };
+        let (call_config, config) = configs(&options);
+        let mut evm = Evm::builder()
+            .with_db(db)
+            .with_external_context(TracingInspector::new(config))
+            .modify_tx_env(|tx| {
+                tx.caller = CALLER;
+                tx.transact_to = TxKind::Call(CONTRACT);
+                tx.gas_limit = 100_000;
+            })
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let result = evm.transact().unwrap().result;
+        let inspector = evm.into_context().external;
+        let trace = build(7, result, inspector, call_config, options);
+
+        let five = Bytes::from(U256::from(5).to_be_bytes::<32>());
+        assert_eq!(trace.block_number, 7);
+        assert_eq!((trace.call.from, trace.call.to), (CALLER, Some(CONTRACT)));
+        assert_eq!(trace.call.typ, "CALL");
+        assert_eq!(trace.call.output, Some(five.clone()));
+        assert!(trace.call.error.is_none());
+
+        let frame = trace.struct_logs.unwrap();
+        assert!(!frame.failed);
+        assert_eq!(frame.return_value, five);
+        let ops: Vec<_> = frame.struct_logs.iter().map(|log| log.op.as_str()).collect();
+        assert_eq!(ops, ["PUSH1", "PUSH1", "ADD", "PUSH1", "MSTORE", "PUSH1", "PUSH1", "RETURN"]);
+        let add = &frame.struct_logs[2];
+        assert_eq!(add.pc, 4);
+        assert_eq!(add.stack, Some(vec![U256::from(2), U256::from(3)]));
+    }
+}
@@ -0,0 +1,408 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use alloy::consensus::{Transaction as _, TxEnvelope};
+use alloy::eips::eip2718::Decodable2718;
+use alloy::primitives::{Address, Bytes, B256};
+use helios::core::types::BlockTag;
+
+use crate::config::write_atomic;
+use crate::error::{KromeError, Result};
+use crate::helios::{HeliosClient, HeliosState};
+use crate::KromeExt;
+
+// Transactions sent through Krome are tracked in the background from
+// submission until they're confirmed, replaced or dropped. The list is kept in
+// the app data dir so tracking picks up again after a restart.
+
+// Sent with the TrackedTx whenever its status changes
+pub const TX_STATUS_EVENT: &str = "helios://tx-status";
+
+const TRANSACTIONS_FILE: &str = "transactions.json";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(6);
+// Blocks on top of the including one before a transaction counts as confirmed
+pub const DEFAULT_CONFIRMATIONS: u64 = 12;
+// A transaction that hasn't been mined by then, with its nonce still unused,
+// is considered dropped from the mempool
+const DROP_AFTER_SECS: u64 = 60 * 60;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase", rename_all_fields = "camelCase")]
+pub enum TxStatus {
+    Pending,
+    // Mined, but with fewer confirmations than asked for
+    Included {
+        block_number: u64,
+        confirmations: u64,
+        success: bool,
+    },
+    Confirmed {
+        block_number: u64,
+        confirmations: u64,
+        success: bool,
+    },
+    // Another transaction with the same nonce was mined instead
+    Replaced { by: Option<B256> },
+    Dropped,
+}
+
+impl TxStatus {
+    // Whether the tracker is done with it
+    pub fn is_final(&self) -> bool {
+        matches!(self, TxStatus::Confirmed { .. } | TxStatus::Replaced { .. } | TxStatus::Dropped)
+    }
+
+    fn is_mined(&self) -> bool {
+        matches!(self, TxStatus::Included { .. } | TxStatus::Confirmed { .. })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedTx {
+    pub hash: B256,
+    pub chain_id: u64,
+    pub from: Address,
+    pub nonce: u64,
+    pub status: TxStatus,
+    pub confirmations_required: u64,
+    // Unix timestamps in seconds
+    pub submitted_at: u64,
+    pub updated_at: u64,
+}
+
+pub struct TxTracker {
+    path: PathBuf,
+    txs: Mutex<Vec<TrackedTx>>,
+}
+
+impl TxTracker {
+    // Reads the saved transactions. A missing file means there are none.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(TRANSACTIONS_FILE);
+        let txs = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(TxTracker {
+            path,
+            txs: Mutex::new(txs),
+        })
+    }
+
+    pub async fn list(&self, chain_id: Option<u64>) -> Vec<TrackedTx> {
+        self.txs
+            .lock()
+            .await
+            .iter()
+            .filter(|tx| chain_id.map_or(true, |id| tx.chain_id == id))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get(&self, hash: B256) -> Option<TrackedTx> {
+        self.txs.lock().await.iter().find(|tx| tx.hash == hash).cloned()
+    }
+
+    pub async fn track(&self, tx: TrackedTx) -> Result<()> {
+        let mut txs = self.txs.lock().await;
+        txs.retain(|t| t.hash != tx.hash);
+        txs.push(tx);
+        save(&self.path, &txs)
+    }
+
+    pub async fn forget(&self, hash: B256) -> Result<()> {
+        let mut txs = self.txs.lock().await;
+        txs.retain(|tx| tx.hash != hash);
+        save(&self.path, &txs)
+    }
+
+    // Checks every unfinished transaction on the client's chain once
+    async fn poll<R: Runtime>(&self, app_handle: &AppHandle<R>, client: &HeliosClient) -> Result<()> {
+        let chain_id = client.chain_id().await;
+        let snapshot: Vec<TrackedTx> = self.list(Some(chain_id)).await;
+        let latest = client.get_block_number().await?.to::<u64>();
+
+        let mut changed = Vec::new();
+        for tx in snapshot.iter().filter(|tx| !tx.status.is_final()) {
+            // One failed lookup mustn't hold up the others
+            let status = match check(client, tx, latest, &snapshot).await {
+                Ok(status) => status,
+                // Receipts older than the client's block window can't be
+                // fetched anymore, so count on from the block it was seen in
+                Err(_) if tx.status.is_mined() => recount(tx, latest),
+                // Left as it is and tried again on the next poll
+                Err(_) => continue,
+            };
+            if status != tx.status {
+                changed.push((tx.hash, status));
+            }
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let mut txs = self.txs.lock().await;
+        let mut updated = Vec::new();
+        for (hash, status) in changed {
+            if let Some(tx) = txs.iter_mut().find(|tx| tx.hash == hash) {
+                tx.status = status;
+                tx.updated_at = now();
+                updated.push(tx.clone());
+            }
+        }
+        save(&self.path, &txs)?;
+        drop(txs);
+
+        for tx in updated {
+            let _ = app_handle.emit(TX_STATUS_EVENT, tx);
+        }
+        Ok(())
+    }
+}
+
+// Works out where a transaction stands as of the latest block
+async fn check(client: &HeliosClient, tx: &TrackedTx, latest: u64, tracked: &[TrackedTx]) -> Result<TxStatus> {
+    // Read the nonce before the receipt, so a transaction mined in between
+    // isn't mistaken for a replaced one
+    let nonce = client.get_nonce(tx.from, BlockTag::Latest).await?;
+
+    if let Some(receipt) = client.get_transaction_receipt(tx.hash).await? {
+        let block_number = receipt.block_number.unwrap_or(latest);
+        return Ok(mined(tx, block_number, latest, receipt.status()));
+    }
+    Ok(without_receipt(tx, nonce, latest, tracked))
+}
+
+// Where a transaction stands when the client has no receipt for it, given the
+// account's nonce. One already seen mined keeps counting from its block, since
+// its receipt may only have left the client's block window.
+fn without_receipt(tx: &TrackedTx, nonce: u64, latest: u64, tracked: &[TrackedTx]) -> TxStatus {
+    if tx.status.is_mined() {
+        return recount(tx, latest);
+    }
+    if nonce > tx.nonce {
+        // If the replacement went through Krome too, point at it
+        let by = tracked
+            .iter()
+            .find(|t| t.hash != tx.hash && t.from == tx.from && t.nonce == tx.nonce && t.status.is_mined())
+            .map(|t| t.hash);
+        return TxStatus::Replaced { by };
+    }
+    if now().saturating_sub(tx.submitted_at) > DROP_AFTER_SECS {
+        return TxStatus::Dropped;
+    }
+    TxStatus::Pending
+}
+
+fn mined(tx: &TrackedTx, block_number: u64, latest: u64, success: bool) -> TxStatus {
+    let confirmations = latest.saturating_sub(block_number) + 1;
+    if confirmations >= tx.confirmations_required {
+        TxStatus::Confirmed {
+            block_number,
+            confirmations,
+            success,
+        }
+    } else {
+        TxStatus::Included {
+            block_number,
+            confirmations,
+            success,
+        }
+    }
+}
+
+// Confirmations of a mined transaction, from the block it was last seen in
+fn recount(tx: &TrackedTx, latest: u64) -> TxStatus {
+    match tx.status {
+        TxStatus::Included { block_number, success, .. } | TxStatus::Confirmed { block_number, success, .. } => {
+            mined(tx, block_number, latest, success)
+        }
+        _ => tx.status.clone(),
+    }
+}
+
+fn save(path: &Path, txs: &[TrackedTx]) -> Result<()> {
+    write_atomic(path, &serde_json::to_string_pretty(txs)?)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Polls tracked transactions for as long as the app runs. Nothing happens
+// while the client is stopped or syncing.
+pub(crate) async fn watch<R: Runtime>(app_handle: AppHandle<R>) {
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let Ok(client) = app_handle.krome().client().await else {
+            continue;
+        };
+        let _ = app_handle.state::<TxTracker>().poll(&app_handle, &client).await;
+    }
+}
+
+// Broadcasts a signed transaction and starts tracking it
+pub async fn send_raw<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    client: &HeliosClient,
+    raw: &Bytes,
+    confirmations: Option<u64>,
+) -> Result<B256> {
+    let envelope = TxEnvelope::decode_2718(&mut raw.as_ref())
+        .map_err(|e| KromeError::InvalidParams(format!("invalid signed transaction: {}", e)))?;
+    let from = envelope
+        .recover_signer()
+        .map_err(|e| KromeError::InvalidParams(format!("invalid signature: {}", e)))?;
+
+    let hash = client.send_raw_transaction(raw).await?;
+
+    let submitted_at = now();
+    let tx = TrackedTx {
+        hash,
+        chain_id: client.chain_id().await,
+        from,
+        nonce: envelope.nonce(),
+        status: TxStatus::Pending,
+        confirmations_required: confirmations.unwrap_or(DEFAULT_CONFIRMATIONS).max(1),
+        submitted_at,
+        updated_at: submitted_at,
+    };
+    app_handle.state::<TxTracker>().track(tx.clone()).await?;
+    let _ = app_handle.emit(TX_STATUS_EVENT, tx);
+    Ok(hash)
+}
+
+#[tauri::command]
+pub(crate) async fn send_raw_transaction<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, HeliosState>,
+    raw: Bytes,
+    confirmations: Option<u64>,
+) -> Result<B256> {
+    let client = state.client().await?;
+    send_raw(&app_handle, &client, &raw, confirmations).await
+}
+
+#[tauri::command]
+pub(crate) async fn get_tracked_transactions(
+    tracker: State<'_, TxTracker>,
+    chain_id: Option<u64>,
+) -> Result<Vec<TrackedTx>> {
+    Ok(tracker.list(chain_id).await)
+}
+
+#[tauri::command]
+pub(crate) async fn get_tracked_transaction(tracker: State<'_, TxTracker>, hash: B256) -> Result<Option<TrackedTx>> {
+    Ok(tracker.get(hash).await)
+}
+
+// Stops tracking a transaction and removes it from the saved list
+#[tauri::command]
+pub(crate) async fn forget_transaction(tracker: State<'_, TxTracker>, hash: B256) -> Result<()> {
+    tracker.forget(hash).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn included(block_number: u64) -> TrackedTx {
+        TrackedTx {
+            hash: B256::repeat_byte(1),
+            chain_id: 1,
+            from: Address::repeat_byte(2),
+            nonce: 0,
+            status: TxStatus::Included {
+                block_number,
+                confirmations: 1,
+                success: true,
+            },
+            confirmations_required: 12,
+            submitted_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn recounts_confirmations_without_a_receipt() {
+        let tx = included(100);
+        assert_eq!(
+            recount(&tx, 105),
+            TxStatus::Included {
+                block_number: 100,
+                confirmations: 6,
+                success: true
+            }
+        );
+        assert_eq!(
+            recount(&tx, 111),
+            TxStatus::Confirmed {
+                block_number: 100,
+                confirmations: 12,
+                success: true
+            }
+        );
+    }
+
+    #[test]
+    fn mined_transactions_stay_mined_without_a_receipt() {
+        // The nonce has moved past it because it was mined, not replaced
+        let tx = included(100);
+        assert_eq!(without_receipt(&tx, 1, 111, &[]), recount(&tx, 111));
+        let confirmed = TrackedTx {
+            status: recount(&tx, 111),
+            ..tx
+        };
+        assert!(matches!(
+            without_receipt(&confirmed, 1, 200, &[]),
+            TxStatus::Confirmed { block_number: 100, .. }
+        ));
+    }
+
+    #[test]
+    fn unmined_transactions_without_a_receipt() {
+        let pending = TrackedTx {
+            status: TxStatus::Pending,
+            submitted_at: now(),
+            ..included(100)
+        };
+        assert_eq!(without_receipt(&pending, 0, 100, &[]), TxStatus::Pending);
+
+        let replacement = TrackedTx {
+            hash: B256::repeat_byte(3),
+            ..included(100)
+        };
+        assert_eq!(
+            without_receipt(&pending, 1, 100, &[pending.clone(), replacement.clone()]),
+            TxStatus::Replaced {
+                by: Some(replacement.hash)
+            }
+        );
+
+        let stale = TrackedTx {
+            submitted_at: 0,
+            ..pending
+        };
+        assert_eq!(without_receipt(&stale, 0, 100, &[]), TxStatus::Dropped);
+    }
+
+    #[test]
+    fn recount_leaves_unmined_transactions_alone() {
+        let tx = TrackedTx {
+            status: TxStatus::Pending,
+            ..included(100)
+        };
+        assert_eq!(recount(&tx, 200), TxStatus::Pending);
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use alloy::primitives::Address;
+use alloy::signers::local::coins_bip39::{
+    ChineseSimplified, ChineseTraditional, Czech, English, French, Italian, Japanese, Korean, Mnemonic,
+    Portuguese, Spanish, Wordlist,
+};
+use alloy::signers::local::{MnemonicBuilder, PrivateKeySigner};
+
+use crate::error::{KromeError, Result};
+
+// BIP-39 seed phrases and BIP-32 derivation along BIP-44 style paths. Paths
+// are templates where `{index}` stands for the account number.
+
+// MetaMask, Rabby, Trezor and most other wallets
+pub const BIP44_PATH: &str = "m/44'/60'/0'/0/{index}";
+// Ledger Live bumps the account level instead
+pub const LEDGER_LIVE_PATH: &str = "m/44'/60'/{index}'/0/0";
+// Ledger's old Chrome app and MyEtherWallet
+pub const LEDGER_LEGACY_PATH: &str = "m/44'/60'/0'/{index}";
+
+const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MnemonicLanguage {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+// Runs a function generic over the wordlist for the given language
+macro_rules! with_wordlist {
+    ($language:expr, $f:ident($($arg:expr),*)) => {
+        match $language {
+            MnemonicLanguage::English => $f::<English>($($arg),*),
+            MnemonicLanguage::ChineseSimplified => $f::<ChineseSimplified>($($arg),*),
+            MnemonicLanguage::ChineseTraditional => $f::<ChineseTraditional>($($arg),*),
+            MnemonicLanguage::Czech => $f::<Czech>($($arg),*),
+            MnemonicLanguage::French => $f::<French>($($arg),*),
+            MnemonicLanguage::Italian => $f::<Italian>($($arg),*),
+            MnemonicLanguage::Japanese => $f::<Japanese>($($arg),*),
+            MnemonicLanguage::Korean => $f::<Korean>($($arg),*),
+            MnemonicLanguage::Portuguese => $f::<Portuguese>($($arg),*),
+            MnemonicLanguage::Spanish => $f::<Spanish>($($arg),*),
+        }
+    };
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedAccount {
+    pub index: u32,
+    pub path: String,
+    pub address: Address,
+}
+
+fn generate_with<W: Wordlist>(word_count: usize) -> Result<String> {
+    let mnemonic = Mnemonic::<W>::new_with_count(&mut rand::thread_rng(), word_count)
+        .map_err(|e| KromeError::InvalidMnemonic(e.to_string()))?;
+    Ok(mnemonic.to_phrase())
+}
+
+fn validate_with<W: Wordlist>(phrase: &str) -> Result<()> {
+    Mnemonic::<W>::new_from_phrase(phrase)
+        .map(|_| ())
+        .map_err(|e| KromeError::InvalidMnemonic(e.to_string()))
+}
+
+fn derive_with<W: Wordlist>(phrase: &str, passphrase: Option<&str>, path: &str) -> Result<PrivateKeySigner> {
+    let mut builder = MnemonicBuilder::<W>::default()
+        .phrase(phrase)
+        .derivation_path(path)
+        .map_err(|e| KromeError::InvalidParams(format!("invalid derivation path {}: {}", path, e)))?;
+    if let Some(passphrase) = passphrase {
+        builder = builder.password(passphrase);
+    }
+    builder.build().map_err(|e| KromeError::InvalidMnemonic(e.to_string()))
+}
+
+pub fn generate(language: MnemonicLanguage, word_count: usize) -> Result<String> {
+    if !WORD_COUNTS.contains(&word_count) {
+        return Err(KromeError::InvalidParams(format!(
+            "word count must be one of {:?}",
+            WORD_COUNTS
+        )));
+    }
+    with_wordlist!(language, generate_with(word_count))
+}
+
+pub fn validate(phrase: &str, language: MnemonicLanguage) -> Result<()> {
+    with_wordlist!(language, validate_with(phrase.trim()))
+}
+
+// Fills `{index}` into a path template
+pub fn path_for(template: &str, index: u32) -> Result<String> {
+    if !template.contains("{index}") {
+        return Err(KromeError::InvalidParams(format!(
+            "derivation path {} has no {{index}} placeholder",
+            template
+        )));
+    }
+    Ok(template.replace("{index}", &index.to_string()))
+}
+
+pub fn derive(
+    phrase: &str,
+    language: MnemonicLanguage,
+    passphrase: Option<&str>,
+    template: &str,
+    index: u32,
+) -> Result<PrivateKeySigner> {
+    let path = path_for(template, index)?;
+    with_wordlist!(language, derive_with(phrase.trim(), passphrase, &path))
+}
+
+pub fn derive_accounts(
+    phrase: &str,
+    language: MnemonicLanguage,
+    passphrase: Option<&str>,
+    template: &str,
+    indices: impl IntoIterator<Item = u32>,
+) -> Result<Vec<DerivedAccount>> {
+    indices
+        .into_iter()
+        .map(|index| {
+            let signer = derive(phrase, language, passphrase, template, index)?;
+            Ok(DerivedAccount {
+                index,
+                path: path_for(template, index)?,
+                address: signer.address(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The mnemonic anvil and hardhat fund their dev accounts from
+    const DEV_PHRASE: &str = "test test test test test test test test test test test junk";
+
+    fn address(hex: &str) -> Address {
+        hex.parse().unwrap()
+    }
+
+    #[test]
+    fn seeds_match_the_bip39_vectors() {
+        let phrase = format!("{}about", "abandon ".repeat(11));
+        let seed = Mnemonic::<English>::new_from_phrase(&phrase).unwrap().to_seed(Some("TREZOR")).unwrap();
+        assert_eq!(
+            alloy::hex::encode(seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn derives_bip44_accounts() {
+        let accounts = derive_accounts(DEV_PHRASE, MnemonicLanguage::English, None, BIP44_PATH, 0..3).unwrap();
+        let addresses: Vec<Address> = accounts.iter().map(|account| account.address).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                address("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"),
+                address("0x70997970C51812dc3A010C7d01b50e0d17dc79C8"),
+                address("0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC"),
+            ]
+        );
+        assert_eq!(accounts[2].path, "m/44'/60'/0'/0/2");
+    }
+
+    #[test]
+    fn derives_along_other_paths() {
+        let derive_at = |template, index| {
+            derive(DEV_PHRASE, MnemonicLanguage::English, None, template, index)
+                .unwrap()
+                .address()
+        };
+        assert_eq!(derive_at(LEDGER_LIVE_PATH, 0), address("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"));
+        assert_eq!(derive_at(LEDGER_LIVE_PATH, 1), address("0x8C8d35429F74ec245F8Ef2f4Fd1e551cFF97d650"));
+        assert_eq!(derive_at(LEDGER_LEGACY_PATH, 1), address("0xc89D42189f0450C2b2c3c61f58Ec5d628176A1E7"));
+
+        // A passphrase gives a different wallet
+        let signer = derive(DEV_PHRASE, MnemonicLanguage::English, Some("krome"), BIP44_PATH, 0).unwrap();
+        assert_eq!(signer.address(), address("0xa29168512034795cfc4721ad9f4bf75663d4193e"));
+        assert!(matches!(
+            derive(DEV_PHRASE, MnemonicLanguage::English, None, "m/44'/60'/0'/0/0", 0),
+            Err(KromeError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn generates_and_validates_phrases() {
+        let phrase = generate(MnemonicLanguage::English, 24).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+        validate(&phrase, MnemonicLanguage::English).unwrap();
+        assert!(matches!(generate(MnemonicLanguage::English, 13), Err(KromeError::InvalidParams(_))));
+
+        // Twelve "abandon"s fail the checksum
+        let bad = "abandon ".repeat(12);
+        assert!(matches!(validate(&bad, MnemonicLanguage::English), Err(KromeError::InvalidMnemonic(_))));
+    }
+}
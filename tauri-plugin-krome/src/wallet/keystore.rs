@@ -0,0 +1,278 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+
+use alloy::hex;
+use alloy::primitives::{Address, B256};
+use alloy::signers::local::PrivateKeySigner;
+
+use crate::error::{KromeError, Result};
+use crate::secrets::{self, SecretStore};
+
+// Web3 Secret Storage v3 keystores, kept in the SecretStore under
+// `wallet/keystore/<address>`. Encryption is delegated to eth-keystore through
+// alloy: scrypt for new keystores, scrypt or pbkdf2 on import, AES-128-CTR
+// either way. The JSON is standard, with the address field geth expects, so
+// exported keystores work in geth and foundry.
+
+const KEY_PREFIX: &str = "wallet/keystore/";
+
+fn store_key(address: Address) -> String {
+    format!("{}{}", KEY_PREFIX, hex::encode(address))
+}
+
+// eth-keystore only works on files, so keystores pass through a scratch file
+// that is removed again when this is dropped. The contents are still
+// encrypted at that point.
+struct ScratchFile(PathBuf);
+
+impl ScratchFile {
+    fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ScratchFile(dir.join(format!(".{}.tmp", hex::encode(rand::random::<[u8; 8]>())))))
+    }
+
+    fn name(&self) -> String {
+        self.0.file_name().unwrap_or_default().to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+pub fn list(store: &dyn SecretStore) -> Result<Vec<Address>> {
+    Ok(store
+        .keys(KEY_PREFIX)?
+        .iter()
+        .filter_map(|key| key.strip_prefix(KEY_PREFIX))
+        .filter_map(|hex| format!("0x{}", hex).parse().ok())
+        .collect())
+}
+
+// The keystore JSON for an account
+pub fn find(store: &dyn SecretStore, address: Address) -> Result<String> {
+    secrets::get_string(store, &store_key(address))?.ok_or(KromeError::AccountNotFound(address))
+}
+
+pub fn decrypt(scratch_dir: &Path, json: &str, password: &str) -> Result<PrivateKeySigner> {
+    let scratch = ScratchFile::new(scratch_dir)?;
+    fs::write(&scratch.0, json)?;
+    Ok(PrivateKeySigner::decrypt_keystore(&scratch.0, password)?)
+}
+
+// Decrypts the keystore stored for `address`. Ones moved in by migrate_dir
+// are filed under the address their JSON claims, which can only be checked
+// against the key once it's decrypted.
+pub fn open(store: &dyn SecretStore, scratch_dir: &Path, address: Address, password: &str) -> Result<PrivateKeySigner> {
+    let signer = decrypt(scratch_dir, &find(store, address)?, password)?;
+    if signer.address() != address {
+        return Err(KromeError::Keystore(format!(
+            "keystore stored for {} holds the key for {}",
+            address,
+            signer.address()
+        )));
+    }
+    Ok(signer)
+}
+
+// Encrypts a key into a new keystore and returns its signer
+pub fn encrypt(
+    store: &dyn SecretStore,
+    scratch_dir: &Path,
+    private_key: B256,
+    password: &str,
+) -> Result<PrivateKeySigner> {
+    let signer = PrivateKeySigner::from_bytes(&private_key)
+        .map_err(|e| KromeError::InvalidParams(format!("invalid private key: {}", e)))?;
+    if list(store)?.contains(&signer.address()) {
+        return Err(KromeError::AccountExists(signer.address()));
+    }
+
+    let scratch = ScratchFile::new(scratch_dir)?;
+    PrivateKeySigner::encrypt_keystore(
+        scratch_dir,
+        &mut rand::thread_rng(),
+        private_key,
+        password,
+        Some(&scratch.name()),
+    )?;
+    save(store, signer.address(), &fs::read_to_string(&scratch.0)?)?;
+    Ok(signer)
+}
+
+// Stores a keystore made elsewhere, once the password has been checked against it
+pub fn import(store: &dyn SecretStore, scratch_dir: &Path, json: &str, password: &str) -> Result<PrivateKeySigner> {
+    serde_json::from_str::<Value>(json)
+        .map_err(|e| KromeError::Keystore(format!("not a keystore file: {}", e)))?;
+
+    let signer = decrypt(scratch_dir, json, password)?;
+    if list(store)?.contains(&signer.address()) {
+        return Err(KromeError::AccountExists(signer.address()));
+    }
+    save(store, signer.address(), json)?;
+    Ok(signer)
+}
+
+// eth-keystore leaves out the address field geth relies on, so it's added here
+fn save(store: &dyn SecretStore, address: Address, json: &str) -> Result<()> {
+    let mut json: Value = serde_json::from_str(json)?;
+    json["address"] = Value::from(hex::encode(address));
+    store.set(&store_key(address), serde_json::to_string(&json)?.as_bytes())
+}
+
+// Moves keystore files left in `dir` by older versions into the store. There's
+// no password here, so each is filed under the address in its JSON and open
+// checks that against the key later. Files for accounts already in the store
+// are left where they are rather than replacing them.
+pub fn migrate_dir(store: &dyn SecretStore, dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let existing = list(store)?;
+    for entry in fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let address = serde_json::from_str::<Value>(&contents)
+            .ok()
+            .and_then(|json| json.get("address")?.as_str().map(str::to_string))
+            .and_then(|address| format!("0x{}", address.trim_start_matches("0x")).parse::<Address>().ok());
+        if let Some(address) = address.filter(|address| !existing.contains(address)) {
+            save(store, address, &contents)?;
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::{MemoryStore, TempDir};
+
+    // The Web3 Secret Storage test vectors: both keystores hold this key,
+    // encrypted with "testpassword"
+    const PASSWORD: &str = "testpassword";
+    const PRIVATE_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+    const ADDRESS: &str = "0x008aeeda4d805471df9b2a5b0f38a0c3bcba786b";
+
+    const PBKDF2_KEYSTORE: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": {
+                "c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256",
+                "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    // As geth and foundry write it, with the address field
+    const GETH_KEYSTORE: &str = r#"{
+        "address": "008aeeda4d805471df9b2a5b0f38a0c3bcba786b",
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+            "ciphertext": "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf": "scrypt",
+            "kdfparams": {
+                "dklen": 32,
+                "n": 262144,
+                "p": 8,
+                "r": 1,
+                "salt": "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+            },
+            "mac": "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    fn address() -> Address {
+        ADDRESS.parse().unwrap()
+    }
+
+    #[test]
+    fn decrypts_the_spec_vectors() {
+        let dir = TempDir::new("krome-keystore");
+        for json in [PBKDF2_KEYSTORE, GETH_KEYSTORE] {
+            let signer = decrypt(&dir.0, json, PASSWORD).unwrap();
+            assert_eq!(hex::encode(signer.to_bytes()), PRIVATE_KEY);
+            assert_eq!(signer.address(), address());
+        }
+        assert!(matches!(
+            decrypt(&dir.0, PBKDF2_KEYSTORE, "wrong"),
+            Err(KromeError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn imports_and_exports_a_geth_keystore() {
+        let dir = TempDir::new("krome-keystore");
+        let store = MemoryStore::default();
+        import(&store, &dir.0, GETH_KEYSTORE, PASSWORD).unwrap();
+        assert_eq!(list(&store).unwrap(), vec![address()]);
+        assert!(matches!(
+            import(&store, &dir.0, PBKDF2_KEYSTORE, PASSWORD),
+            Err(KromeError::AccountExists(_))
+        ));
+
+        let json: Value = serde_json::from_str(&find(&store, address()).unwrap()).unwrap();
+        assert_eq!(json["address"], "008aeeda4d805471df9b2a5b0f38a0c3bcba786b");
+        assert_eq!(open(&store, &dir.0, address(), PASSWORD).unwrap().address(), address());
+    }
+
+    #[test]
+    fn encrypts_keystores_other_tools_can_read() {
+        let dir = TempDir::new("krome-keystore");
+        let store = MemoryStore::default();
+        let key: B256 = PRIVATE_KEY.parse().unwrap();
+        encrypt(&store, &dir.0, key, "hunter2").unwrap();
+
+        let json = find(&store, address()).unwrap();
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["version"], 3);
+        assert_eq!(parsed["crypto"]["kdf"], "scrypt");
+        assert_eq!(decrypt(&dir.0, &json, "hunter2").unwrap().to_bytes(), key);
+    }
+
+    #[test]
+    fn migrated_keystores_are_checked_against_their_key() {
+        let dir = TempDir::new("krome-keystore");
+        let legacy = dir.0.join("legacy");
+        fs::create_dir_all(&legacy).unwrap();
+        // Claims an address the key doesn't derive
+        let other = Address::repeat_byte(0x11);
+        let mislabeled = GETH_KEYSTORE.replace(&ADDRESS[2..], &hex::encode(other));
+        fs::write(legacy.join("mislabeled.json"), &mislabeled).unwrap();
+        fs::write(legacy.join("notes.txt"), "not a keystore").unwrap();
+
+        let store = MemoryStore::default();
+        migrate_dir(&store, &legacy).unwrap();
+        assert_eq!(list(&store).unwrap(), vec![other]);
+        assert!(!legacy.join("mislabeled.json").exists());
+        assert!(legacy.join("notes.txt").exists());
+        assert!(matches!(
+            open(&store, &dir.0, other, PASSWORD),
+            Err(KromeError::Keystore(_))
+        ));
+
+        // A file for an account that's already stored doesn't replace it
+        fs::write(legacy.join("again.json"), GETH_KEYSTORE.replace(&ADDRESS[2..], &hex::encode(other))).unwrap();
+        store.set(&store_key(other), b"{}").unwrap();
+        migrate_dir(&store, &legacy).unwrap();
+        assert_eq!(find(&store, other).unwrap(), "{}");
+        assert!(legacy.join("again.json").exists());
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use alloy::primitives::{Address, B256};
+use alloy::signers::local::PrivateKeySigner;
+
+use crate::error::{KromeError, Result};
+use crate::secrets::{self, SharedSecretStore};
+use crate::Config;
+use hd::{DerivedAccount, MnemonicLanguage};
+
+pub mod hd;
+pub mod keystore;
+
+// Embedded wallet. Accounts are kept as encrypted keystores in the
+// SecretStore and only held decrypted in memory between unlock and lock. An
+// unlocked account locks itself again after going unused for the auto-lock
+// timeout.
+
+// Sent with the address whenever an account locks, manually or by timeout
+pub const WALLET_LOCKED_EVENT: &str = "helios://wallet-locked";
+
+// Where keystores used to be stored, now only a scratch dir for eth-keystore
+const KEYSTORE_DIR: &str = "keystore";
+const AUTO_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Most addresses derive_addresses returns at once
+const MAX_DERIVED_ADDRESSES: u32 = 100;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    pub address: Address,
+    pub unlocked: bool,
+}
+
+impl AccountInfo {
+    // New and imported accounts start out locked
+    fn locked(address: Address) -> Self {
+        AccountInfo {
+            address,
+            unlocked: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletLocked {
+    pub address: Address,
+}
+
+struct UnlockedAccount {
+    signer: PrivateKeySigner,
+    // None keeps it unlocked until lock_account
+    timeout: Option<Duration>,
+    last_used: Instant,
+}
+
+pub struct WalletState {
+    store: SharedSecretStore,
+    scratch_dir: PathBuf,
+    default_timeout: Duration,
+    unlocked: Mutex<HashMap<Address, UnlockedAccount>>,
+}
+
+impl WalletState {
+    pub fn new(store: SharedSecretStore, data_dir: &Path, default_timeout: Duration) -> Result<Self> {
+        let scratch_dir = data_dir.join(KEYSTORE_DIR);
+        keystore::migrate_dir(store.as_ref(), &scratch_dir)?;
+        Ok(WalletState {
+            store,
+            scratch_dir,
+            default_timeout,
+            unlocked: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn accounts(&self) -> Result<Vec<AccountInfo>> {
+        let unlocked = self.unlocked.lock().unwrap();
+        Ok(keystore::list(self.store.as_ref())?
+            .into_iter()
+            .map(|address| AccountInfo {
+                address,
+                unlocked: unlocked.contains_key(&address),
+            })
+            .collect())
+    }
+
+    pub fn create(&self, password: &str) -> Result<Address> {
+        let key = PrivateKeySigner::random().to_bytes();
+        self.import_private_key(key, password)
+    }
+
+    pub fn import_private_key(&self, private_key: B256, password: &str) -> Result<Address> {
+        Ok(keystore::encrypt(self.store.as_ref(), &self.scratch_dir, private_key, password)?.address())
+    }
+
+    pub fn import_keystore(&self, json: &str, password: &str) -> Result<Address> {
+        Ok(keystore::import(self.store.as_ref(), &self.scratch_dir, json, password)?.address())
+    }
+
+    // Standard keystore JSON, usable by geth, foundry and others. The password
+    // is checked first so the encrypted key can't be taken for offline guessing.
+    pub fn export_keystore(&self, address: Address, password: &str) -> Result<String> {
+        keystore::open(self.store.as_ref(), &self.scratch_dir, address, password)?;
+        keystore::find(self.store.as_ref(), address)
+    }
+
+    // Decrypts an account and keeps it unlocked. A timeout of 0 disables
+    // auto-lock; None uses the plugin's default.
+    pub fn unlock(&self, address: Address, password: &str, timeout_secs: Option<u64>) -> Result<()> {
+        let signer = keystore::open(self.store.as_ref(), &self.scratch_dir, address, password)?;
+        let timeout = match timeout_secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(self.default_timeout).filter(|t| !t.is_zero()),
+        };
+        self.unlocked.lock().unwrap().insert(
+            address,
+            UnlockedAccount {
+                signer,
+                timeout,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self, address: Address) -> bool {
+        self.unlocked.lock().unwrap().contains_key(&address)
+    }
+
+    pub fn unlocked_accounts(&self) -> Vec<Address> {
+        let mut accounts: Vec<Address> = self.unlocked.lock().unwrap().keys().copied().collect();
+        accounts.sort();
+        accounts
+    }
+
+    // Returns whether it was unlocked
+    pub fn lock(&self, address: Address) -> bool {
+        self.unlocked.lock().unwrap().remove(&address).is_some()
+    }
+
+    pub fn lock_all(&self) -> Vec<Address> {
+        self.unlocked.lock().unwrap().drain().map(|(address, _)| address).collect()
+    }
+
+    // Signer for an unlocked account. Using it restarts the auto-lock timer.
+    pub fn signer(&self, address: Address) -> Result<PrivateKeySigner> {
+        let mut unlocked = self.unlocked.lock().unwrap();
+        let account = unlocked.get_mut(&address).ok_or(KromeError::AccountLocked(address))?;
+        account.last_used = Instant::now();
+        Ok(account.signer.clone())
+    }
+
+    // Locks accounts whose timeout has passed and returns them
+    fn lock_expired(&self) -> Vec<Address> {
+        let now = Instant::now();
+        let mut unlocked = self.unlocked.lock().unwrap();
+        let expired: Vec<Address> = unlocked
+            .iter()
+            .filter(|(_, account)| account.timeout.is_some_and(|t| now.duration_since(account.last_used) >= t))
+            .map(|(address, _)| *address)
+            .collect();
+        for address in &expired {
+            unlocked.remove(address);
+        }
+        expired
+    }
+}
+
+fn emit_locked<R: Runtime>(app_handle: &AppHandle<R>, addresses: Vec<Address>) {
+    for address in addresses {
+        let _ = app_handle.emit(WALLET_LOCKED_EVENT, WalletLocked { address });
+    }
+}
+
+// Enforces auto-lock for as long as the app runs
+pub(crate) async fn watch<R: Runtime>(app_handle: AppHandle<R>) {
+    let mut ticker = tokio::time::interval(AUTO_LOCK_CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let expired = app_handle.state::<WalletState>().lock_expired();
+        emit_locked(&app_handle, expired);
+    }
+}
+
+// Runs `f` off the async runtime, since keystore encryption is slow on
+// purpose and keychain access can block
+async fn with_wallet<R: Runtime, T: Send + 'static>(
+    app_handle: AppHandle<R>,
+    f: impl FnOnce(&WalletState) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    secrets::blocking(move || f(&app_handle.state::<WalletState>())).await
+}
+
+pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>, store: SharedSecretStore, data_dir: &Path) -> Result<WalletState> {
+    let timeout = Duration::from_secs(app_handle.state::<Config>().wallet_auto_lock_secs);
+    WalletState::new(store, data_dir, timeout)
+}
+
+#[tauri::command]
+pub(crate) async fn list_accounts<R: Runtime>(app_handle: AppHandle<R>) -> Result<Vec<AccountInfo>> {
+    with_wallet(app_handle, |wallet| wallet.accounts()).await
+}
+
+#[tauri::command]
+pub(crate) async fn create_account<R: Runtime>(app_handle: AppHandle<R>, password: String) -> Result<AccountInfo> {
+    let address = with_wallet(app_handle, move |wallet| wallet.create(&password)).await?;
+    Ok(AccountInfo::locked(address))
+}
+
+#[tauri::command]
+pub(crate) async fn import_private_key<R: Runtime>(
+    app_handle: AppHandle<R>,
+    private_key: B256,
+    password: String,
+) -> Result<AccountInfo> {
+    let address = with_wallet(app_handle, move |wallet| wallet.import_private_key(private_key, &password)).await?;
+    Ok(AccountInfo::locked(address))
+}
+
+// Takes the keystore JSON. Reading and writing keystore files is left to the
+// front end, which can only reach the ones the user picks.
+#[tauri::command]
+pub(crate) async fn import_keystore<R: Runtime>(
+    app_handle: AppHandle<R>,
+    json: String,
+    password: String,
+) -> Result<AccountInfo> {
+    let address = with_wallet(app_handle, move |wallet| wallet.import_keystore(&json, &password)).await?;
+    Ok(AccountInfo::locked(address))
+}
+
+// Returns the keystore JSON once `password` has been checked against it
+#[tauri::command]
+pub(crate) async fn export_keystore<R: Runtime>(
+    app_handle: AppHandle<R>,
+    address: Address,
+    password: String,
+) -> Result<String> {
+    with_wallet(app_handle, move |wallet| wallet.export_keystore(address, &password)).await
+}
+
+#[tauri::command]
+pub(crate) async fn unlock_account<R: Runtime>(
+    app_handle: AppHandle<R>,
+    address: Address,
+    password: String,
+    timeout_secs: Option<u64>,
+) -> Result<()> {
+    with_wallet(app_handle, move |wallet| wallet.unlock(address, &password, timeout_secs)).await
+}
+
+#[tauri::command]
+pub(crate) async fn lock_account<R: Runtime>(
+    app_handle: AppHandle<R>,
+    wallet: State<'_, WalletState>,
+    address: Option<Address>,
+) -> Result<()> {
+    let locked = match address {
+        Some(address) => Some(address).filter(|a| wallet.lock(*a)).into_iter().collect(),
+        None => wallet.lock_all(),
+    };
+    emit_locked(&app_handle, locked);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn generate_mnemonic(language: Option<MnemonicLanguage>, word_count: Option<usize>) -> Result<String> {
+    hd::generate(language.unwrap_or_default(), word_count.unwrap_or(12))
+}
+
+#[tauri::command]
+pub(crate) async fn validate_mnemonic(phrase: String, language: Option<MnemonicLanguage>) -> Result<bool> {
+    Ok(hd::validate(&phrase, language.unwrap_or_default()).is_ok())
+}
+
+// Addresses at `count` consecutive indices from `start`, for picking which
+// accounts to restore. `path` defaults to the BIP-44 one MetaMask uses.
+#[tauri::command]
+pub(crate) async fn derive_addresses(
+    phrase: String,
+    language: Option<MnemonicLanguage>,
+    passphrase: Option<String>,
+    path: Option<String>,
+    start: Option<u32>,
+    count: Option<u32>,
+) -> Result<Vec<DerivedAccount>> {
+    let language = language.unwrap_or_default();
+    hd::validate(&phrase, language)?;
+    let start = start.unwrap_or(0);
+    let count = count.unwrap_or(10).min(MAX_DERIVED_ADDRESSES);
+    // PBKDF2 seed derivation is slow, keep it off the async runtime
+    secrets::blocking(move || {
+        hd::derive_accounts(
+            &phrase,
+            language,
+            passphrase.as_deref(),
+            path.as_deref().unwrap_or(hd::BIP44_PATH),
+            start..start.saturating_add(count),
+        )
+    })
+    .await
+}
+
+// Derives the accounts at `indices` and stores each as a keystore encrypted
+// with `password`
+#[tauri::command]
+pub(crate) async fn import_mnemonic<R: Runtime>(
+    app_handle: AppHandle<R>,
+    phrase: String,
+    language: Option<MnemonicLanguage>,
+    passphrase: Option<String>,
+    path: Option<String>,
+    indices: Vec<u32>,
+    password: String,
+) -> Result<Vec<AccountInfo>> {
+    let language = language.unwrap_or_default();
+    hd::validate(&phrase, language)?;
+
+    // Seed derivation and keystore encryption both run off the async runtime
+    with_wallet(app_handle, move |wallet| {
+        // Derive everything first so a bad index doesn't leave a partial import
+        let template = path.as_deref().unwrap_or(hd::BIP44_PATH);
+        let signers = indices
+            .into_iter()
+            .map(|index| hd::derive(&phrase, language, passphrase.as_deref(), template, index))
+            .collect::<Result<Vec<_>>>()?;
+        signers
+            .into_iter()
+            .map(|signer| Ok(AccountInfo::locked(wallet.import_private_key(signer.to_bytes(), &password)?)))
+            .collect()
+    })
+    .await
+}